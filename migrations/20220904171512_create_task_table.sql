@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS tasks (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  title varchar(255) NOT NULL,
+  description text,
+  completed boolean NOT NULL default false,
+  created_at timestamptz NOT NULL default now(),
+  updated_at timestamptz NOT NULL default now()
+);
+
+CREATE INDEX IF NOT EXISTS tasks_user_id_idx ON tasks(user_id);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS lists (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name varchar(100) NOT NULL,
+  created_at timestamptz NOT NULL default now(),
+  updated_at timestamptz NOT NULL default now(),
+  UNIQUE(user_id, name)
+);
+
+-- Deleting a list orphans its tasks unless they are deleted explicitly beforehand
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS list_id uuid REFERENCES lists(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS tasks_list_id_idx ON tasks(list_id);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- Every token rotated from the same login shares a family
+  family_id uuid NOT NULL,
+  token_hash varchar(64) UNIQUE NOT NULL,
+  replaced_by uuid REFERENCES refresh_tokens(id) ON DELETE SET NULL,
+  revoked_at timestamptz,
+  expires_at timestamptz NOT NULL,
+  created_at timestamptz NOT NULL default now()
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id_idx ON refresh_tokens(family_id);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+  jti uuid,
+  PRIMARY KEY(jti),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- Expiration of the revoked token, the row is useless afterwards
+  expires_at timestamptz NOT NULL,
+  revoked_at timestamptz NOT NULL default now()
+);
+
+CREATE INDEX IF NOT EXISTS revoked_tokens_expires_at_idx ON revoked_tokens(expires_at);
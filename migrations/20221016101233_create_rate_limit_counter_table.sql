@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS rate_limit_counters (
+  key varchar(255),
+  PRIMARY KEY(key),
+  count integer NOT NULL,
+  -- End of the current window, the counter restarts afterwards
+  expires_at timestamptz NOT NULL,
+  updated_at timestamptz NOT NULL default now()
+);
+
+CREATE INDEX IF NOT EXISTS rate_limit_counters_expires_at_idx ON rate_limit_counters(expires_at);
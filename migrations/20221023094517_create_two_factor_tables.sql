@@ -0,0 +1,30 @@
+CREATE TABLE IF NOT EXISTS user_totp (
+  user_id uuid,
+  PRIMARY KEY(user_id),
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+  secret varchar(64) NOT NULL,
+  enabled_at timestamptz,
+  last_used_step bigint,
+  created_at timestamptz NOT NULL default now()
+);
+
+CREATE TABLE IF NOT EXISTS recovery_codes (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  code_hash text NOT NULL,
+  used_at timestamptz,
+  created_at timestamptz NOT NULL default now()
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_user_id_idx ON recovery_codes(user_id);
+
+CREATE TABLE IF NOT EXISTS two_factor_challenges (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  token_hash varchar(64) UNIQUE NOT NULL,
+  attempts integer NOT NULL default 0,
+  expires_at timestamptz NOT NULL,
+  created_at timestamptz NOT NULL default now()
+);
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS user_identities (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  provider varchar(50) NOT NULL,
+  subject varchar(255) NOT NULL,
+  email text,
+  last_login_at timestamptz,
+  created_at timestamptz NOT NULL default now(),
+  UNIQUE(provider, subject),
+  UNIQUE(user_id, provider)
+);
+
+CREATE TABLE IF NOT EXISTS oidc_states (
+  id uuid,
+  PRIMARY KEY(id),
+  state_hash varchar(64) UNIQUE NOT NULL,
+  provider varchar(50) NOT NULL,
+  nonce varchar(64) NOT NULL,
+  code_verifier varchar(128) NOT NULL,
+  user_id uuid REFERENCES users(id) ON DELETE CASCADE,
+  expires_at timestamptz NOT NULL,
+  created_at timestamptz NOT NULL default now()
+);
@@ -0,0 +1,2 @@
+-- Hashes made with higher argon2 costs or a pepper key id do not fit in 96 characters
+ALTER TABLE users ALTER COLUMN password_hash TYPE text;
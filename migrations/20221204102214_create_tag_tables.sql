@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS tags (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  name varchar(50) NOT NULL,
+  created_at timestamptz NOT NULL default now(),
+  updated_at timestamptz NOT NULL default now(),
+  UNIQUE(user_id, name)
+);
+
+-- Deleting a task or a tag detaches them
+CREATE TABLE IF NOT EXISTS task_tags (
+  task_id uuid NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+  tag_id uuid NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+  PRIMARY KEY(task_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS task_tags_tag_id_idx ON task_tags(tag_id);
@@ -0,0 +1,45 @@
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS due_at timestamptz;
+
+DO $$ BEGIN
+  CREATE TYPE notification_channel AS ENUM ('email', 'webhook', 'in_app');
+EXCEPTION
+  WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS task_reminders (
+  id uuid,
+  PRIMARY KEY(id),
+  task_id uuid NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  remind_at timestamptz NOT NULL,
+  -- IANA time zone the reminder was set in, its local time is shown in that zone
+  timezone text NOT NULL,
+  channel notification_channel NOT NULL,
+  webhook_url text,
+  -- Claimed reminders are pushed back by a lease, failed attempts by a backoff
+  next_attempt_at timestamptz NOT NULL,
+  attempts integer NOT NULL default 0,
+  last_error text,
+  delivered_at timestamptz,
+  -- Set once every attempt failed
+  failed_at timestamptz,
+  created_at timestamptz NOT NULL default now()
+);
+
+CREATE INDEX IF NOT EXISTS task_reminders_task_id_idx ON task_reminders(task_id);
+CREATE INDEX IF NOT EXISTS task_reminders_pending_idx ON task_reminders(next_attempt_at)
+  WHERE delivered_at IS NULL AND failed_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS notifications (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- A reminder delivered again does not notify twice
+  reminder_id uuid UNIQUE REFERENCES task_reminders(id) ON DELETE SET NULL,
+  title text NOT NULL,
+  body text NOT NULL,
+  created_at timestamptz NOT NULL default now(),
+  read_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS notifications_user_id_idx ON notifications(user_id);
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS task_recurrences (
+  id uuid,
+  PRIMARY KEY(id),
+  user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- Canonical RRULE (RFC 5545), such as FREQ=WEEKLY;BYDAY=MO,TH
+  rule text NOT NULL,
+  -- IANA time zone occurrences keep their local time in
+  timezone text NOT NULL,
+  -- Due date of the first task of the series, which the rule counts from
+  starts_at timestamptz NOT NULL,
+  created_at timestamptz NOT NULL default now()
+);
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS recurrence_id uuid REFERENCES task_recurrences(id) ON DELETE SET NULL;
+
+-- Completing an occurrence again does not create the next one twice
+CREATE UNIQUE INDEX IF NOT EXISTS tasks_recurrence_id_due_at_idx ON tasks(recurrence_id, due_at);
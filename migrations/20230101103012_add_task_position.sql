@@ -0,0 +1,14 @@
+-- Rank of the task in its list, compared byte by byte
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS position text COLLATE "C";
+
+-- Existing tasks keep their creation order
+UPDATE tasks SET position = ranked.position
+FROM (
+    SELECT id, lpad((row_number() OVER (PARTITION BY user_id, list_id ORDER BY created_at, id))::text, 9, '0') || 'V' AS position
+    FROM tasks
+) AS ranked
+WHERE tasks.id = ranked.id AND tasks.position IS NULL;
+
+ALTER TABLE tasks ALTER COLUMN position SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS tasks_position_idx ON tasks(user_id, list_id, position);
@@ -0,0 +1,2 @@
+-- Lets the cleanup task find the lists to rebalance without scanning every task
+CREATE INDEX IF NOT EXISTS tasks_position_length_idx ON tasks(length(position));
@@ -0,0 +1,2 @@
+-- Sealed secrets are longer than the base32 ones they replace
+ALTER TABLE user_totp ALTER COLUMN secret TYPE text;
@@ -0,0 +1,2 @@
+-- Lets emails be looked up whatever their case
+CREATE INDEX IF NOT EXISTS users_lower_email_idx ON users(lower(email));
@@ -1,11 +1,518 @@
+use chrono::{DateTime, Utc};
 use config::{Config, ConfigError, File, FileFormat};
 use serde::Deserialize;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct AppSettings {
     pub host: String,
     pub port: u16,
-    pub jwt_secret: String,
+    /// Secret of HS256 tokens, used to sign when no `jwt_keys` are set and to
+    /// verify tokens issued before switching to `jwt_keys`
+    #[serde(default)]
+    pub jwt_secret: Option<String>,
+    /// Once `jwt_keys` sign tokens, HS256 tokens are only verified until this
+    /// date, and not at all when unset
+    #[serde(default)]
+    pub jwt_secret_verify_until: Option<DateTime<Utc>>,
+    /// Asymmetric keys signing and verifying access tokens, published on
+    /// `/.well-known/jwks.json`
+    #[serde(default)]
+    pub jwt_keys: Vec<JwtKeySettings>,
+    #[serde(default = "default_access_token_ttl_minutes")]
+    pub access_token_ttl_minutes: i64,
+    #[serde(default = "default_refresh_token_ttl_days")]
+    pub refresh_token_ttl_days: i64,
+    #[serde(default = "default_password_reset_ttl_minutes")]
+    pub password_reset_ttl_minutes: i64,
+    /// Base url of the client application, used to build links sent by email
+    #[serde(default = "default_public_url")]
+    pub public_url: String,
+    #[serde(default)]
+    pub email_verification: EmailVerificationPolicy,
+    #[serde(default = "default_email_verification_ttl_hours")]
+    pub email_verification_ttl_hours: i64,
+    #[serde(default = "default_email_verification_resend_cooldown_seconds")]
+    pub email_verification_resend_cooldown_seconds: i64,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+    /// How access and refresh tokens are handed to clients
+    #[serde(default)]
+    pub session_transport: SessionTransport,
+    #[serde(default)]
+    pub session_cookie: SessionCookieSettings,
+    /// Name shown by authenticator apps next to the account
+    #[serde(default = "default_totp_issuer")]
+    pub totp_issuer: String,
+    /// Key encrypting the TOTP secrets at rest, 32 bytes encoded in base64
+    pub totp_encryption_key: String,
+    /// Time given to enter the 2FA code once the password was checked
+    #[serde(default = "default_two_factor_challenge_ttl_minutes")]
+    pub two_factor_challenge_ttl_minutes: i64,
+    /// Time left to users to change their mind once they asked for their
+    /// account to be deleted
+    #[serde(default = "default_account_deletion_grace_period_days")]
+    pub account_deletion_grace_period_days: i64,
+    /// Identity providers users can log in with, through OpenID Connect
+    #[serde(default)]
+    pub oidc_providers: Vec<OidcProviderSettings>,
+    /// Time given to log in on the identity provider once the login started
+    #[serde(default = "default_oidc_state_ttl_minutes")]
+    pub oidc_state_ttl_minutes: i64,
+    #[serde(default)]
+    pub password_hashing: PasswordHashingSettings,
+    /// Rules new passwords have to follow
+    #[serde(default)]
+    pub password_policy: PasswordPolicySettings,
+    #[serde(default)]
+    pub reminders: ReminderSettings,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcProviderSettings {
+    /// Names the provider in urls, such as `/api/users/oidc/{name}/authorize`
+    pub name: String,
+    /// The provider metadata is discovered from `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    /// Public clients without a secret only rely on PKCE
+    pub client_secret: Option<String>,
+    /// Page of the client application the provider redirects to, it hands the
+    /// code and the state over to the api
+    pub redirect_uri: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JwtKeySettings {
+    /// Key id, set in the header of the tokens signed with the key
+    pub kid: String,
+    pub algorithm: JwtAlgorithm,
+    /// Keys without a private key only verify tokens
+    pub private_key_path: Option<String>,
+    pub public_key_path: String,
+    #[serde(default)]
+    pub status: JwtKeyStatus,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    RS256,
+    EdDSA,
+}
+
+/// Keys are rotated by adding the new key first, then retiring the old one once
+/// the tokens it signed have expired
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JwtKeyStatus {
+    /// Published and verifying tokens, the first active key with a private key
+    /// signs new tokens
+    #[default]
+    Active,
+    /// Neither published nor verifying tokens anymore
+    Retired,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionTransport {
+    /// Tokens are returned in the response body and sent back in the
+    /// `Authorization` header
+    #[default]
+    Header,
+    /// Tokens are only set in HttpOnly cookies, for browser clients
+    Cookie,
+    /// Tokens are both returned and set in cookies, either way is accepted
+    Both,
+}
+
+impl SessionTransport {
+    pub fn uses_header(&self) -> bool {
+        matches!(self, SessionTransport::Header | SessionTransport::Both)
+    }
+
+    pub fn uses_cookie(&self) -> bool {
+        matches!(self, SessionTransport::Cookie | SessionTransport::Both)
+    }
+}
+
+/// Argon2id costs of password hashes, existing hashes are upgraded when their
+/// users log in
+#[derive(Deserialize, Debug, Clone)]
+pub struct PasswordHashingSettings {
+    #[serde(default = "default_argon2_memory_cost_kib")]
+    pub memory_cost_kib: u32,
+    /// Number of passes over the memory
+    #[serde(default = "default_argon2_time_cost")]
+    pub time_cost: u32,
+    #[serde(default = "default_argon2_parallelism")]
+    pub parallelism: u32,
+    /// Secret mixed into every hash and kept out of the database. Once set, it
+    /// cannot be removed or changed without resetting the passwords.
+    pub pepper: Option<String>,
+    /// Hashes computed at the same time, the number of cpus by default
+    #[serde(default = "default_hashing_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Time a request waits for a hashing slot before being answered 503
+    #[serde(default = "default_hashing_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+}
+
+impl Default for PasswordHashingSettings {
+    fn default() -> Self {
+        Self {
+            memory_cost_kib: default_argon2_memory_cost_kib(),
+            time_cost: default_argon2_time_cost(),
+            parallelism: default_argon2_parallelism(),
+            pepper: None,
+            max_concurrency: default_hashing_max_concurrency(),
+            queue_timeout_ms: default_hashing_queue_timeout_ms(),
+        }
+    }
+}
+
+fn default_argon2_memory_cost_kib() -> u32 {
+    argon2::Params::DEFAULT_M_COST
+}
+
+fn default_argon2_time_cost() -> u32 {
+    argon2::Params::DEFAULT_T_COST
+}
+
+fn default_argon2_parallelism() -> u32 {
+    argon2::Params::DEFAULT_P_COST
+}
+
+fn default_hashing_max_concurrency() -> usize {
+    std::thread::available_parallelism().map_or(1, usize::from)
+}
+
+fn default_hashing_queue_timeout_ms() -> u64 {
+    2000
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PasswordPolicySettings {
+    #[serde(default = "default_password_min_length")]
+    pub min_length: usize,
+    #[serde(default = "default_password_max_length")]
+    pub max_length: usize,
+    #[serde(default)]
+    pub require_lowercase: bool,
+    #[serde(default)]
+    pub require_uppercase: bool,
+    #[serde(default)]
+    pub require_digit: bool,
+    #[serde(default)]
+    pub require_symbol: bool,
+    /// Rejects passwords containing the username or the email of the user
+    #[serde(default = "default_reject_user_info")]
+    pub reject_user_info: bool,
+    /// Estimated strength required, from 0 (anything goes) to 4
+    #[serde(default = "default_password_min_strength")]
+    pub min_strength: u8,
+    /// Directory of SHA-1 hashes of breached passwords, one file per 5
+    /// characters prefix as served by the HaveIBeenPwned range api
+    pub breached_passwords_dir: Option<String>,
+}
+
+impl Default for PasswordPolicySettings {
+    fn default() -> Self {
+        Self {
+            min_length: default_password_min_length(),
+            max_length: default_password_max_length(),
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            reject_user_info: default_reject_user_info(),
+            min_strength: default_password_min_strength(),
+            breached_passwords_dir: None,
+        }
+    }
+}
+
+fn default_password_min_length() -> usize {
+    8
+}
+
+fn default_password_max_length() -> usize {
+    128
+}
+
+fn default_password_min_strength() -> u8 {
+    2
+}
+
+fn default_reject_user_info() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReminderSettings {
+    /// Time between two lookups of due reminders
+    #[serde(default = "default_reminder_poll_interval_seconds")]
+    pub poll_interval_seconds: u64,
+    /// Reminders sent at most on each lookup
+    #[serde(default = "default_reminder_batch_size")]
+    pub batch_size: i64,
+    /// Reminders of a batch delivered at once
+    #[serde(default = "default_reminder_concurrency")]
+    pub concurrency: usize,
+    /// Time a reminder is left to the worker that claimed it before another
+    /// worker sends it again, it must cover the delivery of a whole batch
+    #[serde(default = "default_reminder_lease_seconds")]
+    pub lease_seconds: u64,
+    /// Deliveries tried before giving up on a reminder
+    #[serde(default = "default_reminder_max_attempts")]
+    pub max_attempts: i32,
+    /// Time before retrying a failed delivery, doubled on each attempt
+    #[serde(default = "default_reminder_retry_base_seconds")]
+    pub retry_base_seconds: u64,
+    /// Signs the webhook payloads, sent as `X-Signature: sha256=<hmac>`
+    pub webhook_secret: Option<String>,
+    #[serde(default = "default_reminder_webhook_timeout_seconds")]
+    pub webhook_timeout_seconds: u64,
+    /// Lets webhooks use http and private addresses, for local development only
+    #[serde(default)]
+    pub allow_private_webhooks: bool,
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval_seconds: default_reminder_poll_interval_seconds(),
+            batch_size: default_reminder_batch_size(),
+            concurrency: default_reminder_concurrency(),
+            lease_seconds: default_reminder_lease_seconds(),
+            max_attempts: default_reminder_max_attempts(),
+            retry_base_seconds: default_reminder_retry_base_seconds(),
+            webhook_secret: None,
+            webhook_timeout_seconds: default_reminder_webhook_timeout_seconds(),
+            allow_private_webhooks: false,
+        }
+    }
+}
+
+fn default_reminder_poll_interval_seconds() -> u64 {
+    30
+}
+
+fn default_reminder_batch_size() -> i64 {
+    100
+}
+
+fn default_reminder_concurrency() -> usize {
+    10
+}
+
+fn default_reminder_lease_seconds() -> u64 {
+    300
+}
+
+fn default_reminder_max_attempts() -> i32 {
+    5
+}
+
+fn default_reminder_retry_base_seconds() -> u64 {
+    60
+}
+
+fn default_reminder_webhook_timeout_seconds() -> u64 {
+    10
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SessionCookieSettings {
+    /// Only disable for local development over plain http
+    #[serde(default = "default_cookie_secure")]
+    pub secure: bool,
+    #[serde(default)]
+    pub same_site: CookieSameSite,
+    /// Domain the cookies are sent to, the host of the api when unset
+    pub domain: Option<String>,
+}
+
+impl Default for SessionCookieSettings {
+    fn default() -> Self {
+        Self {
+            secure: default_cookie_secure(),
+            same_site: CookieSameSite::default(),
+            domain: None,
+        }
+    }
+}
+
+fn default_cookie_secure() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieSameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+/// What users with an unverified email are allowed to do
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailVerificationPolicy {
+    /// Everything, verifying the email is optional
+    #[default]
+    Optional,
+    /// Nothing, they cannot log in until their email is verified
+    RequiredForLogin,
+    /// They can log in and manage their account, but cannot access tasks and lists
+    Restricted,
+}
+
+fn default_access_token_ttl_minutes() -> i64 {
+    15
+}
+
+fn default_refresh_token_ttl_days() -> i64 {
+    30
+}
+
+fn default_password_reset_ttl_minutes() -> i64 {
+    60
+}
+
+fn default_public_url() -> String {
+    "http://localhost:3000".into()
+}
+
+fn default_email_verification_ttl_hours() -> i64 {
+    24
+}
+
+fn default_email_verification_resend_cooldown_seconds() -> i64 {
+    60
+}
+
+fn default_totp_issuer() -> String {
+    "Todo App".into()
+}
+
+fn default_two_factor_challenge_ttl_minutes() -> i64 {
+    5
+}
+
+fn default_account_deletion_grace_period_days() -> i64 {
+    30
+}
+
+fn default_oidc_state_ttl_minutes() -> i64 {
+    10
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".into(), "email".into(), "profile".into()]
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RateLimitSettings {
+    #[serde(default)]
+    pub backend: RateLimitBackend,
+    /// Requests allowed per client ip on authentication endpoints
+    #[serde(default = "default_per_ip_bucket")]
+    pub per_ip: RateLimitBucket,
+    /// Requests allowed per account (username or email) on authentication endpoints
+    #[serde(default = "default_per_account_bucket")]
+    pub per_account: RateLimitBucket,
+    #[serde(default)]
+    pub lockout: LockoutSettings,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitBackend {
+    /// Counters are local to the instance
+    #[default]
+    Memory,
+    /// Counters are shared by every instance using the database
+    Postgres,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct RateLimitBucket {
+    pub max_requests: i32,
+    pub window_seconds: i64,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            backend: RateLimitBackend::default(),
+            per_ip: default_per_ip_bucket(),
+            per_account: default_per_account_bucket(),
+            lockout: LockoutSettings::default(),
+        }
+    }
+}
+
+/// Accounts are locked after `threshold` consecutive failed logins, for
+/// `base_seconds` doubled on every further failure up to `max_seconds`.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct LockoutSettings {
+    pub threshold: i32,
+    pub base_seconds: i64,
+    pub max_seconds: i64,
+    /// Failed logins are forgotten this long after the first of them
+    pub reset_after_seconds: i64,
+}
+
+impl Default for LockoutSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            base_seconds: 30,
+            max_seconds: 60 * 60,
+            reset_after_seconds: 24 * 60 * 60,
+        }
+    }
+}
+
+fn default_per_ip_bucket() -> RateLimitBucket {
+    RateLimitBucket {
+        max_requests: 30,
+        window_seconds: 60,
+    }
+}
+
+fn default_per_account_bucket() -> RateLimitBucket {
+    RateLimitBucket {
+        max_requests: 10,
+        window_seconds: 60,
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum MailerSettings {
+    Smtp {
+        host: String,
+        port: u16,
+        username: String,
+        password: String,
+        from: String,
+    },
+    /// Emails are written to files in `dir`
+    File { dir: String },
+    /// Emails are kept in memory, they are never delivered
+    Memory,
+}
+
+impl Default for MailerSettings {
+    fn default() -> Self {
+        MailerSettings::File {
+            dir: "mails".into(),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -43,6 +550,8 @@ impl AppSettings {
 pub struct AppConfig {
     pub app_settings: AppSettings,
     pub database_settings: DatabaseSettings,
+    #[serde(default)]
+    pub mailer_settings: MailerSettings,
 }
 
 impl AppConfig {
@@ -60,7 +569,37 @@ impl AppConfig {
             }
         };
 
-        config.build()?.try_deserialize()
+        let config: Self = config.build()?.try_deserialize()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the settings that depend on one another
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.app_settings.password_hashing.max_concurrency == 0 {
+            return Err(ConfigError::Message(
+                "app_settings.password_hashing.max_concurrency must be at least 1".into(),
+            ));
+        }
+
+        let reminders = &self.app_settings.reminders;
+        if reminders.concurrency == 0 {
+            return Err(ConfigError::Message(
+                "app_settings.reminders.concurrency must be at least 1".into(),
+            ));
+        }
+        // Every round of deliveries of a batch may last until the webhook timeout
+        let rounds = (reminders.batch_size.max(0) as u64).div_ceil(reminders.concurrency as u64);
+        if reminders.lease_seconds <= rounds * reminders.webhook_timeout_seconds {
+            return Err(ConfigError::Message(format!(
+                "app_settings.reminders.lease_seconds must exceed {} seconds, the time a batch of {} reminders may take to deliver",
+                rounds * reminders.webhook_timeout_seconds,
+                reminders.batch_size
+            )));
+        }
+
+        Ok(())
     }
 }
 
@@ -83,3 +622,35 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lease_must_cover_the_delivery_of_a_batch() {
+        let mut config = AppConfig::build("TEST".into()).unwrap();
+
+        // 100 reminders, 10 at once, each taking up to 10 seconds
+        config.app_settings.reminders.lease_seconds = 100;
+        let short_lease = config.validate();
+        config.app_settings.reminders.lease_seconds = 101;
+        let long_lease = config.validate();
+        config.app_settings.reminders.concurrency = 0;
+        let no_concurrency = config.validate();
+
+        assert!(short_lease.is_err());
+        assert!(long_lease.is_ok());
+        assert!(no_concurrency.is_err());
+    }
+
+    #[test]
+    fn password_hashing_needs_a_slot() {
+        let mut config = AppConfig::build("TEST".into()).unwrap();
+
+        config.app_settings.password_hashing.max_concurrency = 0;
+        let err = config.validate().unwrap_err();
+
+        assert!(err.to_string().contains("password_hashing.max_concurrency"));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::domain::api_token::ApiToken;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(token_hash))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    name: &str,
+    token_hash: &str,
+    scopes: &[String],
+    expires_at: Option<DateTime<Utc>>,
+    db_pool: &PgPool,
+) -> Result<ApiToken, sqlx::Error> {
+    let api_token = sqlx::query_as!(
+        ApiToken,
+        r#"
+    INSERT INTO api_tokens(id, user_id, name, token_hash, scopes, expires_at) values($1,$2,$3,$4,$5,$6) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        token_hash,
+        scopes,
+        expires_at
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(api_token)
+}
+
+/// Finds a token that is neither revoked nor expired
+pub async fn find_active_api_token_by_hash(
+    token_hash: &str,
+    db_pool: &PgPool,
+) -> Result<Option<ApiToken>, sqlx::Error> {
+    let api_token = sqlx::query_as!(
+        ApiToken,
+        r#"
+    select * from api_tokens
+    where token_hash = $1 and revoked_at is null and (expires_at is null or expires_at > now())
+    "#,
+        token_hash
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(api_token)
+}
+
+/// Lists the tokens of the user that were not revoked, expired ones included
+pub async fn find_api_tokens_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<ApiToken>, sqlx::Error> {
+    let api_tokens = sqlx::query_as!(
+        ApiToken,
+        r#"select * from api_tokens where user_id = $1 and revoked_at is null order by created_at"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(api_tokens)
+}
+
+/// Records a use of the token, at most once a minute to spare writes
+pub async fn touch_api_token(id: Uuid, db_pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    UPDATE api_tokens SET last_used_at = now()
+    WHERE id = $1 and (last_used_at is null or last_used_at < now() - interval '1 minute')
+    "#,
+        id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Revokes a token of the user, returns false if there is no such token
+#[tracing::instrument]
+pub async fn revoke_api_token(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE api_tokens SET revoked_at = now()
+    WHERE id = $1 and user_id = $2 and revoked_at is null
+    "#,
+        id,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Revokes every token of the user, returns how many were revoked
+#[tracing::instrument]
+pub async fn revoke_api_tokens_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE api_tokens SET revoked_at = now() WHERE user_id = $1 and revoked_at is null"#,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_utils::{self, insert_user};
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn find_active_api_token_skips_expired_and_revoked() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let scopes = vec!["tasks:read".to_string()];
+
+        let active = create_api_token(user_id, "active", "active", &scopes, None, &db_pool)
+            .await
+            .unwrap();
+        create_api_token(
+            user_id,
+            "expired",
+            "expired",
+            &scopes,
+            Some(Utc::now() - Duration::days(1)),
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        let revoked = create_api_token(user_id, "revoked", "revoked", &scopes, None, &db_pool)
+            .await
+            .unwrap();
+        revoke_api_token(revoked.id, user_id, &db_pool)
+            .await
+            .unwrap();
+
+        let found_active = find_active_api_token_by_hash("active", &db_pool)
+            .await
+            .unwrap();
+        let found_expired = find_active_api_token_by_hash("expired", &db_pool)
+            .await
+            .unwrap();
+        let found_revoked = find_active_api_token_by_hash("revoked", &db_pool)
+            .await
+            .unwrap();
+        let listed = find_api_tokens_by_user_id(user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(found_active, Some(active));
+        assert!(found_expired.is_none());
+        assert!(found_revoked.is_none());
+        assert_eq!(
+            listed
+                .into_iter()
+                .map(|token| token.name)
+                .collect::<Vec<_>>(),
+            vec!["active", "expired"]
+        );
+    }
+
+    #[tokio::test]
+    async fn revoke_api_token_of_another_user() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let other_user_id = insert_user(&db_pool, "other_username").await;
+
+        let api_token = create_api_token(
+            user_id,
+            "token",
+            "hash",
+            &["tasks:read".to_string()],
+            None,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        let revoked_by_other = revoke_api_token(api_token.id, other_user_id, &db_pool)
+            .await
+            .unwrap();
+        let revoked = revoke_api_token(api_token.id, user_id, &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(!revoked_by_other);
+        assert!(revoked);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::domain::email_verification::EmailVerificationToken;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Stores a new verification token, discarding the previous unused ones of the user
+#[tracing::instrument(skip(token_hash))]
+pub async fn create_email_verification_token(
+    user_id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<EmailVerificationToken, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    sqlx::query!(
+        r#"delete from email_verification_tokens where user_id = $1 and used_at is null"#,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let verification_token = sqlx::query_as!(
+        EmailVerificationToken,
+        r#"
+    INSERT INTO email_verification_tokens(id, user_id, token_hash, expires_at) values($1,$2,$3,$4) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(verification_token)
+}
+
+/// When the last verification email was sent to the user, used to throttle resends
+pub async fn last_email_verification_sent_at(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"select max(created_at) as sent_at from email_verification_tokens where user_id = $1"#,
+        user_id
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(row.sent_at)
+}
+
+/// Consumes a valid token and marks the email of its user as verified
+#[tracing::instrument(skip(token_hash))]
+pub async fn verify_email(token_hash: &str, db_pool: &PgPool) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let verification_token = sqlx::query_as!(
+        EmailVerificationToken,
+        r#"
+    UPDATE email_verification_tokens SET used_at = now()
+    WHERE token_hash = $1 and used_at is null and expires_at > now() RETURNING *;
+    "#,
+        token_hash
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let verification_token = match verification_token {
+        Some(verification_token) => verification_token,
+        None => return Ok(None),
+    };
+
+    sqlx::query!(
+        r#"update users set email_verified_at = now() where id = $1 and email_verified_at is null"#,
+        verification_token.user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(verification_token.user_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        test_utils,
+        user::{create_user, find_user_by_id},
+    };
+    use crate::domain::user::CreateUser;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn verify_email_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+        let user = create_user(user_input, &db_pool).await.unwrap();
+
+        let token = create_email_verification_token(
+            user.id,
+            "hash",
+            Utc::now() + Duration::hours(1),
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        let sent_at = last_email_verification_sent_at(user.id, &db_pool)
+            .await
+            .unwrap();
+
+        let verified = verify_email("hash", &db_pool).await.unwrap();
+        let verified_twice = verify_email("hash", &db_pool).await.unwrap();
+        let user = find_user_by_id(user.id, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(sent_at, Some(token.created_at));
+        assert_eq!(verified, Some(user.id));
+        assert!(verified_twice.is_none());
+        assert!(user.email_verified_at.is_some());
+    }
+}
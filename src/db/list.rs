@@ -0,0 +1,281 @@
+use super::task::position_at_end;
+use crate::{
+    domain::list::{CreateList, DeleteListMode, List, UpdateList},
+    utils::rank,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument]
+pub async fn create_list(
+    user_id: Uuid,
+    list_input: CreateList,
+    db_pool: &PgPool,
+) -> Result<List, sqlx::Error> {
+    let list = sqlx::query_as!(
+        List,
+        r#"
+    INSERT INTO lists(id, user_id, name) values($1,$2,$3) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        list_input.name
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(list)
+}
+
+pub async fn find_lists_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<List>, sqlx::Error> {
+    let lists = sqlx::query_as!(
+        List,
+        r#"select * from lists where user_id = $1 order by created_at"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(lists)
+}
+
+pub async fn find_list_by_id(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<List>, sqlx::Error> {
+    let list = sqlx::query_as!(
+        List,
+        r#"select * from lists where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(list)
+}
+
+#[tracing::instrument]
+pub async fn update_list(
+    id: Uuid,
+    user_id: Uuid,
+    list_input: UpdateList,
+    db_pool: &PgPool,
+) -> Result<Option<List>, sqlx::Error> {
+    let list = sqlx::query_as!(
+        List,
+        r#"
+    UPDATE lists SET name = $3, updated_at = now() WHERE id = $1 and user_id = $2 RETURNING *;
+    "#,
+        id,
+        user_id,
+        list_input.name
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(list)
+}
+
+/// Deletes a list, its tasks are either orphaned or deleted depending on `mode`
+#[tracing::instrument]
+pub async fn delete_list(
+    id: Uuid,
+    user_id: Uuid,
+    mode: DeleteListMode,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    // Locking the list first, no task is added to it while it is emptied
+    let list = sqlx::query!(
+        r#"select id from lists where id = $1 and user_id = $2 for update"#,
+        id,
+        user_id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+    if list.is_none() {
+        return Ok(false);
+    }
+
+    match mode {
+        DeleteListMode::Cascade => {
+            sqlx::query!(
+                r#"delete from tasks where list_id = $1 and user_id = $2"#,
+                id,
+                user_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        DeleteListMode::Orphan => {
+            // Orphans go after the tasks of the inbox, in their order in the list
+            let mut position = position_at_end(user_id, None, None, &mut tx).await?;
+            let mut tasks = sqlx::query!(
+                r#"
+    select id, position, created_at from tasks where list_id = $1 and user_id = $2
+    order by id for update;
+    "#,
+                id,
+                user_id
+            )
+            .fetch_all(&mut tx)
+            .await?;
+            tasks.sort_by(|a, b| {
+                (&a.position, a.created_at, a.id).cmp(&(&b.position, b.created_at, b.id))
+            });
+
+            let mut ids = Vec::with_capacity(tasks.len());
+            let mut positions = Vec::with_capacity(tasks.len());
+            for task in tasks {
+                ids.push(task.id);
+                positions.push(position.clone());
+                position = rank::between(Some(&position), None)
+                    .ok_or_else(|| sqlx::Error::Decode("invalid task position".into()))?;
+            }
+
+            sqlx::query!(
+                r#"
+    UPDATE tasks SET list_id = null, position = orphan.position, updated_at = now()
+    FROM unnest($1::uuid[], $2::text[]) AS orphan(id, position)
+    WHERE tasks.id = orphan.id;
+    "#,
+                &ids,
+                &positions
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+    }
+
+    let result = sqlx::query!(
+        r#"delete from lists where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        is_unique_violation,
+        task::{create_task, find_task_by_id, find_tasks_by_user_id},
+        test_utils::{self, insert_user},
+    };
+    use crate::domain::task::CreateTask;
+
+    async fn insert_task(user_id: Uuid, list_id: Option<Uuid>, db_pool: &PgPool) -> Uuid {
+        let task_input = CreateTask {
+            title: "title".into(),
+            description: None,
+            list_id,
+            parent_id: None,
+            due_at: None,
+            tag_ids: vec![],
+        };
+
+        create_task(user_id, task_input, db_pool).await.unwrap().id
+    }
+
+    #[tokio::test]
+    async fn create_and_find_list_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let list_input = CreateList {
+            name: "groceries".into(),
+        };
+
+        let created_list = create_list(user_id, list_input, &db_pool).await.unwrap();
+
+        // Checking inserted list
+        let list = find_list_by_id(created_list.id, user_id, &db_pool)
+            .await
+            .unwrap()
+            .expect("list not found");
+        let duplicate = create_list(
+            user_id,
+            CreateList {
+                name: "groceries".into(),
+            },
+            &db_pool,
+        )
+        .await;
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(created_list, list);
+        assert!(is_unique_violation(&duplicate.unwrap_err()));
+    }
+
+    #[tokio::test]
+    async fn delete_list_orphans_tasks() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let list_input = CreateList {
+            name: "groceries".into(),
+        };
+        let list = create_list(user_id, list_input, &db_pool).await.unwrap();
+        let inbox_task_id = insert_task(user_id, None, &db_pool).await;
+        let first_task_id = insert_task(user_id, Some(list.id), &db_pool).await;
+        let second_task_id = insert_task(user_id, Some(list.id), &db_pool).await;
+
+        let deleted = delete_list(list.id, user_id, DeleteListMode::Orphan, &db_pool)
+            .await
+            .unwrap();
+        let inbox = find_tasks_by_user_id(user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(deleted);
+        assert!(inbox.iter().all(|task| task.list_id.is_none()));
+        assert_eq!(
+            inbox.iter().map(|task| task.id).collect::<Vec<_>>(),
+            vec![inbox_task_id, first_task_id, second_task_id]
+        );
+        assert!(inbox
+            .windows(2)
+            .all(|tasks| tasks[0].position < tasks[1].position));
+    }
+
+    #[tokio::test]
+    async fn delete_list_cascades_to_tasks() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let list_input = CreateList {
+            name: "groceries".into(),
+        };
+        let list = create_list(user_id, list_input, &db_pool).await.unwrap();
+        let task_id = insert_task(user_id, Some(list.id), &db_pool).await;
+
+        let deleted = delete_list(list.id, user_id, DeleteListMode::Cascade, &db_pool)
+            .await
+            .unwrap();
+        let task = find_task_by_id(task_id, user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(deleted);
+        assert!(task.is_none());
+    }
+}
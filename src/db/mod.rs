@@ -1,11 +1,30 @@
+pub mod api_token;
+pub mod email_verification;
+pub mod list;
+pub mod notification;
+pub mod oidc;
+pub mod password_reset;
+pub mod rate_limit;
+pub mod recurrence;
+pub mod refresh_token;
+pub mod reminder;
+pub mod revoked_token;
+pub mod tag;
+pub mod task;
+pub mod two_factor;
 pub mod user;
 
+/// Whether a query failed on a unique constraint, such as a name already taken
+pub fn is_unique_violation(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::Database(err) if err.code().as_deref() == Some("23505"))
+}
+
 #[cfg(test)]
 mod test_utils {
     use sqlx::{Connection, Executor, PgConnection, PgPool};
     use uuid::Uuid;
 
-    use crate::configuration::AppConfig;
+    use crate::{configuration::AppConfig, db::user::create_user, domain::user::CreateUser};
 
     pub async fn configure_database() -> (AppConfig, PgPool) {
         let mut config = AppConfig::build("TEST".into()).unwrap();
@@ -43,6 +62,17 @@ mod test_utils {
         (config, db_pool)
     }
 
+    /// Creates a user named `username`, returns their id
+    pub async fn insert_user(db_pool: &PgPool, username: &str) -> Uuid {
+        let user_input = CreateUser {
+            username: username.into(),
+            email: format!("{}@gmail.com", username),
+            password: "password".into(),
+        };
+
+        create_user(user_input, db_pool).await.unwrap().id
+    }
+
     pub async fn drop_db(config: AppConfig, db_pool: PgPool) {
         db_pool.close().await;
         let mut conn = PgConnection::connect(&config.database_settings.connection_string())
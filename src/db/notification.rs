@@ -0,0 +1,67 @@
+use crate::domain::notification::Notification;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Stores an in-app notification, nothing is stored when the reminder already
+/// notified the user
+#[tracing::instrument(skip(body))]
+pub async fn create_notification(
+    user_id: Uuid,
+    reminder_id: Option<Uuid>,
+    title: &str,
+    body: &str,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    INSERT INTO notifications(id, user_id, reminder_id, title, body) values($1,$2,$3,$4,$5)
+    ON CONFLICT (reminder_id) DO NOTHING;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        reminder_id,
+        title,
+        body
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Notifications of the user, most recent first
+pub async fn find_notifications_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<Notification>, sqlx::Error> {
+    let notifications = sqlx::query_as!(
+        Notification,
+        r#"select * from notifications where user_id = $1 order by created_at desc"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(notifications)
+}
+
+#[tracing::instrument]
+pub async fn mark_notification_read(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<Notification>, sqlx::Error> {
+    let notification = sqlx::query_as!(
+        Notification,
+        r#"
+    UPDATE notifications SET read_at = COALESCE(read_at, now())
+    WHERE id = $1 and user_id = $2 RETURNING *;
+    "#,
+        id,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(notification)
+}
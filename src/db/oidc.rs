@@ -0,0 +1,398 @@
+use crate::domain::{
+    oidc::{OidcState, UserIdentity},
+    user::{CreateUser, Role, User},
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(state_hash, nonce, code_verifier))]
+pub async fn create_oidc_state(
+    state_hash: &str,
+    provider: &str,
+    nonce: &str,
+    code_verifier: &str,
+    user_id: Option<Uuid>,
+    expires_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<OidcState, sqlx::Error> {
+    let oidc_state = sqlx::query_as!(
+        OidcState,
+        r#"
+    INSERT INTO oidc_states(id, state_hash, provider, nonce, code_verifier, user_id, expires_at)
+    values($1,$2,$3,$4,$5,$6,$7) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        state_hash,
+        provider,
+        nonce,
+        code_verifier,
+        user_id,
+        expires_at
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(oidc_state)
+}
+
+/// Consumes an unexpired state started on `provider` by `user_id`, `None` for
+/// a login, a state is only accepted once
+#[tracing::instrument(skip(state_hash))]
+pub async fn consume_oidc_state(
+    state_hash: &str,
+    provider: &str,
+    user_id: Option<Uuid>,
+    db_pool: &PgPool,
+) -> Result<Option<OidcState>, sqlx::Error> {
+    let oidc_state = sqlx::query_as!(
+        OidcState,
+        r#"
+    DELETE FROM oidc_states
+    WHERE state_hash = $1 and provider = $2 and user_id is not distinct from $3 and expires_at > now()
+    RETURNING *;
+    "#,
+        state_hash,
+        provider,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(oidc_state)
+}
+
+/// Removes the states of logins that were never completed
+#[tracing::instrument]
+pub async fn delete_expired_oidc_states(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(r#"delete from oidc_states where expires_at < now()"#)
+        .execute(db_pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Links an account of `provider` to a user.
+///
+/// Returns `None` when the account is already linked, or when the user already
+/// linked another account of the provider.
+#[tracing::instrument]
+pub async fn create_user_identity(
+    user_id: Uuid,
+    provider: &str,
+    subject: &str,
+    email: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<Option<UserIdentity>, sqlx::Error> {
+    let identity = sqlx::query_as!(
+        UserIdentity,
+        r#"
+    INSERT INTO user_identities(id, user_id, provider, subject, email) values($1,$2,$3,$4,$5)
+    ON CONFLICT DO NOTHING RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        provider,
+        subject,
+        email
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(identity)
+}
+
+/// Creates a user signing up with an identity provider along with their
+/// identity, verified when `email_verified`.
+///
+/// Returns `None`, without creating the user, when the identity is already
+/// linked.
+#[tracing::instrument(skip(user_input))]
+pub async fn create_oidc_user(
+    user_input: CreateUser,
+    email_verified: bool,
+    provider: &str,
+    subject: &str,
+    db_pool: &PgPool,
+) -> Result<Option<User>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    INSERT INTO users(id, username, email, password_hash, email_verified_at)
+    values($1,$2,$3,$4, case when $5 then now() end)
+    RETURNING id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at;
+    "#,
+        Uuid::new_v4(),
+        user_input.username,
+        user_input.email,
+        user_input.password,
+        email_verified
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let identity = sqlx::query!(
+        r#"
+    INSERT INTO user_identities(id, user_id, provider, subject, email) values($1,$2,$3,$4,$5)
+    ON CONFLICT DO NOTHING RETURNING id;
+    "#,
+        Uuid::new_v4(),
+        user.id,
+        provider,
+        subject,
+        user.email
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+    if identity.is_none() {
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(user))
+}
+
+pub async fn find_user_identity(
+    provider: &str,
+    subject: &str,
+    db_pool: &PgPool,
+) -> Result<Option<UserIdentity>, sqlx::Error> {
+    let identity = sqlx::query_as!(
+        UserIdentity,
+        r#"select * from user_identities where provider = $1 and subject = $2"#,
+        provider,
+        subject
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(identity)
+}
+
+pub async fn find_user_identities_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<UserIdentity>, sqlx::Error> {
+    let identities = sqlx::query_as!(
+        UserIdentity,
+        r#"select * from user_identities where user_id = $1 order by created_at"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(identities)
+}
+
+/// Records a login with the identity, along with the current email of the account
+pub async fn touch_user_identity(
+    id: Uuid,
+    email: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE user_identities SET last_login_at = now(), email = COALESCE($2, email) WHERE id = $1"#,
+        id,
+        email
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Unlinks an identity of the user, returns whether it existed
+#[tracing::instrument]
+pub async fn delete_user_identity(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"delete from user_identities where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        test_utils::{self, insert_user},
+        user::find_user_by_username,
+    };
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn oidc_state_is_consumed_once_by_its_user() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let expires_at = Utc::now() + Duration::minutes(10);
+
+        create_oidc_state(
+            "login", "idp", "nonce", "verifier", None, expires_at, &db_pool,
+        )
+        .await
+        .unwrap();
+        create_oidc_state(
+            "link",
+            "idp",
+            "nonce",
+            "verifier",
+            Some(user_id),
+            expires_at,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        create_oidc_state(
+            "expired",
+            "idp",
+            "nonce",
+            "verifier",
+            None,
+            Utc::now(),
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        let other_provider = consume_oidc_state("login", "other", None, &db_pool)
+            .await
+            .unwrap();
+        let login = consume_oidc_state("login", "idp", None, &db_pool)
+            .await
+            .unwrap();
+        let login_again = consume_oidc_state("login", "idp", None, &db_pool)
+            .await
+            .unwrap();
+        let link_as_login = consume_oidc_state("link", "idp", None, &db_pool)
+            .await
+            .unwrap();
+        let link = consume_oidc_state("link", "idp", Some(user_id), &db_pool)
+            .await
+            .unwrap();
+        let expired = consume_oidc_state("expired", "idp", None, &db_pool)
+            .await
+            .unwrap();
+        let purged = delete_expired_oidc_states(&db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(other_provider.is_none());
+        assert_eq!(login.expect("state not found").code_verifier, "verifier");
+        assert!(login_again.is_none());
+        assert!(link_as_login.is_none());
+        assert_eq!(link.expect("state not found").user_id, Some(user_id));
+        assert!(expired.is_none());
+        assert_eq!(purged, 1);
+    }
+
+    #[tokio::test]
+    async fn identity_is_linked_once() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let other_user_id = insert_user(&db_pool, "other_username").await;
+
+        let identity = create_user_identity(user_id, "idp", "subject", None, &db_pool)
+            .await
+            .unwrap()
+            .expect("identity not created");
+        let taken_subject = create_user_identity(other_user_id, "idp", "subject", None, &db_pool)
+            .await
+            .unwrap();
+        let second_account = create_user_identity(user_id, "idp", "other_subject", None, &db_pool)
+            .await
+            .unwrap();
+        let other_provider = create_user_identity(user_id, "other", "subject", None, &db_pool)
+            .await
+            .unwrap();
+
+        touch_user_identity(identity.id, Some("email@gmail.com"), &db_pool)
+            .await
+            .unwrap();
+        let found = find_user_identity("idp", "subject", &db_pool)
+            .await
+            .unwrap()
+            .expect("identity not found");
+        let not_owner = delete_user_identity(identity.id, other_user_id, &db_pool)
+            .await
+            .unwrap();
+        let deleted = delete_user_identity(identity.id, user_id, &db_pool)
+            .await
+            .unwrap();
+        let identities = find_user_identities_by_user_id(user_id, &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(taken_subject.is_none());
+        assert!(second_account.is_none());
+        assert!(other_provider.is_some());
+        assert_eq!(found.email.as_deref(), Some("email@gmail.com"));
+        assert!(found.last_login_at.is_some());
+        assert!(!not_owner);
+        assert!(deleted);
+        assert_eq!(identities.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn oidc_user_is_not_created_when_identity_is_linked() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        create_user_identity(user_id, "idp", "subject", None, &db_pool)
+            .await
+            .unwrap()
+            .expect("identity not created");
+
+        let oidc_user_input = |username: &str| CreateUser {
+            username: username.into(),
+            email: format!("{}@gmail.com", username),
+            password: "password".into(),
+        };
+        let created = create_oidc_user(
+            oidc_user_input("oidc_user"),
+            true,
+            "idp",
+            "other_subject",
+            &db_pool,
+        )
+        .await
+        .unwrap()
+        .expect("user not created");
+        let linked = create_oidc_user(
+            oidc_user_input("orphan_user"),
+            true,
+            "idp",
+            "subject",
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        let orphan = find_user_by_username("orphan_user", &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(created.email_verified_at.is_some());
+        assert!(linked.is_none());
+        assert!(orphan.is_none());
+    }
+}
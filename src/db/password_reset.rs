@@ -0,0 +1,243 @@
+use crate::domain::password_reset::PasswordResetToken;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Stores a new reset token, discarding the previous unused ones of the user
+#[tracing::instrument(skip(token_hash))]
+pub async fn create_password_reset_token(
+    user_id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<PasswordResetToken, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    sqlx::query!(
+        r#"delete from password_reset_tokens where user_id = $1 and used_at is null"#,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let reset_token = sqlx::query_as!(
+        PasswordResetToken,
+        r#"
+    INSERT INTO password_reset_tokens(id, user_id, token_hash, expires_at) values($1,$2,$3,$4) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(reset_token)
+}
+
+/// Finds a valid (unused and unexpired) token without consuming it
+#[tracing::instrument(skip(token_hash))]
+pub async fn find_password_reset_token(
+    token_hash: &str,
+    db_pool: &PgPool,
+) -> Result<Option<PasswordResetToken>, sqlx::Error> {
+    let reset_token = sqlx::query_as!(
+        PasswordResetToken,
+        r#"
+    SELECT * FROM password_reset_tokens
+    WHERE token_hash = $1 and used_at is null and expires_at > now();
+    "#,
+        token_hash
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(reset_token)
+}
+
+/// Consumes a valid (unused and unexpired) token and sets the new password of
+/// its user at once, a token can only be consumed once and the other tokens of
+/// the user are deleted with it
+#[tracing::instrument(skip(token_hash, password_hash))]
+pub async fn reset_password_with_token(
+    token_hash: &str,
+    password_hash: &str,
+    db_pool: &PgPool,
+) -> Result<Option<PasswordResetToken>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let reset_token = sqlx::query_as!(
+        PasswordResetToken,
+        r#"
+    UPDATE password_reset_tokens SET used_at = now()
+    WHERE token_hash = $1 and used_at is null and expires_at > now() RETURNING *;
+    "#,
+        token_hash
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let reset_token = match reset_token {
+        Some(reset_token) => reset_token,
+        None => return Ok(None),
+    };
+
+    sqlx::query!(
+        r#"
+    UPDATE users SET password_hash = $2, password_reset_required = false, updated_at = now()
+    WHERE id = $1
+    "#,
+        reset_token.user_id,
+        password_hash
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"delete from password_reset_tokens where user_id = $1"#,
+        reset_token.user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(reset_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        test_utils::{self, insert_user},
+        user::find_user_by_id,
+    };
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn reset_password_with_token_only_once() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        create_password_reset_token(user_id, "hash", Utc::now() + Duration::hours(1), &db_pool)
+            .await
+            .unwrap();
+
+        let first = reset_password_with_token("hash", "password_hash", &db_pool)
+            .await
+            .unwrap();
+        let second = reset_password_with_token("hash", "other_password_hash", &db_pool)
+            .await
+            .unwrap();
+        let user = find_user_by_id(user_id, &db_pool).await.unwrap().unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(first.map(|token| token.user_id), Some(user_id));
+        assert!(second.is_none());
+        assert_eq!(user.password_hash, "password_hash");
+    }
+
+    #[tokio::test]
+    async fn find_password_reset_token_does_not_consume_it() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        create_password_reset_token(user_id, "hash", Utc::now() + Duration::hours(1), &db_pool)
+            .await
+            .unwrap();
+
+        let found = find_password_reset_token("hash", &db_pool).await.unwrap();
+        let consumed = reset_password_with_token("hash", "password_hash", &db_pool)
+            .await
+            .unwrap();
+        let found_after_use = find_password_reset_token("hash", &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(found.map(|token| token.user_id), Some(user_id));
+        assert!(consumed.is_some());
+        assert!(found_after_use.is_none());
+    }
+
+    #[tokio::test]
+    async fn reset_password_with_expired_or_replaced_token() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        create_password_reset_token(
+            user_id,
+            "expired",
+            Utc::now() - Duration::hours(1),
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        let expired = reset_password_with_token("expired", "password_hash", &db_pool)
+            .await
+            .unwrap();
+
+        create_password_reset_token(user_id, "first", Utc::now() + Duration::hours(1), &db_pool)
+            .await
+            .unwrap();
+        create_password_reset_token(user_id, "second", Utc::now() + Duration::hours(1), &db_pool)
+            .await
+            .unwrap();
+        let replaced = reset_password_with_token("first", "password_hash", &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(expired.is_none());
+        assert!(replaced.is_none());
+    }
+
+    #[tokio::test]
+    async fn reset_password_with_token_deletes_other_tokens() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        create_password_reset_token(user_id, "first", Utc::now() + Duration::hours(1), &db_pool)
+            .await
+            .unwrap();
+        // Tokens created concurrently can both be outstanding
+        sqlx::query!(
+            r#"
+        INSERT INTO password_reset_tokens(id, user_id, token_hash, expires_at) values($1,$2,$3,$4)
+        "#,
+            Uuid::new_v4(),
+            user_id,
+            "second",
+            Utc::now() + Duration::hours(1)
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap();
+
+        let consumed = reset_password_with_token("first", "password_hash", &db_pool)
+            .await
+            .unwrap();
+        let other = reset_password_with_token("second", "other_password_hash", &db_pool)
+            .await
+            .unwrap();
+        let user = find_user_by_id(user_id, &db_pool).await.unwrap().unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(consumed.is_some());
+        assert!(other.is_none());
+        assert_eq!(user.password_hash, "password_hash");
+    }
+}
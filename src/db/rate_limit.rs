@@ -0,0 +1,114 @@
+use crate::domain::rate_limit::RateLimitCounter;
+use chrono::Duration;
+use sqlx::PgPool;
+
+/// Increments the counter of `key`, starting a new `window` if the current one is over
+pub async fn increment_rate_limit_counter(
+    key: &str,
+    window: Duration,
+    db_pool: &PgPool,
+) -> Result<RateLimitCounter, sqlx::Error> {
+    let counter = sqlx::query_as!(
+        RateLimitCounter,
+        r#"
+    INSERT INTO rate_limit_counters(key, count, expires_at) values($1, 1, now() + make_interval(secs => $2))
+    ON CONFLICT (key) DO UPDATE SET
+        count = CASE WHEN rate_limit_counters.expires_at <= now() THEN 1 ELSE rate_limit_counters.count + 1 END,
+        expires_at = CASE WHEN rate_limit_counters.expires_at <= now() THEN EXCLUDED.expires_at ELSE rate_limit_counters.expires_at END,
+        updated_at = now()
+    RETURNING *;
+    "#,
+        key,
+        window.num_seconds() as f64
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(counter)
+}
+
+pub async fn find_rate_limit_counter(
+    key: &str,
+    db_pool: &PgPool,
+) -> Result<Option<RateLimitCounter>, sqlx::Error> {
+    let counter = sqlx::query_as!(
+        RateLimitCounter,
+        r#"select * from rate_limit_counters where key = $1 and expires_at > now()"#,
+        key
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(counter)
+}
+
+pub async fn delete_rate_limit_counter(key: &str, db_pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(r#"delete from rate_limit_counters where key = $1"#, key)
+        .execute(db_pool)
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument]
+pub async fn delete_expired_rate_limit_counters(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(r#"delete from rate_limit_counters where expires_at <= now()"#)
+        .execute(db_pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_utils;
+
+    #[tokio::test]
+    async fn increment_rate_limit_counter_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        let first = increment_rate_limit_counter("key", Duration::minutes(1), &db_pool)
+            .await
+            .unwrap();
+        let second = increment_rate_limit_counter("key", Duration::minutes(1), &db_pool)
+            .await
+            .unwrap();
+        let found = find_rate_limit_counter("key", &db_pool).await.unwrap();
+
+        delete_rate_limit_counter("key", &db_pool).await.unwrap();
+        let deleted = find_rate_limit_counter("key", &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(first.count, 1);
+        assert_eq!(second.count, 2);
+        assert_eq!(second.expires_at, first.expires_at);
+        assert_eq!(found, Some(second));
+        assert!(deleted.is_none());
+    }
+
+    #[tokio::test]
+    async fn increment_expired_rate_limit_counter_restarts_window() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        increment_rate_limit_counter("key", Duration::zero(), &db_pool)
+            .await
+            .unwrap();
+        let expired = find_rate_limit_counter("key", &db_pool).await.unwrap();
+        let purged = delete_expired_rate_limit_counters(&db_pool).await.unwrap();
+        let counter = increment_rate_limit_counter("key", Duration::minutes(1), &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(expired.is_none());
+        assert_eq!(purged, 1);
+        assert_eq!(counter.count, 1);
+    }
+}
@@ -0,0 +1,222 @@
+use super::task::position_at_end;
+use crate::domain::{recurrence::TaskRecurrence, task::Task};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Starts a series at `starts_at` with the task as its first occurrence, the
+/// task leaves the series it was part of
+#[tracing::instrument]
+pub async fn set_task_recurrence(
+    task_id: Uuid,
+    user_id: Uuid,
+    rule: &str,
+    timezone: &str,
+    starts_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<TaskRecurrence, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let recurrence = sqlx::query_as!(
+        TaskRecurrence,
+        r#"
+    INSERT INTO task_recurrences(id, user_id, rule, timezone, starts_at) values($1,$2,$3,$4,$5)
+    RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        rule,
+        timezone,
+        starts_at
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE tasks SET recurrence_id = $3, updated_at = now() WHERE id = $1 and user_id = $2"#,
+        task_id,
+        user_id,
+        recurrence.id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(recurrence)
+}
+
+pub async fn find_recurrence_by_id(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<TaskRecurrence>, sqlx::Error> {
+    let recurrence = sqlx::query_as!(
+        TaskRecurrence,
+        r#"select * from task_recurrences where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(recurrence)
+}
+
+pub async fn find_recurrences_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<TaskRecurrence>, sqlx::Error> {
+    let recurrences = sqlx::query_as!(
+        TaskRecurrence,
+        r#"select * from task_recurrences where user_id = $1 order by created_at"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(recurrences)
+}
+
+/// Takes the task out of its series, no occurrence follows it anymore
+#[tracing::instrument]
+pub async fn leave_recurrence(
+    task_id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE tasks SET recurrence_id = null, updated_at = now()
+    WHERE id = $1 and user_id = $2 and recurrence_id is not null;
+    "#,
+        task_id,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Creates the occurrence of the series of `task` due at `due_at`, a copy of
+/// the task along with its tags.
+///
+/// Nothing is created when the series already has an occurrence due then, so
+/// that completing a task again does not repeat it twice.
+#[tracing::instrument(skip(task), fields(task_id = %task.id))]
+pub async fn create_next_occurrence(
+    task: &Task,
+    due_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<Option<Task>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let position = position_at_end(task.user_id, task.list_id, None, &mut tx).await?;
+    let occurrence = sqlx::query_as!(
+        Task,
+        r#"
+    INSERT INTO tasks(id, user_id, list_id, parent_id, title, description, due_at, recurrence_id, position)
+    values($1,$2,$3,$4,$5,$6,$7,$8,$9)
+    ON CONFLICT (recurrence_id, due_at) DO NOTHING
+    RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        task.user_id,
+        task.list_id,
+        task.parent_id,
+        task.title,
+        task.description,
+        due_at,
+        task.recurrence_id,
+        position
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    if let Some(occurrence) = &occurrence {
+        sqlx::query!(
+            r#"INSERT INTO task_tags(task_id, tag_id) SELECT $1, tag_id FROM task_tags WHERE task_id = $2"#,
+            occurrence.id,
+            task.id
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(occurrence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        tag::{create_tag, find_tags_by_task_ids},
+        task::create_task,
+        test_utils::{self, insert_user},
+    };
+    use crate::domain::{tag::CreateTag, task::CreateTask};
+    use chrono::{Duration, TimeZone};
+
+    #[tokio::test]
+    async fn next_occurrence_copies_the_task_once() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let tag = create_tag(
+            user_id,
+            CreateTag {
+                name: "chores".into(),
+            },
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        let due_at = Utc.ymd(2022, 12, 1).and_hms(8, 0, 0);
+        let task_input = CreateTask {
+            title: "water plants".into(),
+            description: Some("the ones on the balcony".into()),
+            list_id: None,
+            parent_id: None,
+            due_at: Some(due_at),
+            tag_ids: vec![tag.id],
+        };
+        let task_id = create_task(user_id, task_input, &db_pool).await.unwrap().id;
+        let recurrence =
+            set_task_recurrence(task_id, user_id, "FREQ=DAILY", "UTC", due_at, &db_pool)
+                .await
+                .unwrap();
+        let task = sqlx::query_as!(Task, "select * from tasks where id = $1", task_id)
+            .fetch_one(&db_pool)
+            .await
+            .unwrap();
+
+        let next_due_at = due_at + Duration::days(1);
+        let occurrence = create_next_occurrence(&task, next_due_at, &db_pool)
+            .await
+            .unwrap()
+            .unwrap();
+        let duplicate = create_next_occurrence(&task, next_due_at, &db_pool)
+            .await
+            .unwrap();
+        let occurrence_tags = find_tags_by_task_ids(&[occurrence.id], &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(task.recurrence_id, Some(recurrence.id));
+        assert_eq!(occurrence.recurrence_id, Some(recurrence.id));
+        assert_eq!(occurrence.title, task.title);
+        assert_eq!(occurrence.description, task.description);
+        assert_eq!(occurrence.due_at, Some(next_due_at));
+        assert!(!occurrence.completed);
+        assert!(duplicate.is_none());
+        assert_eq!(occurrence_tags.len(), 1);
+        assert_eq!(occurrence_tags[0].1.id, tag.id);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::domain::refresh_token::RefreshToken;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(token_hash))]
+pub async fn create_refresh_token(
+    user_id: Uuid,
+    family_id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<RefreshToken, sqlx::Error> {
+    let refresh_token = sqlx::query_as!(
+        RefreshToken,
+        r#"
+    INSERT INTO refresh_tokens(id, user_id, family_id, token_hash, expires_at) values($1,$2,$3,$4,$5) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        family_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(refresh_token)
+}
+
+pub async fn find_refresh_token_by_hash(
+    token_hash: &str,
+    db_pool: &PgPool,
+) -> Result<Option<RefreshToken>, sqlx::Error> {
+    let refresh_token = sqlx::query_as!(
+        RefreshToken,
+        r#"select * from refresh_tokens where token_hash = $1"#,
+        token_hash
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(refresh_token)
+}
+
+/// A session is active as long as one token of its family is not revoked
+pub async fn is_session_active(family_id: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"select exists(select 1 from refresh_tokens where family_id = $1 and revoked_at is null) as "exists!""#,
+        family_id
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(row.exists)
+}
+
+/// Replaces `refresh_token` by a new token of the same family.
+///
+/// Returns `None` when `refresh_token` was rotated or revoked concurrently.
+#[tracing::instrument(skip(token_hash))]
+pub async fn rotate_refresh_token(
+    refresh_token: &RefreshToken,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<Option<RefreshToken>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let new_refresh_token = sqlx::query_as!(
+        RefreshToken,
+        r#"
+    INSERT INTO refresh_tokens(id, user_id, family_id, token_hash, expires_at) values($1,$2,$3,$4,$5) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        refresh_token.user_id,
+        refresh_token.family_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let result = sqlx::query!(
+        r#"
+    UPDATE refresh_tokens SET replaced_by = $2
+    WHERE id = $1 and replaced_by is null and revoked_at is null;
+    "#,
+        refresh_token.id,
+        new_refresh_token.id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(new_refresh_token))
+}
+
+#[tracing::instrument]
+pub async fn revoke_refresh_token_family(
+    family_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"update refresh_tokens set revoked_at = now() where family_id = $1 and revoked_at is null"#,
+        family_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[tracing::instrument]
+pub async fn revoke_refresh_tokens_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"update refresh_tokens set revoked_at = now() where user_id = $1 and revoked_at is null"#,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_utils::{self, insert_user};
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn rotate_refresh_token_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let expires_at = Utc::now() + Duration::days(1);
+
+        let refresh_token =
+            create_refresh_token(user_id, Uuid::new_v4(), "first", expires_at, &db_pool)
+                .await
+                .unwrap();
+
+        let rotated = rotate_refresh_token(&refresh_token, "second", expires_at, &db_pool)
+            .await
+            .unwrap()
+            .expect("token was not rotated");
+        let rotated_twice = rotate_refresh_token(&refresh_token, "third", expires_at, &db_pool)
+            .await
+            .unwrap();
+        let old = find_refresh_token_by_hash("first", &db_pool)
+            .await
+            .unwrap()
+            .expect("token not found");
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(rotated.family_id, refresh_token.family_id);
+        assert!(rotated_twice.is_none());
+        assert_eq!(old.replaced_by, Some(rotated.id));
+        assert!(old.is_used());
+    }
+
+    #[tokio::test]
+    async fn revoke_refresh_token_family_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let expires_at = Utc::now() + Duration::days(1);
+        let family_id = Uuid::new_v4();
+
+        create_refresh_token(user_id, family_id, "first", expires_at, &db_pool)
+            .await
+            .unwrap();
+        create_refresh_token(user_id, family_id, "second", expires_at, &db_pool)
+            .await
+            .unwrap();
+        create_refresh_token(user_id, Uuid::new_v4(), "other", expires_at, &db_pool)
+            .await
+            .unwrap();
+
+        let revoked = revoke_refresh_token_family(family_id, &db_pool)
+            .await
+            .unwrap();
+        let other = find_refresh_token_by_hash("other", &db_pool)
+            .await
+            .unwrap()
+            .expect("token not found");
+        let active = is_session_active(family_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(revoked, 2);
+        assert!(!active);
+        assert!(!other.is_used());
+    }
+}
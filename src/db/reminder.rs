@@ -0,0 +1,349 @@
+use crate::domain::reminder::{DueReminder, NotificationChannel, TaskReminder};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Stores a reminder, first due for delivery at `remind_at`
+#[tracing::instrument]
+pub async fn create_reminder(
+    task_id: Uuid,
+    user_id: Uuid,
+    remind_at: DateTime<Utc>,
+    timezone: &str,
+    channel: NotificationChannel,
+    webhook_url: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<TaskReminder, sqlx::Error> {
+    let reminder = sqlx::query_as!(
+        TaskReminder,
+        r#"
+    INSERT INTO task_reminders(id, task_id, user_id, remind_at, timezone, channel, webhook_url, next_attempt_at)
+    values($1,$2,$3,$4,$5,$6,$7,$4)
+    RETURNING id, task_id, user_id, remind_at, timezone, channel as "channel: NotificationChannel",
+        webhook_url, next_attempt_at, attempts, last_error, delivered_at, failed_at, created_at;
+    "#,
+        Uuid::new_v4(),
+        task_id,
+        user_id,
+        remind_at,
+        timezone,
+        channel as NotificationChannel,
+        webhook_url
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(reminder)
+}
+
+pub async fn find_reminders_by_task_id(
+    task_id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<TaskReminder>, sqlx::Error> {
+    let reminders = sqlx::query_as!(
+        TaskReminder,
+        r#"
+    select id, task_id, user_id, remind_at, timezone, channel as "channel: NotificationChannel",
+        webhook_url, next_attempt_at, attempts, last_error, delivered_at, failed_at, created_at
+    from task_reminders where task_id = $1 and user_id = $2 order by remind_at;
+    "#,
+        task_id,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(reminders)
+}
+
+pub async fn find_reminders_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<TaskReminder>, sqlx::Error> {
+    let reminders = sqlx::query_as!(
+        TaskReminder,
+        r#"
+    select id, task_id, user_id, remind_at, timezone, channel as "channel: NotificationChannel",
+        webhook_url, next_attempt_at, attempts, last_error, delivered_at, failed_at, created_at
+    from task_reminders where user_id = $1 order by remind_at;
+    "#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(reminders)
+}
+
+#[tracing::instrument]
+pub async fn delete_reminder(
+    id: Uuid,
+    task_id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"delete from task_reminders where id = $1 and task_id = $2 and user_id = $3"#,
+        id,
+        task_id,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Claims up to `limit` reminders due for delivery.
+///
+/// Claimed reminders are pushed back by `lease_seconds`, so that another worker
+/// does not pick them up meanwhile, and are delivered again once the lease is
+/// over unless marked as delivered: delivery is at least once. The outcome of
+/// a delivery is only recorded while the lease holds.
+#[tracing::instrument]
+pub async fn claim_due_reminders(
+    limit: i64,
+    lease_seconds: f64,
+    db_pool: &PgPool,
+) -> Result<Vec<DueReminder>, sqlx::Error> {
+    let reminders = sqlx::query_as!(
+        DueReminder,
+        r#"
+    with claimed as (
+        UPDATE task_reminders SET
+            attempts = attempts + 1,
+            next_attempt_at = now() + make_interval(secs => $2)
+        WHERE id in (
+            select id from task_reminders
+            where delivered_at is null and failed_at is null and next_attempt_at <= now()
+            order by next_attempt_at limit $1
+            for update skip locked
+        ) RETURNING *
+    )
+    select claimed.id as "id!", claimed.task_id as "task_id!", claimed.user_id as "user_id!",
+        users.email, tasks.title as task_title, tasks.completed as task_completed, tasks.due_at,
+        claimed.remind_at as "remind_at!", claimed.timezone as "timezone!",
+        claimed.channel as "channel!: NotificationChannel", claimed.webhook_url,
+        claimed.attempts as "attempts!", claimed.next_attempt_at as "lease_until!"
+    from claimed
+    join tasks on tasks.id = claimed.task_id
+    join users on users.id = claimed.user_id
+    order by claimed.remind_at;
+    "#,
+        limit,
+        lease_seconds
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(reminders)
+}
+
+/// Marks a reminder claimed until `lease_until` as delivered, returns `false`
+/// when it was claimed again since
+pub async fn mark_reminder_delivered(
+    id: Uuid,
+    lease_until: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE task_reminders SET delivered_at = now(), last_error = null
+    WHERE id = $1 and next_attempt_at = $2 and delivered_at is null;
+    "#,
+        id,
+        lease_until
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Records a failed delivery of a reminder claimed until `lease_until`, the
+/// reminder is retried at `retry_at` or given up on when `None`.
+///
+/// Returns `false` when the reminder was claimed again since.
+pub async fn record_reminder_failure(
+    id: Uuid,
+    lease_until: DateTime<Utc>,
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE task_reminders SET
+        last_error = $3,
+        next_attempt_at = COALESCE($4, next_attempt_at),
+        failed_at = CASE WHEN $4::timestamptz is null THEN now() END
+    WHERE id = $1 and next_attempt_at = $2 and delivered_at is null;
+    "#,
+        id,
+        lease_until,
+        error,
+        retry_at
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        task::create_task,
+        test_utils::{self, insert_user},
+    };
+    use crate::domain::task::CreateTask;
+    use chrono::Duration;
+
+    async fn insert_task(db_pool: &PgPool) -> (Uuid, Uuid) {
+        let user_id = insert_user(db_pool, "username").await;
+
+        let task_input = CreateTask {
+            title: "title".into(),
+            description: None,
+            list_id: None,
+            parent_id: None,
+            due_at: None,
+            tag_ids: vec![],
+        };
+        let task_id = create_task(user_id, task_input, db_pool).await.unwrap().id;
+
+        (user_id, task_id)
+    }
+
+    #[tokio::test]
+    async fn claimed_reminders_are_leased_until_delivered() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let (user_id, task_id) = insert_task(&db_pool).await;
+
+        let due = create_reminder(
+            task_id,
+            user_id,
+            Utc::now() - Duration::minutes(1),
+            "Europe/Paris",
+            NotificationChannel::Email,
+            None,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        create_reminder(
+            task_id,
+            user_id,
+            Utc::now() + Duration::hours(1),
+            "Europe/Paris",
+            NotificationChannel::Email,
+            None,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        let claimed = claim_due_reminders(10, 60.0, &db_pool).await.unwrap();
+        let leased = claim_due_reminders(10, 60.0, &db_pool).await.unwrap();
+
+        // The lease is over, the reminder was not delivered
+        sqlx::query!(
+            "UPDATE task_reminders SET next_attempt_at = now() WHERE id = $1",
+            due.id
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap();
+        let reclaimed_after_lease = claim_due_reminders(10, 60.0, &db_pool).await.unwrap();
+
+        // Only the worker holding the lease records the delivery
+        let marked_after_lease = mark_reminder_delivered(due.id, claimed[0].lease_until, &db_pool)
+            .await
+            .unwrap();
+        let marked =
+            mark_reminder_delivered(due.id, reclaimed_after_lease[0].lease_until, &db_pool)
+                .await
+                .unwrap();
+        sqlx::query!(
+            "UPDATE task_reminders SET next_attempt_at = now() WHERE id = $1",
+            due.id
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap();
+        let delivered = claim_due_reminders(10, 60.0, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(
+            claimed
+                .iter()
+                .map(|reminder| reminder.id)
+                .collect::<Vec<_>>(),
+            vec![due.id]
+        );
+        assert_eq!(claimed[0].email, "username@gmail.com");
+        assert_eq!(claimed[0].attempts, 1);
+        assert!(leased.is_empty());
+        assert_eq!(reclaimed_after_lease[0].attempts, 2);
+        assert!(!marked_after_lease);
+        assert!(marked);
+        assert!(delivered.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_reminder_is_retried_then_given_up() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let (user_id, task_id) = insert_task(&db_pool).await;
+
+        let reminder = create_reminder(
+            task_id,
+            user_id,
+            Utc::now() - Duration::minutes(1),
+            "UTC",
+            NotificationChannel::Webhook,
+            Some("https://example.com/hook"),
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        let claimed = claim_due_reminders(10, 60.0, &db_pool).await.unwrap();
+        record_reminder_failure(
+            reminder.id,
+            claimed[0].lease_until,
+            "timed out",
+            Some(Utc::now()),
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        let retried = claim_due_reminders(10, 60.0, &db_pool).await.unwrap();
+        record_reminder_failure(
+            reminder.id,
+            retried[0].lease_until,
+            "timed out again",
+            None,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        let reminders = find_reminders_by_task_id(task_id, user_id, &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(retried.len(), 1);
+        assert_eq!(reminders[0].attempts, 2);
+        assert_eq!(reminders[0].last_error.as_deref(), Some("timed out again"));
+        assert!(reminders[0].failed_at.is_some());
+        assert!(reminders[0].delivered_at.is_none());
+    }
+}
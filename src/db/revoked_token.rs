@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument]
+pub async fn revoke_token(
+    jti: Uuid,
+    user_id: Uuid,
+    expires_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    INSERT INTO revoked_tokens(jti, user_id, expires_at) values($1,$2,$3) ON CONFLICT DO NOTHING;
+    "#,
+        jti,
+        user_id,
+        expires_at
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn is_token_revoked(jti: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"select exists(select 1 from revoked_tokens where jti = $1) as "exists!""#,
+        jti
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(row.exists)
+}
+
+/// Removes revocations of tokens that are expired and thus rejected anyway
+#[tracing::instrument]
+pub async fn delete_expired_revoked_tokens(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(r#"delete from revoked_tokens where expires_at < now()"#)
+        .execute(db_pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_utils, user::create_user};
+    use crate::domain::user::CreateUser;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn revoke_token_and_cleanup_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+        let user = create_user(user_input, &db_pool).await.unwrap();
+
+        let active_jti = Uuid::new_v4();
+        let expired_jti = Uuid::new_v4();
+        let now = Utc::now();
+
+        revoke_token(active_jti, user.id, now + Duration::hours(1), &db_pool)
+            .await
+            .unwrap();
+        revoke_token(expired_jti, user.id, now - Duration::hours(1), &db_pool)
+            .await
+            .unwrap();
+
+        let deleted = delete_expired_revoked_tokens(&db_pool).await.unwrap();
+        let active_revoked = is_token_revoked(active_jti, &db_pool).await.unwrap();
+        let unknown_revoked = is_token_revoked(Uuid::new_v4(), &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(deleted, 1);
+        assert!(active_revoked);
+        assert!(!unknown_revoked);
+    }
+}
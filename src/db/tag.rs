@@ -0,0 +1,241 @@
+use crate::domain::tag::{CreateTag, Tag, UpdateTag};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument]
+pub async fn create_tag(
+    user_id: Uuid,
+    tag_input: CreateTag,
+    db_pool: &PgPool,
+) -> Result<Tag, sqlx::Error> {
+    let tag = sqlx::query_as!(
+        Tag,
+        r#"
+    INSERT INTO tags(id, user_id, name) values($1,$2,$3) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        tag_input.name
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(tag)
+}
+
+pub async fn find_tags_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<Tag>, sqlx::Error> {
+    let tags = sqlx::query_as!(
+        Tag,
+        r#"select * from tags where user_id = $1 order by name"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(tags)
+}
+
+pub async fn find_tag_by_id(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<Tag>, sqlx::Error> {
+    let tag = sqlx::query_as!(
+        Tag,
+        r#"select * from tags where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(tag)
+}
+
+/// Finds the tags of the user among `ids`, the ones of other users are left out
+pub async fn find_tags_by_ids(
+    ids: &[Uuid],
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<Tag>, sqlx::Error> {
+    let tags = sqlx::query_as!(
+        Tag,
+        r#"select * from tags where id = any($1) and user_id = $2 order by name"#,
+        ids,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(tags)
+}
+
+/// Finds the tags attached to each of the tasks, as `(task_id, tag)` pairs
+pub async fn find_tags_by_task_ids(
+    task_ids: &[Uuid],
+    db_pool: &PgPool,
+) -> Result<Vec<(Uuid, Tag)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+    select task_tags.task_id, tags.id, tags.user_id, tags.name, tags.created_at, tags.updated_at
+    from task_tags join tags on tags.id = task_tags.tag_id
+    where task_tags.task_id = any($1) order by tags.name;
+    "#,
+        task_ids
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let tags = rows
+        .into_iter()
+        .map(|row| {
+            let tag = Tag {
+                id: row.id,
+                user_id: row.user_id,
+                name: row.name,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            };
+
+            (row.task_id, tag)
+        })
+        .collect();
+
+    Ok(tags)
+}
+
+#[tracing::instrument]
+pub async fn update_tag(
+    id: Uuid,
+    user_id: Uuid,
+    tag_input: UpdateTag,
+    db_pool: &PgPool,
+) -> Result<Option<Tag>, sqlx::Error> {
+    let tag = sqlx::query_as!(
+        Tag,
+        r#"
+    UPDATE tags SET name = $3, updated_at = now() WHERE id = $1 and user_id = $2 RETURNING *;
+    "#,
+        id,
+        user_id,
+        tag_input.name
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(tag)
+}
+
+/// Deletes a tag, it is detached from its tasks by the `ON DELETE CASCADE` foreign key
+#[tracing::instrument]
+pub async fn delete_tag(id: Uuid, user_id: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"delete from tags where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        is_unique_violation,
+        task::{create_task, find_task_by_id},
+        test_utils::{self, insert_user},
+    };
+    use crate::domain::task::CreateTask;
+
+    async fn insert_tag(user_id: Uuid, name: &str, db_pool: &PgPool) -> Tag {
+        let tag_input = CreateTag { name: name.into() };
+
+        create_tag(user_id, tag_input, db_pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn create_and_find_tag_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let created_tag = insert_tag(user_id, "work", &db_pool).await;
+
+        // Checking inserted tag
+        let tag = find_tag_by_id(created_tag.id, user_id, &db_pool)
+            .await
+            .unwrap()
+            .expect("tag not found");
+        let duplicate = create_tag(
+            user_id,
+            CreateTag {
+                name: "work".into(),
+            },
+            &db_pool,
+        )
+        .await;
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(created_tag, tag);
+        assert!(is_unique_violation(&duplicate.unwrap_err()));
+    }
+
+    #[tokio::test]
+    async fn find_tags_by_ids_leaves_out_other_users_tags() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let owner_id = insert_user(&db_pool, "owner").await;
+        let other_id = insert_user(&db_pool, "other").await;
+
+        let owned = insert_tag(owner_id, "work", &db_pool).await;
+        let other = insert_tag(other_id, "home", &db_pool).await;
+
+        let tags = find_tags_by_ids(&[owned.id, other.id], owner_id, &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(tags, vec![owned]);
+    }
+
+    #[tokio::test]
+    async fn delete_tag_detaches_it_from_tasks() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let tag = insert_tag(user_id, "work", &db_pool).await;
+        let task_input = CreateTask {
+            title: "title".into(),
+            description: None,
+            list_id: None,
+            parent_id: None,
+            due_at: None,
+            tag_ids: vec![tag.id],
+        };
+        let task = create_task(user_id, task_input, &db_pool).await.unwrap();
+
+        let tags_before = find_tags_by_task_ids(&[task.id], &db_pool).await.unwrap();
+        let deleted = delete_tag(tag.id, user_id, &db_pool).await.unwrap();
+        let tags_after = find_tags_by_task_ids(&[task.id], &db_pool).await.unwrap();
+        let found = find_task_by_id(task.id, user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(tags_before, vec![(task.id, tag)]);
+        assert!(deleted);
+        assert!(tags_after.is_empty());
+        assert!(found.is_some());
+    }
+}
@@ -0,0 +1,1065 @@
+use crate::{
+    domain::task::{
+        CreateTask, FilterTasks, SubtaskCompletion, TagMatch, Task, TaskPlacement, UpdateTask,
+        MAX_SUBTASK_DEPTH,
+    },
+    utils::rank,
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum TaskPositionError {
+    /// The positions around the destination leave no room, such as
+    /// positions that are not ranks
+    #[error("no position left between the neighbours of the task")]
+    NoRoom,
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum TaskParentError {
+    /// The parent is not a task of the user
+    #[error("parent task not found")]
+    ParentNotFound,
+    /// The parent is the task itself or one of its subtasks
+    #[error("task cannot be moved under itself")]
+    Cycle,
+    /// The task or its subtasks would be nested deeper than `MAX_SUBTASK_DEPTH`
+    #[error("subtasks are nested too deep")]
+    TooDeep,
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+/// Creates a task, its parent is checked to belong to the user and not to be
+/// nested too deep
+#[tracing::instrument]
+pub async fn create_task(
+    user_id: Uuid,
+    task_input: CreateTask,
+    db_pool: &PgPool,
+) -> Result<Task, TaskParentError> {
+    let mut tx = db_pool.begin().await?;
+
+    let position = position_at_end(user_id, task_input.list_id, None, &mut tx).await?;
+    if let Some(parent_id) = task_input.parent_id {
+        check_task_parent(None, parent_id, user_id, &mut tx).await?;
+    }
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+    INSERT INTO tasks(id, user_id, list_id, parent_id, title, description, due_at, position)
+    values($1,$2,$3,$4,$5,$6,$7,$8) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        task_input.list_id,
+        task_input.parent_id,
+        task_input.title,
+        task_input.description,
+        task_input.due_at,
+        position
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    replace_task_tags(task.id, user_id, &task_input.tag_ids, &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(task)
+}
+
+/// Checks that the task `id`, a new task when `None`, can be put under
+/// `parent_id`.
+///
+/// The subtree of the task and the ancestors of the parent stay locked until
+/// the end of the transaction, so that concurrent moves cannot make a cycle or
+/// nest tasks too deep.
+async fn check_task_parent(
+    id: Option<Uuid>,
+    parent_id: Uuid,
+    user_id: Uuid,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), TaskParentError> {
+    // Locking in id order, as moves do
+    sqlx::query!(
+        r#"
+    with recursive subtree as (
+        select id from tasks where id = $1 and user_id = $3
+        union
+        select tasks.id from tasks join subtree on tasks.parent_id = subtree.id
+    ), ancestors as (
+        select id, parent_id from tasks where id = $2 and user_id = $3
+        union
+        select tasks.id, tasks.parent_id from tasks join ancestors on tasks.id = ancestors.parent_id
+    )
+    select id from tasks
+    where id in (select id from subtree union select id from ancestors)
+    order by id for update;
+    "#,
+        id,
+        parent_id,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // The parent along with its ancestors, read again once locked
+    let ancestors = sqlx::query_scalar!(
+        r#"
+    with recursive ancestors as (
+        select id, parent_id from tasks where id = $1 and user_id = $2
+        union
+        select tasks.id, tasks.parent_id from tasks join ancestors on tasks.id = ancestors.parent_id
+    ) select id as "id!" from ancestors;
+    "#,
+        parent_id,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if ancestors.is_empty() {
+        return Err(TaskParentError::ParentNotFound);
+    }
+    let height = match id {
+        Some(id) if ancestors.contains(&id) => return Err(TaskParentError::Cycle),
+        // The whole subtree moves down along with the task
+        Some(id) => {
+            sqlx::query_scalar!(
+                r#"
+    with recursive subtree as (
+        select id, 0 as depth from tasks where id = $1 and user_id = $2
+        union all
+        select tasks.id, subtree.depth + 1 from tasks join subtree on tasks.parent_id = subtree.id
+    ) select coalesce(max(depth), 0)::bigint as "height!" from subtree;
+    "#,
+                id,
+                user_id
+            )
+            .fetch_one(&mut *tx)
+            .await?
+        }
+        None => 0,
+    };
+
+    if ancestors.len() as i64 + height > MAX_SUBTASK_DEPTH {
+        return Err(TaskParentError::TooDeep);
+    }
+
+    Ok(())
+}
+
+/// Replaces the tags of a task, tags of other users are not attached
+async fn replace_task_tags(
+    task_id: Uuid,
+    user_id: Uuid,
+    tag_ids: &[Uuid],
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(r#"delete from task_tags where task_id = $1"#, task_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+    INSERT INTO task_tags(task_id, tag_id)
+    SELECT $1, id FROM tags WHERE id = any($2) and user_id = $3;
+    "#,
+        task_id,
+        tag_ids,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Locks a list, the inbox of the user when `list_id` is `None`, until the end
+/// of the transaction so that concurrent inserts and moves in it do not get
+/// the same position
+async fn lock_task_list(
+    user_id: Uuid,
+    list_id: Option<Uuid>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    // `no key update` still lets other transactions insert rows referencing
+    // the user or the list
+    match list_id {
+        Some(list_id) => {
+            sqlx::query!(
+                r#"select id from lists where id = $1 for no key update"#,
+                list_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+        }
+        None => {
+            sqlx::query!(
+                r#"select id from users where id = $1 for no key update"#,
+                user_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Position after the last task of a list, the inbox when `list_id` is `None`.
+///
+/// The list stays locked until the end of the transaction.
+pub(super) async fn position_at_end(
+    user_id: Uuid,
+    list_id: Option<Uuid>,
+    excluded_id: Option<Uuid>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<String, sqlx::Error> {
+    lock_task_list(user_id, list_id, tx).await?;
+
+    let row = sqlx::query!(
+        r#"
+    select max(position) as position from tasks
+    where user_id = $1 and list_id is not distinct from $2 and id is distinct from $3;
+    "#,
+        user_id,
+        list_id,
+        excluded_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // There is always room after the last position, unless it is not a rank
+    rank::between(row.position.as_deref(), None)
+        .ok_or_else(|| sqlx::Error::Decode("invalid task position".into()))
+}
+
+fn position_between(lower: Option<&str>, upper: Option<&str>) -> Result<String, TaskPositionError> {
+    rank::between(lower, upper).ok_or(TaskPositionError::NoRoom)
+}
+
+pub async fn find_tasks_by_user_id(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<Task>, sqlx::Error> {
+    let tasks = sqlx::query_as!(
+        Task,
+        r#"select * from tasks where user_id = $1 order by position, created_at"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+/// Finds the tasks of the user having any or all of the filter tags
+pub async fn find_tasks_by_filter(
+    user_id: Uuid,
+    filter: &FilterTasks,
+    db_pool: &PgPool,
+) -> Result<Vec<Task>, sqlx::Error> {
+    let mut tag_ids = filter.tags.clone();
+    tag_ids.sort_unstable();
+    tag_ids.dedup();
+
+    let tasks = sqlx::query_as!(
+        Task,
+        r#"
+    select * from tasks where user_id = $1 and (
+        cardinality($2::uuid[]) = 0
+        or (select count(*) from task_tags where task_id = tasks.id and tag_id = any($2))
+            >= case when $3 then cardinality($2) else 1 end
+    ) order by position, created_at;
+    "#,
+        user_id,
+        &tag_ids,
+        filter.tag_match == TagMatch::All
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+pub async fn find_tasks_by_list_id(
+    list_id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<Task>, sqlx::Error> {
+    let tasks = sqlx::query_as!(
+        Task,
+        r#"select * from tasks where list_id = $1 and user_id = $2 order by position, created_at"#,
+        list_id,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+pub async fn find_task_by_id(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<Task>, sqlx::Error> {
+    let task = sqlx::query_as!(
+        Task,
+        r#"select * from tasks where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(task)
+}
+
+/// Finds a task and all its subtasks, at every depth
+pub async fn find_task_subtree(
+    id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<Task>, sqlx::Error> {
+    let tasks = sqlx::query_as!(
+        Task,
+        r#"
+    select * from tasks where user_id = $2 and id in (
+        with recursive subtree as (
+            select id from tasks where id = $1 and user_id = $2
+            union
+            select tasks.id from tasks join subtree on tasks.parent_id = subtree.id
+        ) select id from subtree
+    ) order by position, created_at;
+    "#,
+        id,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+/// Number of ancestors of a task, 0 for a top level task
+pub async fn find_task_depth(id: Uuid, db_pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+    with recursive ancestors as (
+        select parent_id from tasks where id = $1
+        union
+        select tasks.parent_id from tasks join ancestors on tasks.id = ancestors.parent_id
+    ) select count(*) as "depth!" from ancestors where parent_id is not null;
+    "#,
+        id
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(row.depth)
+}
+
+/// Updates a task, along with the completion of its subtasks when `subtasks`
+/// cascades
+#[tracing::instrument]
+pub async fn update_task(
+    id: Uuid,
+    user_id: Uuid,
+    task_input: UpdateTask,
+    subtasks: SubtaskCompletion,
+    db_pool: &PgPool,
+) -> Result<Option<Task>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+    UPDATE tasks SET
+        title = COALESCE($3, title),
+        description = CASE WHEN $4 THEN $5 ELSE description END,
+        completed = COALESCE($6, completed),
+        due_at = CASE WHEN $7 THEN $8 ELSE due_at END,
+        updated_at = now()
+    WHERE id = $1 and user_id = $2 RETURNING *;
+    "#,
+        id,
+        user_id,
+        task_input.title,
+        task_input.description.is_some(),
+        task_input.description.flatten(),
+        task_input.completed,
+        task_input.due_at.is_some(),
+        task_input.due_at.flatten()
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    if let (Some(task), Some(tag_ids)) = (&task, &task_input.tag_ids) {
+        replace_task_tags(task.id, user_id, tag_ids, &mut tx).await?;
+    }
+    if let (Some(task), Some(completed), SubtaskCompletion::Cascade) =
+        (&task, task_input.completed, subtasks)
+    {
+        set_subtasks_completed(task.id, user_id, completed, &mut tx).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(task)
+}
+
+/// Moves a task into a list at `placement`, the list and anchor ownership is
+/// checked by the caller.
+///
+/// Only the row of the task is updated, it gets a position between the ones of
+/// its new neighbours. Returns `None` when the task or the anchor is not found.
+#[tracing::instrument]
+pub async fn reorder_task(
+    id: Uuid,
+    user_id: Uuid,
+    list_id: Option<Uuid>,
+    placement: TaskPlacement,
+    db_pool: &PgPool,
+) -> Result<Option<Task>, TaskPositionError> {
+    let mut tx = db_pool.begin().await?;
+
+    let anchor_id = match placement {
+        TaskPlacement::After(anchor_id) | TaskPlacement::Before(anchor_id) => Some(anchor_id),
+        TaskPlacement::End => None,
+    };
+
+    // Locking the destination list, then the task and its anchor, rebalancing
+    // waits for the move to be over
+    lock_task_list(user_id, list_id, &mut tx).await?;
+    let locked = sqlx::query!(
+        r#"
+    select id, position from tasks where user_id = $1 and id = any($2) order by id for update;
+    "#,
+        user_id,
+        &[Some(id), anchor_id]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    if !locked.iter().any(|row| row.id == id) {
+        return Ok(None);
+    }
+    let anchor_position = match anchor_id {
+        Some(anchor_id) => match locked.into_iter().find(|row| row.id == anchor_id) {
+            Some(anchor) => Some(anchor.position),
+            None => return Ok(None),
+        },
+        None => None,
+    };
+
+    let position = match (placement, anchor_position) {
+        (TaskPlacement::After(_), Some(anchor_position)) => {
+            let row = sqlx::query!(
+                r#"
+    select min(position) as position from tasks
+    where user_id = $1 and list_id is not distinct from $2 and position > $3 and id <> $4;
+    "#,
+                user_id,
+                list_id,
+                anchor_position,
+                id
+            )
+            .fetch_one(&mut tx)
+            .await?;
+
+            position_between(Some(&anchor_position), row.position.as_deref())?
+        }
+        (TaskPlacement::Before(_), Some(anchor_position)) => {
+            let row = sqlx::query!(
+                r#"
+    select max(position) as position from tasks
+    where user_id = $1 and list_id is not distinct from $2 and position < $3 and id <> $4;
+    "#,
+                user_id,
+                list_id,
+                anchor_position,
+                id
+            )
+            .fetch_one(&mut tx)
+            .await?;
+
+            position_between(row.position.as_deref(), Some(&anchor_position))?
+        }
+        _ => position_at_end(user_id, list_id, Some(id), &mut tx).await?,
+    };
+
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+    UPDATE tasks SET list_id = $3, position = $4, updated_at = now()
+    WHERE id = $1 and user_id = $2 RETURNING *;
+    "#,
+        id,
+        user_id,
+        list_id,
+        position
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(task)
+}
+
+/// Spreads again the positions of the lists having a position longer than
+/// `max_length`, returns the number of lists rebalanced
+#[tracing::instrument]
+pub async fn rebalance_task_positions(
+    max_length: usize,
+    db_pool: &PgPool,
+) -> Result<u64, sqlx::Error> {
+    let scopes = sqlx::query!(
+        r#"
+    select distinct user_id, list_id from tasks where length(position) > $1;
+    "#,
+        max_length as i32
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    for scope in &scopes {
+        let mut tx = db_pool.begin().await?;
+
+        // Locking the list, then its tasks in id order, as moves do
+        lock_task_list(scope.user_id, scope.list_id, &mut tx).await?;
+        let mut tasks = sqlx::query!(
+            r#"
+    select id, position, created_at from tasks where user_id = $1 and list_id is not distinct from $2
+    order by id for update;
+    "#,
+            scope.user_id,
+            scope.list_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        tasks.sort_by(|a, b| {
+            (&a.position, a.created_at, a.id).cmp(&(&b.position, b.created_at, b.id))
+        });
+
+        let ids = tasks.into_iter().map(|task| task.id).collect::<Vec<_>>();
+        sqlx::query!(
+            r#"
+    UPDATE tasks SET position = spread.position
+    FROM unnest($1::uuid[], $2::text[]) AS spread(id, position)
+    WHERE tasks.id = spread.id;
+    "#,
+            &ids,
+            &rank::spread(ids.len())
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+    }
+
+    Ok(scopes.len() as u64)
+}
+
+/// Moves a task, along with its subtasks, under another task or back to the
+/// top level
+#[tracing::instrument]
+pub async fn set_task_parent(
+    id: Uuid,
+    user_id: Uuid,
+    parent_id: Option<Uuid>,
+    db_pool: &PgPool,
+) -> Result<Option<Task>, TaskParentError> {
+    let mut tx = db_pool.begin().await?;
+
+    if let Some(parent_id) = parent_id {
+        check_task_parent(Some(id), parent_id, user_id, &mut tx).await?;
+    }
+
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+    UPDATE tasks SET parent_id = $3, updated_at = now() WHERE id = $1 and user_id = $2 RETURNING *;
+    "#,
+        id,
+        user_id,
+        parent_id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(task)
+}
+
+/// Sets the completion of every subtask of a task, at every depth
+async fn set_subtasks_completed(
+    id: Uuid,
+    user_id: Uuid,
+    completed: bool,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    with recursive subtree as (
+        select id from tasks where parent_id = $1 and user_id = $2
+        union
+        select tasks.id from tasks join subtree on tasks.parent_id = subtree.id
+    )
+    UPDATE tasks SET completed = $3, updated_at = now()
+    WHERE id in (select id from subtree) and completed <> $3;
+    "#,
+        id,
+        user_id,
+        completed
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes a task, its subtasks are deleted by the `ON DELETE CASCADE` foreign key
+#[tracing::instrument]
+pub async fn delete_task(id: Uuid, user_id: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"delete from tasks where id = $1 and user_id = $2"#,
+        id,
+        user_id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        tag::create_tag,
+        test_utils::{self, insert_user},
+    };
+    use crate::domain::tag::CreateTag;
+
+    #[tokio::test]
+    async fn create_and_find_task_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        // Creating task input
+        let task_input = CreateTask {
+            title: "title".into(),
+            description: Some("description".into()),
+            list_id: None,
+            parent_id: None,
+            due_at: None,
+            tag_ids: vec![],
+        };
+
+        let created_task = create_task(user_id, task_input, &db_pool).await.unwrap();
+
+        // Checking inserted task
+        let task = find_task_by_id(created_task.id, user_id, &db_pool)
+            .await
+            .unwrap()
+            .expect("task not found");
+        let tasks = find_tasks_by_user_id(user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(created_task, task);
+        assert_eq!(tasks, vec![task]);
+        assert!(!created_task.completed);
+    }
+
+    #[tokio::test]
+    async fn task_is_scoped_to_its_owner() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let owner_id = insert_user(&db_pool, "owner").await;
+        let other_id = insert_user(&db_pool, "other").await;
+
+        let task_input = CreateTask {
+            title: "title".into(),
+            description: None,
+            list_id: None,
+            parent_id: None,
+            due_at: None,
+            tag_ids: vec![],
+        };
+        let task = create_task(owner_id, task_input, &db_pool).await.unwrap();
+
+        // Another user can neither see, update nor delete the task
+        let found = find_task_by_id(task.id, other_id, &db_pool).await.unwrap();
+        let update_input = UpdateTask {
+            title: Some("new title".into()),
+            description: None,
+            completed: None,
+            due_at: None,
+            tag_ids: None,
+        };
+        let updated = update_task(
+            task.id,
+            other_id,
+            update_input,
+            SubtaskCompletion::Keep,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+        let deleted = delete_task(task.id, other_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(found.is_none());
+        assert!(updated.is_none());
+        assert!(!deleted);
+    }
+
+    #[tokio::test]
+    async fn update_and_delete_task_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let task_input = CreateTask {
+            title: "title".into(),
+            description: Some("description".into()),
+            list_id: None,
+            parent_id: None,
+            due_at: None,
+            tag_ids: vec![],
+        };
+        let task = create_task(user_id, task_input, &db_pool).await.unwrap();
+
+        let update_input = UpdateTask {
+            title: None,
+            description: None,
+            completed: Some(true),
+            due_at: None,
+            tag_ids: None,
+        };
+        let updated = update_task(
+            task.id,
+            user_id,
+            update_input,
+            SubtaskCompletion::Keep,
+            &db_pool,
+        )
+        .await
+        .unwrap()
+        .expect("task not found");
+        let deleted = delete_task(task.id, user_id, &db_pool).await.unwrap();
+        let found = find_task_by_id(task.id, user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(updated.completed);
+        assert_eq!(updated.title, task.title);
+        assert_eq!(updated.description, task.description);
+        assert!(deleted);
+        assert!(found.is_none());
+    }
+
+    #[tokio::test]
+    async fn find_tasks_by_any_or_all_tags() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let mut tag_ids = Vec::new();
+        for name in ["work", "urgent"] {
+            let tag_input = CreateTag { name: name.into() };
+            tag_ids.push(create_tag(user_id, tag_input, &db_pool).await.unwrap().id);
+        }
+
+        let mut task_ids = Vec::new();
+        for tags in [vec![tag_ids[0]], tag_ids.clone(), vec![]] {
+            let task_input = CreateTask {
+                title: "title".into(),
+                description: None,
+                list_id: None,
+                parent_id: None,
+                due_at: None,
+                tag_ids: tags,
+            };
+            task_ids.push(create_task(user_id, task_input, &db_pool).await.unwrap().id);
+        }
+
+        let mut found = Vec::new();
+        for tag_match in [TagMatch::Any, TagMatch::All] {
+            let filter = FilterTasks {
+                tags: tag_ids.clone(),
+                tag_match,
+            };
+            let tasks = find_tasks_by_filter(user_id, &filter, &db_pool)
+                .await
+                .unwrap();
+            found.push(tasks.into_iter().map(|task| task.id).collect::<Vec<_>>());
+        }
+        let unfiltered = find_tasks_by_filter(user_id, &FilterTasks::default(), &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(found[0], vec![task_ids[0], task_ids[1]]);
+        assert_eq!(found[1], vec![task_ids[1]]);
+        assert_eq!(unfiltered.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn subtree_depth_and_cascade_delete_of_subtasks() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let mut task_ids: Vec<Uuid> = Vec::new();
+        for _ in 0..3 {
+            let task_input = CreateTask {
+                title: "title".into(),
+                description: None,
+                list_id: None,
+                parent_id: task_ids.last().copied(),
+                due_at: None,
+                tag_ids: vec![],
+            };
+            task_ids.push(create_task(user_id, task_input, &db_pool).await.unwrap().id);
+        }
+
+        let subtree = find_task_subtree(task_ids[1], user_id, &db_pool)
+            .await
+            .unwrap();
+        let depth = find_task_depth(task_ids[2], &db_pool).await.unwrap();
+        let update_input = UpdateTask {
+            title: None,
+            description: None,
+            completed: Some(true),
+            due_at: None,
+            tag_ids: None,
+        };
+        update_task(
+            task_ids[0],
+            user_id,
+            update_input,
+            SubtaskCompletion::Cascade,
+            &db_pool,
+        )
+        .await
+        .unwrap()
+        .expect("task not found");
+        let completed = find_task_subtree(task_ids[0], user_id, &db_pool)
+            .await
+            .unwrap();
+        let deleted = delete_task(task_ids[0], user_id, &db_pool).await.unwrap();
+        let remaining = find_tasks_by_user_id(user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(
+            subtree.into_iter().map(|task| task.id).collect::<Vec<_>>(),
+            task_ids[1..]
+        );
+        assert_eq!(depth, 2);
+        assert!(completed.iter().all(|task| task.completed));
+        assert!(deleted);
+        assert!(remaining.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reorder_and_rebalance_task_positions() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let mut task_ids = Vec::new();
+        for title in ["a", "b", "c"] {
+            let task_input = CreateTask {
+                title: title.into(),
+                description: None,
+                list_id: None,
+                parent_id: None,
+                due_at: None,
+                tag_ids: vec![],
+            };
+            task_ids.push(create_task(user_id, task_input, &db_pool).await.unwrap().id);
+        }
+
+        // c, a, b then c, b, a
+        reorder_task(
+            task_ids[2],
+            user_id,
+            None,
+            TaskPlacement::Before(task_ids[0]),
+            &db_pool,
+        )
+        .await
+        .unwrap()
+        .expect("task not found");
+        reorder_task(
+            task_ids[0],
+            user_id,
+            None,
+            TaskPlacement::After(task_ids[1]),
+            &db_pool,
+        )
+        .await
+        .unwrap()
+        .expect("task not found");
+        let reordered = find_tasks_by_user_id(user_id, &db_pool).await.unwrap();
+
+        // Keys grown too long are spread again, keeping the order
+        sqlx::query!(
+            r#"UPDATE tasks SET position = position || 'zzzzzzzzzz' WHERE id = $1"#,
+            task_ids[1]
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap();
+        let rebalanced = rebalance_task_positions(8, &db_pool).await.unwrap();
+        let rebalanced_again = rebalance_task_positions(8, &db_pool).await.unwrap();
+        let tasks = find_tasks_by_user_id(user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        let expected = vec![task_ids[2], task_ids[1], task_ids[0]];
+        assert_eq!(
+            reordered.iter().map(|task| task.id).collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(rebalanced, 1);
+        assert_eq!(rebalanced_again, 0);
+        assert_eq!(
+            tasks.iter().map(|task| task.id).collect::<Vec<_>>(),
+            expected
+        );
+        assert!(tasks.iter().all(|task| task.position.len() <= 2));
+    }
+
+    #[tokio::test]
+    async fn reorder_next_to_an_invalid_position_is_rejected() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let mut task_ids = Vec::new();
+        for title in ["a", "b", "c"] {
+            let task_input = CreateTask {
+                title: title.into(),
+                description: None,
+                list_id: None,
+                parent_id: None,
+                due_at: None,
+                tag_ids: vec![],
+            };
+            task_ids.push(create_task(user_id, task_input, &db_pool).await.unwrap().id);
+        }
+
+        // Ranks do not end with a zero
+        sqlx::query!(
+            r#"UPDATE tasks SET position = 'W0' WHERE id = $1"#,
+            task_ids[1]
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap();
+        let result = reorder_task(
+            task_ids[2],
+            user_id,
+            None,
+            TaskPlacement::After(task_ids[0]),
+            &db_pool,
+        )
+        .await;
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(matches!(result, Err(TaskPositionError::NoRoom)));
+    }
+
+    #[tokio::test]
+    async fn concurrent_creates_get_distinct_positions() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let creates = (0..10).map(|i| {
+            let task_input = CreateTask {
+                title: format!("title {}", i),
+                description: None,
+                list_id: None,
+                parent_id: None,
+                due_at: None,
+                tag_ids: vec![],
+            };
+            create_task(user_id, task_input, &db_pool)
+        });
+        let tasks = futures_util::future::try_join_all(creates).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        let mut positions = tasks
+            .iter()
+            .map(|task| task.position.clone())
+            .collect::<Vec<_>>();
+        positions.sort();
+        positions.dedup();
+
+        assert_eq!(positions.len(), tasks.len());
+    }
+
+    #[tokio::test]
+    async fn concurrent_moves_cannot_make_a_cycle() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let mut task_ids: Vec<Uuid> = Vec::new();
+        for _ in 0..3 {
+            let task_input = CreateTask {
+                title: "title".into(),
+                description: None,
+                list_id: None,
+                parent_id: None,
+                due_at: None,
+                tag_ids: vec![],
+            };
+            task_ids.push(create_task(user_id, task_input, &db_pool).await.unwrap().id);
+        }
+
+        // Each task under the next one, the last one under the first one
+        let moves = task_ids.iter().enumerate().map(|(i, id)| {
+            let parent_id = task_ids[(i + 1) % task_ids.len()];
+            set_task_parent(*id, user_id, Some(parent_id), &db_pool)
+        });
+        let results = futures_util::future::join_all(moves).await;
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, Err(TaskParentError::Cycle))));
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{
+    domain::two_factor::{RecoveryCode, TwoFactorChallenge, UserTotp},
+    utils::cipher::{SecretCipher, SEALED_PREFIX},
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Stores a new pending secret for the user, replacing a previous pending one.
+///
+/// Returns `None` when 2FA is already enabled, the secret is left untouched.
+#[tracing::instrument(skip(secret))]
+pub async fn create_pending_totp(
+    user_id: Uuid,
+    secret: &str,
+    db_pool: &PgPool,
+) -> Result<Option<UserTotp>, sqlx::Error> {
+    let totp = sqlx::query_as!(
+        UserTotp,
+        r#"
+    INSERT INTO user_totp(user_id, secret) values($1,$2)
+    ON CONFLICT (user_id) DO UPDATE SET secret = excluded.secret, created_at = now()
+    WHERE user_totp.enabled_at is null
+    RETURNING *;
+    "#,
+        user_id,
+        secret
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(totp)
+}
+
+pub async fn find_user_totp(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<UserTotp>, sqlx::Error> {
+    let totp = sqlx::query_as!(
+        UserTotp,
+        r#"select * from user_totp where user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(totp)
+}
+
+/// Enables a pending 2FA, replacing the recovery codes of the user.
+///
+/// Returns false when there is no pending 2FA to enable.
+#[tracing::instrument(skip(recovery_code_hashes))]
+pub async fn enable_totp(
+    user_id: Uuid,
+    used_step: i64,
+    recovery_code_hashes: &[String],
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let result = sqlx::query!(
+        r#"
+    UPDATE user_totp SET enabled_at = now(), last_used_step = $2
+    WHERE user_id = $1 and enabled_at is null
+    "#,
+        user_id,
+        used_step
+    )
+    .execute(&mut tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(r#"delete from recovery_codes where user_id = $1"#, user_id)
+        .execute(&mut tx)
+        .await?;
+
+    for code_hash in recovery_code_hashes {
+        sqlx::query!(
+            r#"INSERT INTO recovery_codes(id, user_id, code_hash) values($1,$2,$3)"#,
+            Uuid::new_v4(),
+            user_id,
+            code_hash
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Records that a code of `step` was accepted, returns false if a code of this
+/// step or a later one was already accepted
+pub async fn use_totp_step(
+    user_id: Uuid,
+    step: i64,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE user_totp SET last_used_step = $2
+    WHERE user_id = $1 and enabled_at is not null and (last_used_step is null or last_used_step < $2)
+    "#,
+        user_id,
+        step
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Disables 2FA, discarding the secret, the recovery codes and pending challenges
+#[tracing::instrument]
+pub async fn delete_totp(user_id: Uuid, db_pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    sqlx::query!(r#"delete from user_totp where user_id = $1"#, user_id)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query!(r#"delete from recovery_codes where user_id = $1"#, user_id)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query!(
+        r#"delete from two_factor_challenges where user_id = $1"#,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Seals the secrets stored in plaintext before they were encrypted, returns
+/// how many were sealed
+#[tracing::instrument(skip_all)]
+pub async fn seal_plaintext_totp_secrets(
+    cipher: &SecretCipher,
+    db_pool: &PgPool,
+) -> Result<u64, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let plaintext = sqlx::query_as!(
+        UserTotp,
+        r#"select * from user_totp where not starts_with(secret, $1) for update"#,
+        SEALED_PREFIX
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    for totp in &plaintext {
+        sqlx::query!(
+            r#"UPDATE user_totp SET secret = $2 WHERE user_id = $1"#,
+            totp.user_id,
+            cipher.seal(&totp.secret, totp.user_id.as_bytes())
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(plaintext.len() as u64)
+}
+
+pub async fn find_unused_recovery_codes(
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Vec<RecoveryCode>, sqlx::Error> {
+    let codes = sqlx::query_as!(
+        RecoveryCode,
+        r#"select * from recovery_codes where user_id = $1 and used_at is null"#,
+        user_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(codes)
+}
+
+/// Marks a recovery code as used, returns false if it already was
+pub async fn use_recovery_code(id: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE recovery_codes SET used_at = now() WHERE id = $1 and used_at is null"#,
+        id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[tracing::instrument(skip(token_hash))]
+pub async fn create_two_factor_challenge(
+    user_id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<TwoFactorChallenge, sqlx::Error> {
+    let challenge = sqlx::query_as!(
+        TwoFactorChallenge,
+        r#"
+    INSERT INTO two_factor_challenges(id, user_id, token_hash, expires_at) values($1,$2,$3,$4) RETURNING *;
+    "#,
+        Uuid::new_v4(),
+        user_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(challenge)
+}
+
+/// Finds an unexpired challenge
+pub async fn find_two_factor_challenge(
+    token_hash: &str,
+    db_pool: &PgPool,
+) -> Result<Option<TwoFactorChallenge>, sqlx::Error> {
+    let challenge = sqlx::query_as!(
+        TwoFactorChallenge,
+        r#"select * from two_factor_challenges where token_hash = $1 and expires_at > now()"#,
+        token_hash
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(challenge)
+}
+
+/// Counts a wrong code for the challenge, deleting it after `max_attempts`
+pub async fn record_failed_challenge_attempt(
+    id: Uuid,
+    max_attempts: i32,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let attempts = sqlx::query_scalar!(
+        r#"UPDATE two_factor_challenges SET attempts = attempts + 1 WHERE id = $1 RETURNING attempts"#,
+        id
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    if attempts.is_some_and(|attempts| attempts >= max_attempts) {
+        sqlx::query!(r#"delete from two_factor_challenges where id = $1"#, id)
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Deletes a challenge once it was answered, returns false if it already was
+pub async fn consume_two_factor_challenge(id: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(r#"delete from two_factor_challenges where id = $1"#, id)
+        .execute(db_pool)
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_utils::{self, insert_user};
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn enabled_totp_cannot_be_replaced() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        create_pending_totp(user_id, "FIRST", &db_pool)
+            .await
+            .unwrap();
+        let replaced = create_pending_totp(user_id, "SECOND", &db_pool)
+            .await
+            .unwrap();
+        let enabled = enable_totp(user_id, 10, &["hash".into()], &db_pool)
+            .await
+            .unwrap();
+        let after_enabled = create_pending_totp(user_id, "THIRD", &db_pool)
+            .await
+            .unwrap();
+        let totp = find_user_totp(user_id, &db_pool).await.unwrap().unwrap();
+        let codes = find_unused_recovery_codes(user_id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(replaced.map(|totp| totp.secret), Some("SECOND".into()));
+        assert!(enabled);
+        assert!(after_enabled.is_none());
+        assert_eq!(totp.secret, "SECOND");
+        assert!(totp.is_enabled());
+        assert_eq!(codes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn totp_steps_cannot_be_reused() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        create_pending_totp(user_id, "SECRET", &db_pool)
+            .await
+            .unwrap();
+        enable_totp(user_id, 10, &[], &db_pool).await.unwrap();
+
+        let same_step = use_totp_step(user_id, 10, &db_pool).await.unwrap();
+        let next_step = use_totp_step(user_id, 11, &db_pool).await.unwrap();
+        let previous_step = use_totp_step(user_id, 10, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(!same_step);
+        assert!(next_step);
+        assert!(!previous_step);
+    }
+
+    #[tokio::test]
+    async fn plaintext_secrets_are_sealed_once() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+        let cipher = SecretCipher::from_key(&config.app_settings.totp_encryption_key).unwrap();
+
+        create_pending_totp(user_id, "JBSWY3DPEHPK3PXP", &db_pool)
+            .await
+            .unwrap();
+        let first = seal_plaintext_totp_secrets(&cipher, &db_pool)
+            .await
+            .unwrap();
+        let second = seal_plaintext_totp_secrets(&cipher, &db_pool)
+            .await
+            .unwrap();
+        let totp = find_user_totp(user_id, &db_pool).await.unwrap().unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(first, 1);
+        assert_eq!(second, 0);
+        assert_eq!(
+            cipher.open(&totp.secret, user_id.as_bytes()).unwrap(),
+            "JBSWY3DPEHPK3PXP"
+        );
+    }
+
+    #[tokio::test]
+    async fn challenge_is_deleted_after_max_attempts() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+        let user_id = insert_user(&db_pool, "username").await;
+
+        let challenge = create_two_factor_challenge(
+            user_id,
+            "hash",
+            Utc::now() + Duration::minutes(5),
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        record_failed_challenge_attempt(challenge.id, 2, &db_pool)
+            .await
+            .unwrap();
+        let after_first = find_two_factor_challenge("hash", &db_pool).await.unwrap();
+        record_failed_challenge_attempt(challenge.id, 2, &db_pool)
+            .await
+            .unwrap();
+        let after_second = find_two_factor_challenge("hash", &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(after_first.map(|challenge| challenge.attempts), Some(1));
+        assert!(after_second.is_none());
+    }
+}
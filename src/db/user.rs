@@ -1,4 +1,5 @@
-use crate::domain::user::{CreateUser, User};
+use crate::domain::user::{CreateUser, Role, User};
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -7,7 +8,9 @@ pub async fn create_user(user_input: CreateUser, db_pool: &PgPool) -> Result<Use
     let user = sqlx::query_as!(
         User,
         r#"
-    INSERT INTO users(id, username, email,password_hash) values($1,$2,$3,$4) RETURNING *;
+    INSERT INTO users(id, username, email,password_hash) values($1,$2,$3,$4)
+    RETURNING id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at;
     "#,
         Uuid::new_v4(),
         user_input.username,
@@ -15,8 +18,7 @@ pub async fn create_user(user_input: CreateUser, db_pool: &PgPool) -> Result<Use
         user_input.password
     )
     .fetch_one(db_pool)
-    .await
-    .unwrap();
+    .await?;
 
     Ok(user)
 }
@@ -25,28 +27,279 @@ pub async fn find_user_by_username(
     username: &str,
     db_pool: &PgPool,
 ) -> Result<Option<User>, sqlx::Error> {
-    let user = sqlx::query_as!(User, r#"select * from users where username = $1"#, username)
-        .fetch_optional(db_pool)
-        .await?;
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    select id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at
+    from users where username = $1
+    "#,
+        username
+    )
+    .fetch_optional(db_pool)
+    .await?;
 
     Ok(user)
 }
 
-#[tracing::instrument]
-pub async fn user_exists_by_username_or_email(
-    username: &str,
+/// Finds a user by email when the login contains `@` and by username
+/// otherwise. Usernames registered before `@` was forbidden in them can still
+/// be used, when no email matches
+pub async fn find_user_by_login(
+    login: &str,
+    db_pool: &PgPool,
+) -> Result<Option<User>, sqlx::Error> {
+    if login.contains('@') {
+        if let Some(user) = find_user_by_email(login, db_pool).await? {
+            return Ok(Some(user));
+        }
+    }
+
+    find_user_by_username(login, db_pool).await
+}
+
+pub async fn find_user_by_email(
     email: &str,
     db_pool: &PgPool,
-) -> Result<Option<i64>, sqlx::Error> {
-    let row = sqlx::query!(
-        r#"select count(*) from users where username = $1 or email = $2"#,
-        username,
+) -> Result<Option<User>, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    select id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at
+    from users where email = $1
+    "#,
+        email
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Whether an account uses the email, whatever its case
+pub async fn email_exists(email: &str, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let exists = sqlx::query_scalar!(
+        r#"select exists(select 1 from users where lower(email) = lower($1)) as "exists!""#,
         email
     )
     .fetch_one(db_pool)
     .await?;
 
-    Ok(row.count)
+    Ok(exists)
+}
+
+pub async fn find_user_by_id(id: Uuid, db_pool: &PgPool) -> Result<Option<User>, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    select id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at
+    from users where id = $1
+    "#,
+        id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Updates the given fields of a user, `None` fields are left untouched
+#[tracing::instrument(skip(password_hash))]
+pub async fn update_user(
+    id: Uuid,
+    username: Option<&str>,
+    password_hash: Option<&str>,
+    db_pool: &PgPool,
+) -> Result<Option<User>, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    UPDATE users SET
+        username = COALESCE($2, username),
+        password_hash = COALESCE($3, password_hash),
+        password_reset_required = password_reset_required and $3::varchar is null,
+        updated_at = now()
+    WHERE id = $1
+    RETURNING id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at;
+    "#,
+        id,
+        username,
+        password_hash
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Replaces a password hash by one of the same password made with other
+/// parameters, unless the password was changed in the meantime
+#[tracing::instrument(skip(old_password_hash, password_hash))]
+pub async fn rehash_user_password(
+    id: Uuid,
+    old_password_hash: &str,
+    password_hash: &str,
+    db_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE users SET password_hash = $3 WHERE id = $1 and password_hash = $2"#,
+        id,
+        old_password_hash,
+        password_hash
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Lists users, optionally those whose username or email contains `search`
+pub async fn search_users(
+    search: Option<&str>,
+    limit: i64,
+    offset: i64,
+    db_pool: &PgPool,
+) -> Result<Vec<User>, sqlx::Error> {
+    // Matching the search literally, not as a LIKE pattern
+    let pattern = search.map(|search| {
+        format!(
+            "%{}%",
+            search
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        )
+    });
+
+    let users = sqlx::query_as!(
+        User,
+        r#"
+    select id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at
+    from users
+    where $1::text is null or username ilike $1 or email ilike $1
+    order by created_at, id limit $2 offset $3
+    "#,
+        pattern,
+        limit,
+        offset
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(users)
+}
+
+/// Disables or re-enables a user, a disabled user cannot log in
+#[tracing::instrument]
+pub async fn set_user_disabled(
+    id: Uuid,
+    disabled: bool,
+    db_pool: &PgPool,
+) -> Result<Option<User>, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    UPDATE users SET
+        disabled_at = case when $2 then COALESCE(disabled_at, now()) else null end,
+        updated_at = now()
+    WHERE id = $1
+    RETURNING id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at;
+    "#,
+        id,
+        disabled
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Prevents the user from logging in until they reset their password
+#[tracing::instrument]
+pub async fn require_password_reset(
+    id: Uuid,
+    db_pool: &PgPool,
+) -> Result<Option<User>, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    UPDATE users SET password_reset_required = true, updated_at = now()
+    WHERE id = $1
+    RETURNING id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at;
+    "#,
+        id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Deletes a user along with everything they own
+#[tracing::instrument]
+pub async fn delete_user(id: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(r#"delete from users where id = $1"#, id)
+        .execute(db_pool)
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Schedules the deletion of a user, unless a deletion is already scheduled
+#[tracing::instrument]
+pub async fn schedule_user_deletion(
+    id: Uuid,
+    scheduled_at: DateTime<Utc>,
+    db_pool: &PgPool,
+) -> Result<Option<User>, sqlx::Error> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+    UPDATE users SET
+        deletion_scheduled_at = COALESCE(deletion_scheduled_at, $2),
+        updated_at = now()
+    WHERE id = $1
+    RETURNING id, username, email, password_hash, created_at, updated_at, email_verified_at,
+        role as "role: Role", disabled_at, password_reset_required, deletion_scheduled_at;
+    "#,
+        id,
+        scheduled_at
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Cancels the scheduled deletion of a user, returns whether one was scheduled
+#[tracing::instrument]
+pub async fn cancel_user_deletion(id: Uuid, db_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE users SET deletion_scheduled_at = null, updated_at = now()
+    WHERE id = $1 and deletion_scheduled_at is not null
+    "#,
+        id
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Deletes the users whose grace period is over, returns how many were deleted
+pub async fn delete_users_scheduled_for_deletion(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(r#"delete from users where deletion_scheduled_at <= now()"#)
+        .execute(db_pool)
+        .await?;
+
+    Ok(result.rows_affected())
 }
 
 #[cfg(test)]
@@ -105,6 +358,361 @@ mod tests {
         assert_eq!(created_user, user);
     }
 
+    #[tokio::test]
+    async fn find_user_by_id_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        // Creating user input
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        // Checking inserted user
+        let user = find_user_by_id(created_user.id, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(created_user, user);
+    }
+
+    #[tokio::test]
+    async fn update_user_with_success() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        // Creating user input
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        let user = update_user(created_user.id, Some("new_username"), None, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(user.username, "new_username");
+        assert_eq!(user.password_hash, created_user.password_hash);
+        assert!(user.updated_at > created_user.updated_at);
+    }
+
+    #[tokio::test]
+    async fn find_user_by_login_with_username_or_email() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        // Creating user input
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        let by_username = find_user_by_login("username", &db_pool).await.unwrap();
+        let by_email = find_user_by_login("email@gmail.com", &db_pool)
+            .await
+            .unwrap();
+        let unknown = find_user_by_login("unknown", &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(by_username, Some(created_user.clone()));
+        assert_eq!(by_email, Some(created_user));
+        assert!(unknown.is_none());
+    }
+
+    #[tokio::test]
+    async fn email_exists_whatever_its_case() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+
+        create_user(user_input, &db_pool).await.unwrap();
+
+        let same_case = email_exists("email@gmail.com", &db_pool).await.unwrap();
+        let other_case = email_exists("Email@Gmail.com", &db_pool).await.unwrap();
+        let unknown = email_exists("other@gmail.com", &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(same_case);
+        assert!(other_case);
+        assert!(!unknown);
+    }
+
+    #[tokio::test]
+    async fn find_user_by_login_with_legacy_username() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        // Usernames could contain `@` before it was forbidden
+        let user_input = CreateUser {
+            username: "legacy@username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        let by_username = find_user_by_login("legacy@username", &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(by_username, Some(created_user));
+    }
+
+    #[tokio::test]
+    async fn search_users_by_username_or_email() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        for (username, email) in [
+            ("first_user", "first@gmail.com"),
+            ("second_user", "100%@gmail.com"),
+        ] {
+            let user_input = CreateUser {
+                username: username.into(),
+                email: email.into(),
+                password: "password".into(),
+            };
+            create_user(user_input, &db_pool).await.unwrap();
+        }
+
+        let all = search_users(None, 10, 0, &db_pool).await.unwrap();
+        let by_username = search_users(Some("FIRST"), 10, 0, &db_pool).await.unwrap();
+        let by_email = search_users(Some("100%"), 10, 0, &db_pool).await.unwrap();
+        let wildcard = search_users(Some("%"), 10, 0, &db_pool).await.unwrap();
+        let second_page = search_users(None, 1, 1, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(all.len(), 2);
+        assert_eq!(by_username.len(), 1);
+        assert_eq!(by_username[0].username, "first_user");
+        assert_eq!(by_email.len(), 1);
+        assert_eq!(by_email[0].username, "second_user");
+        assert_eq!(wildcard.len(), 1);
+        assert_eq!(second_page.len(), 1);
+        assert_eq!(second_page[0].username, "second_user");
+    }
+
+    #[tokio::test]
+    async fn disable_and_enable_user() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        let disabled = set_user_disabled(created_user.id, true, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+        let disabled_again = set_user_disabled(created_user.id, true, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+        let enabled = set_user_disabled(created_user.id, false, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+        let unknown = set_user_disabled(Uuid::new_v4(), true, &db_pool)
+            .await
+            .unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert_eq!(created_user.role, Role::User);
+        assert!(created_user.disabled_at.is_none());
+        assert!(disabled.is_disabled());
+        assert_eq!(disabled_again.disabled_at, disabled.disabled_at);
+        assert!(!enabled.is_disabled());
+        assert!(unknown.is_none());
+    }
+
+    #[tokio::test]
+    async fn password_change_clears_required_reset() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        let required = require_password_reset(created_user.id, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+        let renamed = update_user(created_user.id, Some("new_username"), None, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+        let reset = update_user(created_user.id, None, Some("new_hash"), &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(required.password_reset_required);
+        assert!(renamed.password_reset_required);
+        assert!(!reset.password_reset_required);
+    }
+
+    #[tokio::test]
+    async fn rehash_keeps_required_reset_and_skips_changed_password() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "old_hash".into(),
+        };
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        require_password_reset(created_user.id, &db_pool)
+            .await
+            .unwrap();
+        let rehashed = rehash_user_password(created_user.id, "old_hash", "new_hash", &db_pool)
+            .await
+            .unwrap();
+        let stale = rehash_user_password(created_user.id, "old_hash", "other_hash", &db_pool)
+            .await
+            .unwrap();
+        let user = find_user_by_id(created_user.id, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(rehashed);
+        assert!(!stale);
+        assert_eq!(user.password_hash, "new_hash");
+        assert!(user.password_reset_required);
+    }
+
+    #[tokio::test]
+    async fn delete_user_once() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        let user_input = CreateUser {
+            username: "username".into(),
+            email: "email@gmail.com".into(),
+            password: "password".into(),
+        };
+        let created_user = create_user(user_input, &db_pool).await.unwrap();
+
+        let deleted = delete_user(created_user.id, &db_pool).await.unwrap();
+        let deleted_again = delete_user(created_user.id, &db_pool).await.unwrap();
+        let user = find_user_by_id(created_user.id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(deleted);
+        assert!(!deleted_again);
+        assert!(user.is_none());
+    }
+
+    #[tokio::test]
+    async fn schedule_cancel_and_purge_user_deletion() {
+        // Init database
+        let (config, db_pool) = test_utils::configure_database().await;
+
+        let kept_input = CreateUser {
+            username: "kept_user".into(),
+            email: "kept@gmail.com".into(),
+            password: "password".into(),
+        };
+        let kept_user = create_user(kept_input, &db_pool).await.unwrap();
+        let deleted_input = CreateUser {
+            username: "deleted_user".into(),
+            email: "deleted@gmail.com".into(),
+            password: "password".into(),
+        };
+        let deleted_user = create_user(deleted_input, &db_pool).await.unwrap();
+
+        let in_a_month = Utc::now() + chrono::Duration::days(30);
+        let scheduled = schedule_user_deletion(kept_user.id, in_a_month, &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+        let scheduled_again = schedule_user_deletion(kept_user.id, Utc::now(), &db_pool)
+            .await
+            .unwrap()
+            .expect("user not found");
+        let purged_early = delete_users_scheduled_for_deletion(&db_pool).await.unwrap();
+        let cancelled = cancel_user_deletion(kept_user.id, &db_pool).await.unwrap();
+        let cancelled_again = cancel_user_deletion(kept_user.id, &db_pool).await.unwrap();
+
+        let yesterday = Utc::now() - chrono::Duration::days(1);
+        schedule_user_deletion(deleted_user.id, yesterday, &db_pool)
+            .await
+            .unwrap();
+        let purged = delete_users_scheduled_for_deletion(&db_pool).await.unwrap();
+        let kept_user = find_user_by_id(kept_user.id, &db_pool).await.unwrap();
+        let deleted_user = find_user_by_id(deleted_user.id, &db_pool).await.unwrap();
+
+        // Dropping database
+        test_utils::drop_db(config, db_pool).await;
+
+        assert!(scheduled.deletion_scheduled_at.is_some());
+        assert_eq!(
+            scheduled_again.deletion_scheduled_at,
+            scheduled.deletion_scheduled_at
+        );
+        assert_eq!(purged_early, 0);
+        assert!(cancelled);
+        assert!(!cancelled_again);
+        assert_eq!(purged, 1);
+        assert!(kept_user
+            .expect("user not found")
+            .deletion_scheduled_at
+            .is_none());
+        assert!(deleted_user.is_none());
+    }
+
     #[tokio::test]
     async fn find_user_none() {
         // Init database
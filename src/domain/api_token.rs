@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Prefix telling api tokens apart from access tokens (JWT)
+pub const API_TOKEN_PREFIX: &str = "tdo_";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiToken {
+    pub fn has_scope(&self, scope: ApiTokenScope) -> bool {
+        self.scopes.iter().any(|s| s == scope.as_str())
+    }
+}
+
+/// What an api token is allowed to do, tokens cannot manage the account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiTokenScope {
+    #[serde(rename = "tasks:read")]
+    TasksRead,
+    #[serde(rename = "tasks:write")]
+    TasksWrite,
+    #[serde(rename = "lists:read")]
+    ListsRead,
+    #[serde(rename = "lists:write")]
+    ListsWrite,
+    /// Reading the profile of the user
+    #[serde(rename = "user:read")]
+    UserRead,
+}
+
+impl ApiTokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::TasksRead => "tasks:read",
+            ApiTokenScope::TasksWrite => "tasks:write",
+            ApiTokenScope::ListsRead => "lists:read",
+            ApiTokenScope::ListsWrite => "lists:write",
+            ApiTokenScope::UserRead => "user:read",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiToken {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<ApiTokenScope>,
+    /// Tokens without expiry stay valid until revoked
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Returned once on creation, only the hash of the token is stored
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub token: String,
+    pub api_token: ApiToken,
+}
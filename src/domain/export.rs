@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    api_token::ApiToken, list::List, notification::Notification, oidc::UserIdentity,
+    recurrence::TaskRecurrence, reminder::TaskReminder, tag::Tag, task::TaggedTask, user::User,
+};
+
+/// Archive of everything stored about a user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub two_factor_enabled: bool,
+    pub lists: Vec<List>,
+    pub tags: Vec<Tag>,
+    pub tasks: Vec<TaggedTask>,
+    pub recurrences: Vec<TaskRecurrence>,
+    pub reminders: Vec<TaskReminder>,
+    pub notifications: Vec<Notification>,
+    pub api_tokens: Vec<ApiToken>,
+    pub identities: Vec<UserIdentity>,
+}
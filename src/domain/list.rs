@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct List {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateList {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateList {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+/// What happens to the tasks of a deleted list
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteListMode {
+    /// Tasks are kept without a list
+    #[default]
+    Orphan,
+    /// Tasks are deleted along with the list
+    Cascade,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteListOptions {
+    #[serde(default)]
+    pub tasks: DeleteListMode,
+}
@@ -1 +1,15 @@
+pub mod api_token;
+pub mod email_verification;
+pub mod export;
+pub mod list;
+pub mod notification;
+pub mod oidc;
+pub mod password_reset;
+pub mod rate_limit;
+pub mod recurrence;
+pub mod refresh_token;
+pub mod reminder;
+pub mod tag;
+pub mod task;
+pub mod two_factor;
 pub mod user;
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Account of an identity provider linked to a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Name of the provider in the settings
+    pub provider: String,
+    /// Identifier of the account on the provider (`sub` claim)
+    pub subject: String,
+    pub email: Option<String>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Login or account linking started on an identity provider, waiting for the
+/// provider to redirect back
+#[derive(Debug, Clone)]
+pub struct OidcState {
+    pub id: Uuid,
+    pub state_hash: String,
+    pub provider: String,
+    pub nonce: String,
+    /// PKCE secret, only its hash was sent to the provider
+    pub code_verifier: String,
+    /// Set when a logged in user links the provider to their account
+    pub user_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where to send the user to log in on the identity provider
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcAuthorization {
+    pub authorization_url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Parameters the identity provider redirected the user back with
+#[derive(Debug, Deserialize)]
+pub struct OidcCallback {
+    pub code: String,
+    pub state: String,
+}
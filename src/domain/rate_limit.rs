@@ -0,0 +1,11 @@
+use chrono::{DateTime, Utc};
+
+/// Number of hits of a key in the current window
+#[derive(Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct RateLimitCounter {
+    pub key: String,
+    pub count: i32,
+    pub expires_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
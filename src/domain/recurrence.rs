@@ -0,0 +1,50 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Series of tasks repeating along a recurrence rule, completing one of them
+/// creates the next one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct TaskRecurrence {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// RRULE (RFC 5545), such as `FREQ=WEEKLY;BYDAY=MO,TH`
+    pub rule: String,
+    /// IANA time zone occurrences keep their local time in
+    pub timezone: String,
+    /// Due date of the first task of the series
+    pub starts_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SetRecurrence {
+    /// Either a bare rule or an `RRULE:` property
+    #[validate(length(min = 1, max = 255))]
+    pub rule: String,
+    /// IANA time zone, such as `Europe/Paris`
+    #[validate(length(min = 1, max = 64))]
+    pub timezone: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PreviewOccurrences {
+    #[serde(default = "default_preview_count")]
+    #[validate(range(min = 1, max = 100))]
+    pub count: usize,
+}
+
+fn default_preview_count() -> usize {
+    5
+}
+
+/// Due date of an upcoming task of a series
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct Occurrence {
+    pub due_at: DateTime<Utc>,
+    /// Time of the occurrence in the time zone of the series
+    pub local_due_at: NaiveDateTime,
+}
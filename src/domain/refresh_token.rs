@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub replaced_by: Option<Uuid>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RefreshToken {
+    /// A token that was already rotated or revoked must never be presented again
+    pub fn is_used(&self) -> bool {
+        self.replaced_by.is_some() || self.revoked_at.is_some()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenInput {
+    pub refresh_token: String,
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Where a reminder is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "notification_channel", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    /// Sent to the email address of the user
+    Email,
+    /// Posted to the webhook url of the reminder
+    Webhook,
+    /// Listed on `/api/users/me/notifications`
+    InApp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct TaskReminder {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub user_id: Uuid,
+    pub remind_at: DateTime<Utc>,
+    /// IANA time zone the reminder was set in
+    pub timezone: String,
+    pub channel: NotificationChannel,
+    pub webhook_url: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    /// Set once every delivery attempt failed
+    pub failed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateReminder {
+    /// Local time of the reminder in `timezone`, without offset
+    pub remind_at: NaiveDateTime,
+    /// IANA time zone, such as `Europe/Paris`
+    #[validate(length(min = 1, max = 64))]
+    pub timezone: String,
+    pub channel: NotificationChannel,
+    /// Required by the webhook channel
+    #[validate(url)]
+    pub webhook_url: Option<String>,
+}
+
+/// Reminder claimed for delivery, along with what its notification shows
+#[derive(Debug, Clone)]
+pub struct DueReminder {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+    pub task_title: String,
+    pub task_completed: bool,
+    pub due_at: Option<DateTime<Utc>>,
+    pub remind_at: DateTime<Utc>,
+    pub timezone: String,
+    pub channel: NotificationChannel,
+    pub webhook_url: Option<String>,
+    /// Including the current one
+    pub attempts: i32,
+    /// End of the claim of the worker delivering the reminder
+    pub lease_until: DateTime<Utc>,
+}
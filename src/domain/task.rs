@@ -0,0 +1,203 @@
+use chrono::{DateTime, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::tag::Tag;
+
+/// Levels of subtasks a top level task can have
+pub const MAX_SUBTASK_DEPTH: i64 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct Task {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub list_id: Option<Uuid>,
+    /// Task this one is a subtask of
+    pub parent_id: Option<Uuid>,
+    pub title: String,
+    pub description: Option<String>,
+    pub completed: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub due_at: Option<DateTime<Utc>>,
+    /// Series the task is an occurrence of
+    pub recurrence_id: Option<Uuid>,
+    /// Rank of the task in its list, tasks are listed by ascending position
+    pub position: String,
+}
+
+/// Task along with the tags attached to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct TaggedTask {
+    #[serde(flatten)]
+    pub task: Task,
+    pub tags: Vec<Tag>,
+}
+
+/// Task along with its subtasks, recursively
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct TaskTree {
+    #[serde(flatten)]
+    pub task: TaggedTask,
+    /// Completion of the direct subtasks, `None` without subtasks
+    pub progress: Option<TaskProgress>,
+    pub subtasks: Vec<TaskTree>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct TaskProgress {
+    pub completed: usize,
+    pub total: usize,
+    /// Rounded down
+    pub percent: u8,
+}
+
+impl TaskProgress {
+    pub fn of(subtasks: &[TaskTree]) -> Option<Self> {
+        if subtasks.is_empty() {
+            return None;
+        }
+
+        let total = subtasks.len();
+        let completed = subtasks
+            .iter()
+            .filter(|subtask| subtask.task.task.completed)
+            .count();
+
+        Some(Self {
+            completed,
+            total,
+            percent: (completed * 100 / total) as u8,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateTask {
+    #[validate(length(min = 1, max = 255))]
+    pub title: String,
+    #[validate(length(max = 2000))]
+    pub description: Option<String>,
+    pub list_id: Option<Uuid>,
+    /// Creates the task as a subtask of this one
+    pub parent_id: Option<Uuid>,
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub tag_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateTask {
+    #[validate(length(min = 1, max = 255))]
+    pub title: Option<String>,
+    /// `Some(None)` clears the description, `None` keeps it
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(length(max = 2000))]
+    pub description: Option<Option<String>>,
+    pub completed: Option<bool>,
+    /// `Some(None)` clears the due date, `None` keeps it
+    #[serde(default, deserialize_with = "double_option")]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    /// Replaces the tags of the task, `None` keeps them
+    #[validate(length(max = 20))]
+    pub tag_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveTask {
+    /// Destination list, `None` takes the task out of its current list
+    pub list_id: Option<Uuid>,
+}
+
+/// Body of a task move, at most one of `after` and `before` is given
+#[derive(Debug, Deserialize)]
+pub struct ReorderTask {
+    /// Puts the task right after this one, in its list
+    pub after: Option<Uuid>,
+    /// Puts the task right before this one, in its list
+    pub before: Option<Uuid>,
+    /// Destination list, the task goes at its end without `after` or `before`.
+    /// `None` is the list of the anchor, or the inbox without one
+    pub list_id: Option<Uuid>,
+}
+
+/// Where a moved task goes in its destination list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskPlacement {
+    After(Uuid),
+    Before(Uuid),
+    End,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetTaskParent {
+    /// New parent task, `None` makes the task a top level one
+    pub parent_id: Option<Uuid>,
+}
+
+/// What happens to the subtasks of a task whose completion changes
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtaskCompletion {
+    /// Subtasks keep their own completion
+    #[default]
+    Keep,
+    /// Subtasks, at every depth, get the completion of the task
+    Cascade,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateTaskOptions {
+    #[serde(default)]
+    pub subtasks: SubtaskCompletion,
+}
+
+/// How the tags of a task listing filter are combined
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    /// Tasks having at least one of the tags
+    #[default]
+    Any,
+    /// Tasks having every tag
+    All,
+}
+
+/// Query parameters of the task listing
+#[derive(Debug, Default, Deserialize)]
+pub struct FilterTasks {
+    /// Comma separated tag ids, every task is listed when empty
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub tags: Vec<Uuid>,
+    #[serde(default, rename = "match")]
+    pub tag_match: TagMatch,
+}
+
+fn comma_separated<'de, D>(deserializer: D) -> Result<Vec<Uuid>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ids = String::deserialize(deserializer)?;
+
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+/// Tells a `null` field, deserialized as `Some(None)`, from a missing one,
+/// left to `None` by `#[serde(default)]`
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+pub struct UserTotp {
+    pub user_id: Uuid,
+    /// Secret shared with the authenticator app, sealed with the totp cipher
+    pub secret: String,
+    /// Set once the user confirmed a first code, 2FA is pending until then
+    pub enabled_at: Option<DateTime<Utc>>,
+    /// Last time step a code was accepted for, codes cannot be replayed
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UserTotp {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecoveryCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub code_hash: String,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TwoFactorChallenge {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub attempts: i32,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmTwoFactor {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodes {
+    /// Shown once, only their hashes are stored
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTwoFactor {
+    pub password: String,
+}
+
+/// Returned by the login instead of a session when 2FA is enabled
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorRequired {
+    pub challenge_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Second step of the login, either a code or a recovery code is required
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLogin {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
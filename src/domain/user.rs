@@ -1,49 +1,123 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::utils::validation::field_error;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: String,
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     pub password_hash: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub role: Role,
+    pub disabled_at: Option<DateTime<Utc>>,
+    /// Set by an admin, the user has to reset their password before logging in again
+    pub password_reset_required: bool,
+    /// Set when the user asked for their account to be deleted, the account
+    /// is deleted for good once this date is passed
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Admin,
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateUser {
-    #[validate(length(min = 6, max = 25))]
+    #[validate(length(min = 6, max = 25), custom = "validate_username")]
     pub username: String,
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 6))]
+    /// Checked against the password policy
     pub password: String,
 }
 
+/// Usernames cannot contain `@`, so that a login is never both a username and
+/// the email of another user
+fn validate_username(username: &str) -> Result<(), ValidationError> {
+    if username.contains('@') {
+        return Err(field_error("username", "cannot contain @"));
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Deserialize)]
 pub struct FindUser {
-    pub username: String,
+    /// Either the username or the email of the user
+    #[serde(alias = "username", alias = "email")]
+    pub login: String,
     pub password: String,
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct UpdateUser {
-    #[validate(length(min = 6, max = 25))]
-    pub username: String,
+    #[validate(length(min = 6, max = 25), custom = "validate_username")]
+    pub username: Option<String>,
+    /// Current password, required to confirm any change
     #[validate(length(min = 6))]
     pub old_password: String,
-    #[validate(length(min = 6))]
-    pub new_password: String,
+    /// Checked against the password policy
+    pub new_password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccount {
+    /// Current password, required to confirm the deletion
+    pub password: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountDeletion {
+    pub deletion_scheduled_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Claims {
     pub sub: String,
+    /// Unique token id, used to revoke a single token
+    pub jti: Uuid,
+    /// Refresh token family (session) the token was issued for
+    pub sid: Uuid,
+    /// Role of the user when the token was issued
+    #[serde(default)]
+    pub role: Role,
+    // Registered claims are NumericDate values (seconds since epoch)
+    #[serde(with = "chrono::serde::ts_seconds")]
     pub iat: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
     pub exp: DateTime<Utc>,
 }
+
+/// Query parameters of the admin user listing
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchUsers {
+    /// Part of the username or email to look for
+    pub search: Option<String>,
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+    #[validate(range(min = 0))]
+    pub offset: Option<i64>,
+}
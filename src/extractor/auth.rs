@@ -0,0 +1,301 @@
+use axum::{
+    async_trait,
+    extract::{FromRequest, OriginalUri, RequestParts},
+    http::{header::AUTHORIZATION, Method},
+};
+use axum_extra::extract::cookie::CookieJar;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    configuration::EmailVerificationPolicy,
+    db::{
+        api_token::{find_active_api_token_by_hash, touch_api_token},
+        refresh_token::is_session_active,
+        revoked_token::is_token_revoked,
+        user::find_user_by_id,
+    },
+    domain::{
+        api_token::{ApiToken, ApiTokenScope, API_TOKEN_PREFIX},
+        user::{Claims, User},
+    },
+    handler::ApiError,
+    router::State,
+    utils::{
+        cookie::{csrf_token_matches, ACCESS_TOKEN_COOKIE},
+        jwt::decode_token,
+        token::hash_token,
+    },
+};
+
+/// Tolerated clock skew when checking the `iat` claim
+const IAT_LEEWAY_SECONDS: i64 = 60;
+
+/// Extracts the user authenticated by the `Authorization: Bearer <token>` header,
+/// the token being either an access token or an api token, or by the access
+/// token cookie when the session transport uses cookies.
+///
+/// The extracted user is cached in the request extensions so that a handler
+/// behind an [`authenticated`](crate::router::authenticated) router does not hit
+/// the database a second time.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user: User,
+    pub credentials: Credentials,
+}
+
+/// How the user authenticated
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    /// Access token of a session opened by logging in
+    Session(Claims),
+    ApiToken(ApiToken),
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for AuthUser
+where
+    B: Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        if let Some(auth_user) = req.extensions().get::<AuthUser>() {
+            return Ok(auth_user.clone());
+        }
+
+        let state = req
+            .extensions()
+            .get::<Arc<State>>()
+            .cloned()
+            .expect("state extension is missing");
+
+        let bearer = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_owned);
+        let transport = state.session_transport;
+        let path = match req.extensions().get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.path().to_owned(),
+            None => req.uri().path().to_owned(),
+        };
+
+        let auth_user = match bearer {
+            // Api tokens are meant for scripts, they are accepted whatever the session transport
+            Some(token) if token.starts_with(API_TOKEN_PREFIX) => {
+                let scope = required_scope(req.method(), &path);
+
+                authenticate_api_token(&token, scope, &state).await?
+            }
+            Some(token) if transport.uses_header() => {
+                authenticate_access_token(&token, &state).await?
+            }
+            _ if transport.uses_cookie() => {
+                let jar = CookieJar::from_request(req)
+                    .await
+                    .expect("cookie jar extraction is infallible");
+                let token = jar
+                    .get(ACCESS_TOKEN_COOKIE)
+                    .map(|cookie| cookie.value().to_owned())
+                    .ok_or(ApiError::Unauthorized)?;
+
+                // Cookies are sent along with cross-site requests, unlike headers
+                if !is_safe_method(req.method()) && !csrf_token_matches(&jar, req.headers()) {
+                    return Err(ApiError::InvalidCsrfToken);
+                }
+
+                authenticate_access_token(&token, &state).await?
+            }
+            _ => return Err(ApiError::Unauthorized),
+        };
+
+        // Tokens issued before the account was disabled are no longer honored
+        if auth_user.user.is_disabled() {
+            return Err(ApiError::AccountDisabled);
+        }
+        // Api tokens outlive sessions, logging in again is the way to cancel a deletion
+        if auth_user.user.deletion_scheduled_at.is_some() {
+            return Err(ApiError::Unauthorized);
+        }
+        // Once a reset is required, the account can only change its password
+        if auth_user.user.password_reset_required && !is_password_change(req.method(), &path) {
+            return Err(ApiError::PasswordResetRequired);
+        }
+
+        req.extensions_mut().insert(auth_user.clone());
+
+        Ok(auth_user)
+    }
+}
+
+async fn authenticate_access_token(token: &str, state: &State) -> Result<AuthUser, ApiError> {
+    // Checking signature and expiration
+    let claims = decode_token(token, &state.jwt_keys).map_err(|_| ApiError::Unauthorized)?;
+
+    // Rejecting tokens issued in the future
+    if claims.iat > Utc::now() + Duration::seconds(IAT_LEEWAY_SECONDS) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    // Rejecting tokens revoked on logout, or whose session was revoked
+    if is_token_revoked(claims.jti, &state.db_pool).await?
+        || !is_session_active(claims.sid, &state.db_pool).await?
+    {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized)?;
+
+    let user = find_user_by_id(user_id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    Ok(AuthUser {
+        user,
+        credentials: Credentials::Session(claims),
+    })
+}
+
+async fn authenticate_api_token(
+    token: &str,
+    scope: Option<ApiTokenScope>,
+    state: &State,
+) -> Result<AuthUser, ApiError> {
+    let api_token = find_active_api_token_by_hash(&hash_token(token), &state.db_pool)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    if !scope.is_some_and(|scope| api_token.has_scope(scope)) {
+        return Err(ApiError::InsufficientScope);
+    }
+
+    touch_api_token(api_token.id, &state.db_pool).await?;
+
+    let user = find_user_by_id(api_token.user_id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    Ok(AuthUser {
+        user,
+        credentials: Credentials::ApiToken(api_token),
+    })
+}
+
+/// Whether the request goes to `PATCH /api/users/me`, which changes the password
+fn is_password_change(method: &Method, path: &str) -> bool {
+    method == Method::PATCH && path.trim_end_matches('/') == "/api/users/me"
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Scope an api token needs for a request, `None` when api tokens are not
+/// accepted at all (managing the account, sessions and tokens)
+fn required_scope(method: &Method, path: &str) -> Option<ApiTokenScope> {
+    let read = method == Method::GET || method == Method::HEAD;
+    let resource = path.trim_start_matches("/api/").split('/').next();
+
+    match (resource, read) {
+        // Tags only label tasks, they share their scopes
+        (Some("tasks" | "tags"), true) => Some(ApiTokenScope::TasksRead),
+        (Some("tasks" | "tags"), false) => Some(ApiTokenScope::TasksWrite),
+        (Some("lists"), true) => Some(ApiTokenScope::ListsRead),
+        (Some("lists"), false) => Some(ApiTokenScope::ListsWrite),
+        (Some("users"), true) if path.trim_end_matches('/') == "/api/users/me" => {
+            Some(ApiTokenScope::UserRead)
+        }
+        _ => None,
+    }
+}
+
+/// Extracts an [`AuthUser`] whose email is verified, when the email
+/// verification policy restricts unverified users.
+#[derive(Debug, Clone)]
+pub struct VerifiedUser(pub AuthUser);
+
+#[async_trait]
+impl<B> FromRequest<B> for VerifiedUser
+where
+    B: Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let auth_user = AuthUser::from_request(req).await?;
+
+        let state = req
+            .extensions()
+            .get::<Arc<State>>()
+            .expect("state extension is missing");
+
+        if state.email_verification == EmailVerificationPolicy::Restricted
+            && auth_user.user.email_verified_at.is_none()
+        {
+            return Err(ApiError::EmailNotVerified);
+        }
+
+        Ok(VerifiedUser(auth_user))
+    }
+}
+
+/// Extracts an [`AuthUser`] having the admin role.
+///
+/// The role is read from the database rather than from the token claims, so
+/// that a demoted admin loses access right away.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthUser);
+
+#[async_trait]
+impl<B> FromRequest<B> for AdminUser
+where
+    B: Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let auth_user = AuthUser::from_request(req).await?;
+
+        if !auth_user.user.is_admin() {
+            return Err(ApiError::AdminRequired);
+        }
+
+        Ok(AdminUser(auth_user))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn required_scope_depends_on_resource_and_method() {
+        assert_eq!(
+            required_scope(&Method::GET, "/api/tasks/"),
+            Some(ApiTokenScope::TasksRead)
+        );
+        assert_eq!(
+            required_scope(&Method::PATCH, "/api/tasks/some-id"),
+            Some(ApiTokenScope::TasksWrite)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/tags/some-id"),
+            Some(ApiTokenScope::TasksWrite)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/api/lists/some-id/tasks"),
+            Some(ApiTokenScope::ListsRead)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/api/users/me"),
+            Some(ApiTokenScope::UserRead)
+        );
+        assert_eq!(required_scope(&Method::PATCH, "/api/users/me"), None);
+        assert_eq!(required_scope(&Method::GET, "/api/users/me/tokens"), None);
+        assert_eq!(required_scope(&Method::POST, "/api/users/logout"), None);
+        assert_eq!(required_scope(&Method::GET, "/api/admin/users"), None);
+    }
+}
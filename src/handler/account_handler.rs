@@ -0,0 +1,121 @@
+use axum::{
+    http::{header::CONTENT_DISPOSITION, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+use chrono::Utc;
+use std::sync::Arc;
+
+use super::{clear_session_cookies, confirm_password, task_handler::with_tags, ApiError};
+use crate::{
+    db::{
+        api_token::find_api_tokens_by_user_id, list::find_lists_by_user_id,
+        notification::find_notifications_by_user_id, oidc::find_user_identities_by_user_id,
+        recurrence::find_recurrences_by_user_id, refresh_token::revoke_refresh_tokens_by_user_id,
+        reminder::find_reminders_by_user_id, tag::find_tags_by_user_id,
+        task::find_tasks_by_user_id, two_factor::find_user_totp, user::schedule_user_deletion,
+    },
+    domain::{
+        export::UserExport,
+        two_factor::UserTotp,
+        user::{AccountDeletion, DeleteAccount},
+    },
+    extractor::AuthUser,
+    mailer::Email,
+    router::State,
+};
+
+/// Schedules the deletion of the account of the authenticated user and closes
+/// all their sessions.
+///
+/// The account and everything it owns are deleted for good once the grace
+/// period is over, logging in before that cancels the deletion.
+#[tracing::instrument(err, skip_all)]
+pub async fn delete_me_handler(
+    jar: CookieJar,
+    AuthUser { user, .. }: AuthUser,
+    Json(delete_input): Json<DeleteAccount>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(StatusCode, CookieJar, Json<AccountDeletion>), ApiError> {
+    confirm_password(&user, &delete_input.password, &state).await?;
+
+    let user = schedule_user_deletion(
+        user.id,
+        Utc::now() + state.account_deletion_grace_period,
+        &state.db_pool,
+    )
+    .await?
+    .ok_or(ApiError::UserNotFound)?;
+    let deletion_scheduled_at = user
+        .deletion_scheduled_at
+        .expect("deletion was just scheduled");
+
+    revoke_refresh_tokens_by_user_id(user.id, &state.db_pool).await?;
+
+    let email = Email {
+        to: user.email,
+        subject: "Your account will be deleted".into(),
+        body: format!(
+            "Hello {},\n\nYour account and all its data will be deleted on {}. To keep your account, log in before that date.",
+            user.username,
+            deletion_scheduled_at.format("%Y-%m-%d %H:%M UTC"),
+        ),
+    };
+    // The deletion is scheduled anyway, the email is only a courtesy
+    if let Err(err) = state.mailer.send(email).await {
+        tracing::error!(%err, "could not send account deletion email");
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        clear_session_cookies(jar, &state),
+        Json(AccountDeletion {
+            deletion_scheduled_at,
+        }),
+    ))
+}
+
+/// Exports the profile of the authenticated user along with everything they
+/// own, as a downloadable JSON document
+#[tracing::instrument(err, skip_all)]
+pub async fn export_me_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let two_factor_enabled = find_user_totp(user.id, &state.db_pool)
+        .await?
+        .filter(UserTotp::is_enabled)
+        .is_some();
+    let lists = find_lists_by_user_id(user.id, &state.db_pool).await?;
+    let tags = find_tags_by_user_id(user.id, &state.db_pool).await?;
+    let tasks = find_tasks_by_user_id(user.id, &state.db_pool).await?;
+    let tasks = with_tags(tasks, &state.db_pool).await?;
+    let recurrences = find_recurrences_by_user_id(user.id, &state.db_pool).await?;
+    let reminders = find_reminders_by_user_id(user.id, &state.db_pool).await?;
+    let notifications = find_notifications_by_user_id(user.id, &state.db_pool).await?;
+    let api_tokens = find_api_tokens_by_user_id(user.id, &state.db_pool).await?;
+    let identities = find_user_identities_by_user_id(user.id, &state.db_pool).await?;
+
+    let export = UserExport {
+        exported_at: Utc::now(),
+        user,
+        two_factor_enabled,
+        lists,
+        tags,
+        tasks,
+        recurrences,
+        reminders,
+        notifications,
+        api_tokens,
+        identities,
+    };
+
+    Ok((
+        [(
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"account-export.json\"",
+        )],
+        Json(export),
+    ))
+}
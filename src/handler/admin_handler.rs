@@ -0,0 +1,126 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{send_password_reset_email, ApiError};
+use crate::{
+    db::{
+        api_token::revoke_api_tokens_by_user_id,
+        refresh_token::revoke_refresh_tokens_by_user_id,
+        user::{
+            delete_user, find_user_by_id, require_password_reset, search_users, set_user_disabled,
+        },
+    },
+    domain::user::{SearchUsers, User},
+    extractor::AdminUser,
+    router::State,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+
+pub async fn list_users_handler(
+    Query(query): Query<SearchUsers>,
+    _: AdminUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<User>>, ApiError> {
+    // Validating query
+    query.validate()?;
+
+    let users = search_users(
+        query.search.as_deref().filter(|search| !search.is_empty()),
+        query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        query.offset.unwrap_or(0),
+        &state.db_pool,
+    )
+    .await?;
+
+    Ok(Json(users))
+}
+
+pub async fn get_user_handler(
+    Path(id): Path<Uuid>,
+    _: AdminUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<User>, ApiError> {
+    let user = find_user_by_id(id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::UserNotFound)?;
+
+    Ok(Json(user))
+}
+
+/// Disables a user and closes their sessions, api tokens are rejected while disabled
+#[tracing::instrument(err, skip(state, admin))]
+pub async fn disable_user_handler(
+    Path(id): Path<Uuid>,
+    AdminUser(admin): AdminUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<User>, ApiError> {
+    // Admins cannot lock themselves out
+    if admin.user.id == id {
+        return Err(ApiError::SelfManagement);
+    }
+
+    let user = set_user_disabled(id, true, &state.db_pool)
+        .await?
+        .ok_or(ApiError::UserNotFound)?;
+    revoke_refresh_tokens_by_user_id(user.id, &state.db_pool).await?;
+
+    Ok(Json(user))
+}
+
+#[tracing::instrument(err, skip(state, _admin))]
+pub async fn enable_user_handler(
+    Path(id): Path<Uuid>,
+    _admin: AdminUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<User>, ApiError> {
+    let user = set_user_disabled(id, false, &state.db_pool)
+        .await?
+        .ok_or(ApiError::UserNotFound)?;
+
+    Ok(Json(user))
+}
+
+/// Closes the sessions of a user and emails them a reset link, they cannot log
+/// in until their password is reset
+#[tracing::instrument(err, skip(state, _admin))]
+pub async fn force_password_reset_handler(
+    Path(id): Path<Uuid>,
+    _admin: AdminUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    let user = require_password_reset(id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::UserNotFound)?;
+    // Every credential issued with the old password stops working, api tokens
+    // included as they never expire otherwise
+    revoke_refresh_tokens_by_user_id(user.id, &state.db_pool).await?;
+    revoke_api_tokens_by_user_id(user.id, &state.db_pool).await?;
+
+    send_password_reset_email(user, &state).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[tracing::instrument(err, skip(state, admin))]
+pub async fn delete_user_handler(
+    Path(id): Path<Uuid>,
+    AdminUser(admin): AdminUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    if admin.user.id == id {
+        return Err(ApiError::SelfManagement);
+    }
+
+    if !delete_user(id, &state.db_pool).await? {
+        return Err(ApiError::UserNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
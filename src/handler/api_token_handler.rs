@@ -0,0 +1,73 @@
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::ApiError;
+use crate::{
+    db::api_token::{create_api_token, find_api_tokens_by_user_id, revoke_api_token},
+    domain::api_token::{ApiToken, CreateApiToken, CreatedApiToken, API_TOKEN_PREFIX},
+    extractor::AuthUser,
+    router::State,
+    utils::token::{generate_token, hash_token},
+};
+
+/// Creates an api token, the token itself is only returned in this response
+#[tracing::instrument(err, skip(state))]
+pub async fn create_api_token_handler(
+    Json(token_input): Json<CreateApiToken>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(StatusCode, Json<CreatedApiToken>), ApiError> {
+    // Validating token_input
+    token_input.validate()?;
+
+    let mut scopes = token_input
+        .scopes
+        .iter()
+        .map(|scope| scope.as_str().to_owned())
+        .collect::<Vec<_>>();
+    scopes.sort();
+    scopes.dedup();
+
+    let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+    let api_token = create_api_token(
+        user.id,
+        &token_input.name,
+        &hash_token(&token),
+        &scopes,
+        token_input
+            .expires_in_days
+            .map(|days| Utc::now() + Duration::days(days)),
+        &state.db_pool,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiToken { token, api_token }),
+    ))
+}
+
+pub async fn list_api_tokens_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<ApiToken>>, ApiError> {
+    let api_tokens = find_api_tokens_by_user_id(user.id, &state.db_pool).await?;
+
+    Ok(Json(api_tokens))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn revoke_api_token_handler(
+    Path(token_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    if !revoke_api_token(token_id, user.id, &state.db_pool).await? {
+        return Err(ApiError::ApiTokenNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,103 @@
+use axum::{http::StatusCode, Extension, Json};
+use chrono::Utc;
+use std::sync::Arc;
+use validator::Validate;
+
+use super::ApiError;
+use crate::{
+    db::{
+        email_verification::{
+            create_email_verification_token, last_email_verification_sent_at, verify_email,
+        },
+        user::find_user_by_email,
+    },
+    domain::{
+        email_verification::{ResendVerificationEmail, VerifyEmail},
+        user::User,
+    },
+    mailer::Email,
+    router::State,
+    utils::token::{generate_token, hash_token},
+};
+
+/// Sends a verification link to the email address of `user`
+pub(super) async fn send_verification_email(user: &User, state: &State) -> Result<(), ApiError> {
+    let token = generate_token();
+    create_email_verification_token(
+        user.id,
+        &hash_token(&token),
+        Utc::now() + state.email_verification_ttl,
+        &state.db_pool,
+    )
+    .await?;
+
+    let email = Email {
+        to: user.email.clone(),
+        subject: "Verify your email".into(),
+        body: format!(
+            "Hello {},\n\nUse the following link to verify your email: {}/verify-email?token={}\n\nThe link expires in {} hours.",
+            user.username,
+            state.public_url,
+            token,
+            state.email_verification_ttl.num_hours()
+        ),
+    };
+
+    // The user can ask for another email if this one is lost
+    if let Err(err) = state.mailer.send(email).await {
+        tracing::error!(%err, "could not send verification email");
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(err, skip_all)]
+pub async fn verify_email_handler(
+    Json(verify_input): Json<VerifyEmail>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    verify_email(&hash_token(&verify_input.token), &state.db_pool)
+        .await?
+        .ok_or(ApiError::InvalidVerificationToken)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Sends a new verification email, at most once per cooldown period.
+///
+/// The response is the same whether the email belongs to an unverified user or not.
+#[tracing::instrument(err, skip_all)]
+pub async fn resend_verification_email_handler(
+    Json(resend_input): Json<ResendVerificationEmail>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    // Validating resend_input
+    resend_input.validate()?;
+
+    // Looked up and emailed in the background, for the response time not to
+    // disclose whether the account exists and is unverified
+    tokio::spawn(async move {
+        if let Err(err) = resend_verification_email(&resend_input.email, &state).await {
+            tracing::error!(%err, "could not handle verification email resend");
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn resend_verification_email(email: &str, state: &State) -> Result<(), ApiError> {
+    let user = match find_user_by_email(email, &state.db_pool).await? {
+        Some(user) if user.email_verified_at.is_none() => user,
+        _ => return Ok(()),
+    };
+
+    // Throttling resends
+    let last_sent_at = last_email_verification_sent_at(user.id, &state.db_pool).await?;
+    if matches!(last_sent_at, Some(sent_at) if sent_at + state.email_verification_resend_cooldown > Utc::now())
+    {
+        tracing::info!(user_id = %user.id, "verification email resend throttled");
+        return Ok(());
+    }
+
+    send_verification_email(&user, state).await
+}
@@ -0,0 +1,16 @@
+use axum::{
+    http::header::CACHE_CONTROL,
+    response::{IntoResponse, Json},
+    Extension,
+};
+use std::sync::Arc;
+
+use crate::router::State;
+
+/// Public keys verifying access tokens, cached by clients for a few minutes
+pub async fn jwks_handler(Extension(state): Extension<Arc<State>>) -> impl IntoResponse {
+    (
+        [(CACHE_CONTROL, "public, max-age=300")],
+        Json(state.jwt_keys.jwks().clone()),
+    )
+}
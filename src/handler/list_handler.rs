@@ -0,0 +1,107 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{task_handler::with_tags, ApiError};
+use crate::{
+    db::{
+        list::{create_list, delete_list, find_list_by_id, find_lists_by_user_id, update_list},
+        task::find_tasks_by_list_id,
+    },
+    domain::{
+        list::{CreateList, DeleteListOptions, List, UpdateList},
+        task::TaggedTask,
+    },
+    extractor::AuthUser,
+    router::State,
+};
+
+#[tracing::instrument(err, skip(state))]
+pub async fn create_list_handler(
+    Json(list_input): Json<CreateList>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(StatusCode, Json<List>), ApiError> {
+    // Validating list_input
+    list_input.validate()?;
+
+    let list = create_list(user.id, list_input, &state.db_pool)
+        .await
+        .map_err(ApiError::on_unique_violation(ApiError::ListAlreadyExists))?;
+
+    Ok((StatusCode::CREATED, Json(list)))
+}
+
+pub async fn list_lists_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<List>>, ApiError> {
+    let lists = find_lists_by_user_id(user.id, &state.db_pool).await?;
+
+    Ok(Json(lists))
+}
+
+pub async fn get_list_handler(
+    Path(list_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<List>, ApiError> {
+    let list = find_list_by_id(list_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::ListNotFound)?;
+
+    Ok(Json(list))
+}
+
+pub async fn list_tasks_of_list_handler(
+    Path(list_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<TaggedTask>>, ApiError> {
+    find_list_by_id(list_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::ListNotFound)?;
+
+    let tasks = find_tasks_by_list_id(list_id, user.id, &state.db_pool).await?;
+
+    Ok(Json(with_tags(tasks, &state.db_pool).await?))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn update_list_handler(
+    Path(list_id): Path<Uuid>,
+    Json(list_input): Json<UpdateList>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<List>, ApiError> {
+    // Validating list_input
+    list_input.validate()?;
+
+    let list = update_list(list_id, user.id, list_input, &state.db_pool)
+        .await
+        .map_err(ApiError::on_unique_violation(ApiError::ListAlreadyExists))?
+        .ok_or(ApiError::ListNotFound)?;
+
+    Ok(Json(list))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn delete_list_handler(
+    Path(list_id): Path<Uuid>,
+    Query(options): Query<DeleteListOptions>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = delete_list(list_id, user.id, options.tasks, &state.db_pool).await?;
+
+    if !deleted {
+        return Err(ApiError::ListNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,5 +1,33 @@
+mod account_handler;
+mod admin_handler;
+mod api_token_handler;
+mod email_verification_handler;
+mod keys_handler;
+mod list_handler;
+mod notification_handler;
+mod oidc_handler;
+mod password_handler;
+mod recurrence_handler;
+mod reminder_handler;
 mod status_handler;
+mod tag_handler;
+mod task_handler;
+mod two_factor_handler;
 mod user_handler;
 
+pub use account_handler::*;
+pub use admin_handler::*;
+pub use api_token_handler::*;
+pub use email_verification_handler::*;
+pub use keys_handler::*;
+pub use list_handler::*;
+pub use notification_handler::*;
+pub use oidc_handler::*;
+pub use password_handler::*;
+pub use recurrence_handler::*;
+pub use reminder_handler::*;
 pub use status_handler::*;
+pub use tag_handler::*;
+pub use task_handler::*;
+pub use two_factor_handler::*;
 pub use user_handler::*;
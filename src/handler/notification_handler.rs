@@ -0,0 +1,34 @@
+use axum::{extract::Path, Extension, Json};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::ApiError;
+use crate::{
+    db::notification::{find_notifications_by_user_id, mark_notification_read},
+    domain::notification::Notification,
+    extractor::AuthUser,
+    router::State,
+};
+
+/// Lists the in-app notifications of the user, most recent first
+pub async fn list_notifications_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<Notification>>, ApiError> {
+    let notifications = find_notifications_by_user_id(user.id, &state.db_pool).await?;
+
+    Ok(Json(notifications))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn read_notification_handler(
+    Path(notification_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Notification>, ApiError> {
+    let notification = mark_notification_read(notification_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::NotificationNotFound)?;
+
+    Ok(Json(notification))
+}
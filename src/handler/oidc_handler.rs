@@ -0,0 +1,292 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{
+    check_can_login, create_session, email_verification_handler::send_verification_email,
+    session_response, two_factor_handler::start_two_factor_challenge, ApiError,
+};
+use crate::{
+    configuration::EmailVerificationPolicy,
+    db::{
+        oidc::{
+            consume_oidc_state, create_oidc_state, create_oidc_user, create_user_identity,
+            delete_user_identity, find_user_identities_by_user_id, find_user_identity,
+            touch_user_identity,
+        },
+        user::{email_exists, find_user_by_id, find_user_by_username},
+    },
+    domain::{
+        oidc::{OidcAuthorization, OidcCallback, UserIdentity},
+        user::{CreateUser, User},
+    },
+    extractor::AuthUser,
+    oidc::{IdTokenClaims, OidcProvider},
+    router::State,
+    utils::token::{generate_token, hash_token},
+};
+
+const USERNAME_MIN_LENGTH: usize = 6;
+const USERNAME_MAX_LENGTH: usize = 25;
+const USERNAME_SUFFIX_LENGTH: usize = 6;
+
+fn find_provider<'a>(state: &'a State, name: &str) -> Result<&'a OidcProvider, ApiError> {
+    state.oidc.get(name).ok_or(ApiError::OidcProviderNotFound)
+}
+
+/// Stores a new state for `user_id` (`None` for a login) and builds the url
+/// to send the user to
+async fn start_authorization(
+    provider: &OidcProvider,
+    user_id: Option<Uuid>,
+    state: &State,
+) -> Result<OidcAuthorization, ApiError> {
+    let oidc_state = generate_token();
+    let nonce = generate_token();
+    let code_verifier = generate_token();
+
+    let authorization_url = provider
+        .authorization_url(&oidc_state, &nonce, &code_verifier)
+        .await?;
+    let oidc_state = create_oidc_state(
+        &hash_token(&oidc_state),
+        provider.name(),
+        &nonce,
+        &code_verifier,
+        user_id,
+        Utc::now() + state.oidc_state_ttl,
+        &state.db_pool,
+    )
+    .await?;
+
+    Ok(OidcAuthorization {
+        authorization_url,
+        expires_at: oidc_state.expires_at,
+    })
+}
+
+/// Checks the state the provider redirected back with, then exchanges the code
+/// for the claims of the user on the provider
+async fn complete_authorization(
+    provider: &OidcProvider,
+    callback: &OidcCallback,
+    user_id: Option<Uuid>,
+    state: &State,
+) -> Result<IdTokenClaims, ApiError> {
+    let oidc_state = consume_oidc_state(
+        &hash_token(&callback.state),
+        provider.name(),
+        user_id,
+        &state.db_pool,
+    )
+    .await?
+    .ok_or(ApiError::InvalidOidcState)?;
+
+    Ok(provider
+        .exchange_code(&callback.code, &oidc_state.code_verifier, &oidc_state.nonce)
+        .await?)
+}
+
+/// Derives a free username from the claims, adding a random suffix when it is
+/// taken or too short
+async fn available_username(claims: &IdTokenClaims, state: &State) -> Result<String, ApiError> {
+    let base: String = claims
+        .preferred_username
+        .as_deref()
+        .or(claims.email.as_deref())
+        .and_then(|name| name.split('@').next())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .take(USERNAME_MAX_LENGTH)
+        .collect();
+
+    if base.len() >= USERNAME_MIN_LENGTH
+        && find_user_by_username(&base, &state.db_pool)
+            .await?
+            .is_none()
+    {
+        return Ok(base);
+    }
+
+    let prefix: String = base
+        .chars()
+        .take(USERNAME_MAX_LENGTH - USERNAME_SUFFIX_LENGTH - 1)
+        .collect();
+    loop {
+        let username = format!("{}_{}", prefix, &generate_token()[..USERNAME_SUFFIX_LENGTH]);
+        if find_user_by_username(&username, &state.db_pool)
+            .await?
+            .is_none()
+        {
+            return Ok(username);
+        }
+    }
+}
+
+/// Creates the account of a user logging in with a provider for the first time
+async fn register_oidc_user(
+    provider: &OidcProvider,
+    claims: &IdTokenClaims,
+    state: &State,
+) -> Result<User, ApiError> {
+    let email = claims
+        .email
+        .as_deref()
+        .map(str::trim)
+        .ok_or(ApiError::OidcEmailMissing)?;
+
+    // Linking by email would hand the account over to whoever controls the
+    // provider, the owner has to log in and link it themselves. Providers do not
+    // always keep the case the email was registered with
+    if email_exists(email, &state.db_pool).await? {
+        return Err(ApiError::OidcAccountExists);
+    }
+
+    // Nobody knows this password, one can be set through a password reset
+    let password = state
+        .hasher
+        .hash_password(generate_token().as_bytes())
+        .await?;
+    let user_input = CreateUser {
+        username: available_username(claims, state).await?,
+        email: email.into(),
+        password,
+    };
+    let user = create_oidc_user(
+        user_input,
+        claims.is_email_verified(),
+        provider.name(),
+        &claims.sub,
+        &state.db_pool,
+    )
+    .await?
+    .ok_or(ApiError::IdentityAlreadyLinked)?;
+
+    if user.email_verified_at.is_none() {
+        send_verification_email(&user, state).await?;
+    }
+
+    Ok(user)
+}
+
+/// Starts a login with an identity provider
+#[tracing::instrument(err, skip(state))]
+pub async fn oidc_authorize_handler(
+    Path(provider): Path<String>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<OidcAuthorization>, ApiError> {
+    let provider = find_provider(&state, &provider)?;
+
+    Ok(Json(start_authorization(provider, None, &state).await?))
+}
+
+/// Completes a login with an identity provider, registering the user on their
+/// first login.
+///
+/// Like the password login, a challenge token is returned instead of a session
+/// when 2FA is enabled.
+#[tracing::instrument(err, skip_all)]
+pub async fn oidc_callback_handler(
+    jar: CookieJar,
+    Path(provider): Path<String>,
+    Json(callback): Json<OidcCallback>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Response, ApiError> {
+    let provider = find_provider(&state, &provider)?;
+    let claims = complete_authorization(provider, &callback, None, &state).await?;
+
+    let user = match find_user_identity(provider.name(), &claims.sub, &state.db_pool).await? {
+        Some(identity) => {
+            touch_user_identity(identity.id, claims.email.as_deref(), &state.db_pool).await?;
+            find_user_by_id(identity.user_id, &state.db_pool)
+                .await?
+                .ok_or(ApiError::UserNotFound)?
+        }
+        None => register_oidc_user(provider, &claims, &state).await?,
+    };
+
+    check_can_login(&user)?;
+
+    if state.email_verification == EmailVerificationPolicy::RequiredForLogin
+        && user.email_verified_at.is_none()
+    {
+        return Err(ApiError::EmailNotVerified);
+    }
+
+    if let Some(challenge) = start_two_factor_challenge(&user, &state).await? {
+        return Ok((StatusCode::ACCEPTED, Json(challenge)).into_response());
+    }
+
+    let res = create_session(user, &state).await?;
+
+    Ok(session_response(res, jar, &state))
+}
+
+/// Starts linking an identity provider to the account of the authenticated user
+#[tracing::instrument(err, skip(state))]
+pub async fn link_identity_authorize_handler(
+    Path(provider): Path<String>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<OidcAuthorization>, ApiError> {
+    let provider = find_provider(&state, &provider)?;
+
+    Ok(Json(
+        start_authorization(provider, Some(user.id), &state).await?,
+    ))
+}
+
+/// Links the account of the identity provider to the authenticated user, who
+/// can then log in with it
+#[tracing::instrument(err, skip_all)]
+pub async fn link_identity_callback_handler(
+    Path(provider): Path<String>,
+    AuthUser { user, .. }: AuthUser,
+    Json(callback): Json<OidcCallback>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(StatusCode, Json<UserIdentity>), ApiError> {
+    let provider = find_provider(&state, &provider)?;
+    let claims = complete_authorization(provider, &callback, Some(user.id), &state).await?;
+
+    let identity = create_user_identity(
+        user.id,
+        provider.name(),
+        &claims.sub,
+        claims.email.as_deref(),
+        &state.db_pool,
+    )
+    .await?
+    .ok_or(ApiError::IdentityAlreadyLinked)?;
+
+    Ok((StatusCode::CREATED, Json(identity)))
+}
+
+pub async fn list_identities_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<UserIdentity>>, ApiError> {
+    let identities = find_user_identities_by_user_id(user.id, &state.db_pool).await?;
+
+    Ok(Json(identities))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn unlink_identity_handler(
+    Path(identity_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    if !delete_user_identity(identity_id, user.id, &state.db_pool).await? {
+        return Err(ApiError::IdentityNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,123 @@
+use axum::{http::StatusCode, Extension, Json};
+use chrono::Utc;
+use std::sync::Arc;
+use validator::Validate;
+
+use super::ApiError;
+use crate::{
+    db::{
+        password_reset::{
+            create_password_reset_token, find_password_reset_token, reset_password_with_token,
+        },
+        refresh_token::revoke_refresh_tokens_by_user_id,
+        user::{find_user_by_email, find_user_by_id},
+    },
+    domain::{
+        password_reset::{ForgotPassword, ResetPassword},
+        user::User,
+    },
+    mailer::Email,
+    router::State,
+    utils::token::{generate_token, hash_token},
+};
+
+/// Sends a password reset link by email.
+///
+/// The response is the same whether the email belongs to a user or not.
+#[tracing::instrument(err, skip_all)]
+pub async fn forgot_password_handler(
+    Json(forgot_input): Json<ForgotPassword>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    // Validating forgot_input
+    forgot_input.validate()?;
+
+    // Looked up and emailed in the background, for the response time not to
+    // disclose whether the account exists
+    tokio::spawn(async move {
+        let result = match find_user_by_email(&forgot_input.email, &state.db_pool).await {
+            Ok(Some(user)) => send_password_reset_email(user, &state).await,
+            Ok(None) => Ok(()),
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            tracing::error!(%err, "could not handle forgot password");
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Emails `user` a link to reset their password
+pub(super) async fn send_password_reset_email(user: User, state: &State) -> Result<(), ApiError> {
+    let token = generate_token();
+    create_password_reset_token(
+        user.id,
+        &hash_token(&token),
+        Utc::now() + state.password_reset_ttl,
+        &state.db_pool,
+    )
+    .await?;
+
+    let email = Email {
+        to: user.email,
+        subject: "Reset your password".into(),
+        body: format!(
+            "Hello {},\n\nUse the following link to reset your password: {}/reset-password?token={}\n\nThe link expires in {} minutes. If you did not ask for it, you can ignore this email.",
+            user.username,
+            state.public_url,
+            token,
+            state.password_reset_ttl.num_minutes()
+        ),
+    };
+
+    // Failures are not surfaced, for forgot password not to disclose that the account exists
+    if let Err(err) = state.mailer.send(email).await {
+        tracing::error!(%err, "could not send password reset email");
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(err, skip_all)]
+pub async fn reset_password_handler(
+    Json(reset_input): Json<ResetPassword>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    let token_hash = hash_token(&reset_input.token);
+
+    // The token is only consumed once the new password is accepted, so that
+    // the user can pick another one with the same link
+    let reset_token = find_password_reset_token(&token_hash, &state.db_pool)
+        .await?
+        .ok_or(ApiError::InvalidResetToken)?;
+    let user = find_user_by_id(reset_token.user_id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::InvalidResetToken)?;
+
+    // Validating reset_input
+    state
+        .password_policy
+        .validate(
+            reset_input.validate(),
+            "new_password",
+            &reset_input.new_password,
+            &[&user.username, &user.email],
+        )
+        .await?;
+
+    // Hashed before the token is consumed, a busy hasher leaves the link usable
+    let password_hash = state
+        .hasher
+        .hash_password(reset_input.new_password.as_bytes())
+        .await?;
+
+    let reset_token = reset_password_with_token(&token_hash, &password_hash, &state.db_pool)
+        .await?
+        .ok_or(ApiError::InvalidResetToken)?;
+
+    // Sessions opened with the old password are no longer trusted
+    revoke_refresh_tokens_by_user_id(reset_token.user_id, &state.db_pool).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::{Validate, ValidationErrors};
+
+use super::ApiError;
+use crate::{
+    db::{
+        recurrence::{
+            create_next_occurrence, find_recurrence_by_id, leave_recurrence, set_task_recurrence,
+        },
+        task::find_task_by_id,
+    },
+    domain::{
+        recurrence::{Occurrence, PreviewOccurrences, SetRecurrence, TaskRecurrence},
+        task::Task,
+    },
+    extractor::AuthUser,
+    router::State,
+    utils::{rrule::RecurrenceRule, validation::field_error},
+};
+
+/// Makes a task repeat along an RRULE, starting from its due date
+#[tracing::instrument(err, skip(state))]
+pub async fn set_recurrence_handler(
+    Path(task_id): Path<Uuid>,
+    Json(recurrence_input): Json<SetRecurrence>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TaskRecurrence>, ApiError> {
+    // Validating recurrence_input
+    let rule = validate_recurrence(&recurrence_input)?;
+
+    let task = find_task_by_id(task_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TaskNotFound)?;
+    let starts_at = task.due_at.ok_or(ApiError::TaskDueDateRequired)?;
+
+    let recurrence = set_task_recurrence(
+        task.id,
+        user.id,
+        &rule.to_string(),
+        &recurrence_input.timezone,
+        starts_at,
+        &state.db_pool,
+    )
+    .await?;
+
+    Ok(Json(recurrence))
+}
+
+pub async fn get_recurrence_handler(
+    Path(task_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TaskRecurrence>, ApiError> {
+    let (_, recurrence) = find_task_recurrence(task_id, user.id, &state.db_pool).await?;
+
+    Ok(Json(recurrence))
+}
+
+/// Stops the series at the task, earlier occurrences are kept
+#[tracing::instrument(err, skip(state))]
+pub async fn delete_recurrence_handler(
+    Path(task_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    find_task_by_id(task_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TaskNotFound)?;
+
+    if !leave_recurrence(task_id, user.id, &state.db_pool).await? {
+        return Err(ApiError::RecurrenceNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Previews the `count` occurrences following the task
+pub async fn list_occurrences_handler(
+    Path(task_id): Path<Uuid>,
+    Query(preview): Query<PreviewOccurrences>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<Occurrence>>, ApiError> {
+    preview.validate()?;
+
+    let (task, recurrence) = find_task_recurrence(task_id, user.id, &state.db_pool).await?;
+    let Some((rule, tz)) = parse_recurrence(&recurrence) else {
+        return Ok(Json(vec![]));
+    };
+    let after = task.due_at.unwrap_or(recurrence.starts_at);
+
+    let occurrences = rule
+        .occurrences(recurrence.starts_at, tz)
+        .skip_while(|due_at| *due_at <= after)
+        .take(preview.count)
+        .map(|due_at| Occurrence {
+            due_at,
+            local_due_at: due_at.with_timezone(&tz).naive_local(),
+        })
+        .collect();
+
+    Ok(Json(occurrences))
+}
+
+/// Creates the occurrence following a task that was just completed, if its
+/// series goes on
+pub(super) async fn create_following_occurrence(
+    task: &Task,
+    db_pool: &PgPool,
+) -> Result<Option<Task>, ApiError> {
+    let (Some(recurrence_id), Some(due_at)) = (task.recurrence_id, task.due_at) else {
+        return Ok(None);
+    };
+    let Some(recurrence) = find_recurrence_by_id(recurrence_id, task.user_id, db_pool).await?
+    else {
+        return Ok(None);
+    };
+    let Some((rule, tz)) = parse_recurrence(&recurrence) else {
+        return Ok(None);
+    };
+
+    match rule.next_after(recurrence.starts_at, tz, due_at) {
+        Some(next_due_at) => Ok(create_next_occurrence(task, next_due_at, db_pool).await?),
+        None => Ok(None),
+    }
+}
+
+async fn find_task_recurrence(
+    task_id: Uuid,
+    user_id: Uuid,
+    db_pool: &PgPool,
+) -> Result<(Task, TaskRecurrence), ApiError> {
+    let task = find_task_by_id(task_id, user_id, db_pool)
+        .await?
+        .ok_or(ApiError::TaskNotFound)?;
+    let recurrence_id = task.recurrence_id.ok_or(ApiError::RecurrenceNotFound)?;
+    let recurrence = find_recurrence_by_id(recurrence_id, user_id, db_pool)
+        .await?
+        .ok_or(ApiError::RecurrenceNotFound)?;
+
+    Ok((task, recurrence))
+}
+
+/// Stored rules and time zones were validated, `None` is only logged
+fn parse_recurrence(recurrence: &TaskRecurrence) -> Option<(RecurrenceRule, Tz)> {
+    match (
+        recurrence.rule.parse::<RecurrenceRule>(),
+        recurrence.timezone.parse::<Tz>(),
+    ) {
+        (Ok(rule), Ok(tz)) => Some((rule, tz)),
+        _ => {
+            tracing::error!(recurrence_id = %recurrence.id, "invalid stored recurrence");
+            None
+        }
+    }
+}
+
+fn validate_recurrence(input: &SetRecurrence) -> Result<RecurrenceRule, ValidationErrors> {
+    let mut errors = input.validate().err().unwrap_or_default();
+
+    let rule = input
+        .rule
+        .parse::<RecurrenceRule>()
+        .map_err(|err| errors.add("rule", field_error("rrule", err.to_string())))
+        .ok();
+    let tz = input
+        .timezone
+        .parse::<Tz>()
+        .map_err(|_| errors.add("timezone", field_error("timezone", "unknown time zone")))
+        .ok();
+
+    match (rule, tz) {
+        (Some(rule), Some(_)) if errors.is_empty() => Ok(rule),
+        _ => Err(errors),
+    }
+}
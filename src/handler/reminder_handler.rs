@@ -0,0 +1,167 @@
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::{Validate, ValidationErrors};
+
+use super::ApiError;
+use crate::{
+    db::{
+        reminder::{create_reminder, delete_reminder, find_reminders_by_task_id},
+        task::find_task_by_id,
+    },
+    domain::reminder::{CreateReminder, NotificationChannel, TaskReminder},
+    extractor::AuthUser,
+    notifier::check_webhook_url,
+    router::State,
+    utils::validation::field_error,
+};
+
+/// Sets a reminder on a task, `remind_at` is a local time in `timezone`
+#[tracing::instrument(err, skip(state))]
+pub async fn create_reminder_handler(
+    Path(task_id): Path<Uuid>,
+    Json(reminder_input): Json<CreateReminder>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(StatusCode, Json<TaskReminder>), ApiError> {
+    // Validating reminder_input
+    let remind_at = validate_reminder(&reminder_input, Utc::now(), state.allow_private_webhooks)?;
+
+    find_task_by_id(task_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TaskNotFound)?;
+
+    let reminder = create_reminder(
+        task_id,
+        user.id,
+        remind_at,
+        &reminder_input.timezone,
+        reminder_input.channel,
+        reminder_input.webhook_url.as_deref(),
+        &state.db_pool,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(reminder)))
+}
+
+pub async fn list_reminders_handler(
+    Path(task_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<TaskReminder>>, ApiError> {
+    find_task_by_id(task_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TaskNotFound)?;
+
+    let reminders = find_reminders_by_task_id(task_id, user.id, &state.db_pool).await?;
+
+    Ok(Json(reminders))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn delete_reminder_handler(
+    Path((task_id, reminder_id)): Path<(Uuid, Uuid)>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = delete_reminder(reminder_id, task_id, user.id, &state.db_pool).await?;
+
+    if !deleted {
+        return Err(ApiError::ReminderNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Validates the reminder and returns when it is due, in UTC.
+///
+/// Ambiguous local times, repeated when clocks go back, resolve to the first
+/// occurrence, while the ones skipped when clocks go forward are rejected.
+fn validate_reminder(
+    input: &CreateReminder,
+    now: DateTime<Utc>,
+    allow_private_webhooks: bool,
+) -> Result<DateTime<Utc>, ValidationErrors> {
+    let mut errors = input.validate().err().unwrap_or_default();
+
+    let mut remind_at = None;
+    match input.timezone.parse::<Tz>() {
+        Ok(tz) => match tz.from_local_datetime(&input.remind_at).earliest() {
+            Some(local) if local.with_timezone(&Utc) > now => {
+                remind_at = Some(local.with_timezone(&Utc))
+            }
+            Some(_) => errors.add("remind_at", field_error("past", "must be in the future")),
+            None => errors.add(
+                "remind_at",
+                field_error("nonexistent", "does not exist in this time zone"),
+            ),
+        },
+        Err(_) => errors.add("timezone", field_error("timezone", "unknown time zone")),
+    }
+
+    match &input.webhook_url {
+        None if input.channel == NotificationChannel::Webhook => errors.add(
+            "webhook_url",
+            field_error("required", "required by the webhook channel"),
+        ),
+        Some(url) if check_webhook_url(url, allow_private_webhooks).is_err() => errors.add(
+            "webhook_url",
+            field_error("forbidden", "must be a public https url"),
+        ),
+        _ => {}
+    }
+
+    match remind_at {
+        Some(remind_at) if errors.is_empty() => Ok(remind_at),
+        _ => Err(errors),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn reminder_input(remind_at: &str, timezone: &str) -> CreateReminder {
+        CreateReminder {
+            remind_at: remind_at.parse().unwrap(),
+            timezone: timezone.into(),
+            channel: NotificationChannel::Email,
+            webhook_url: None,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.from_utc_datetime(&NaiveDate::from_ymd(2022, 1, 1).and_hms(0, 0, 0))
+    }
+
+    #[test]
+    fn local_times_resolve_around_daylight_saving_time() {
+        // Clocks went forward at 02:00 and back at 03:00 in Paris
+        let skipped = validate_reminder(
+            &reminder_input("2022-03-27T02:30:00", "Europe/Paris"),
+            now(),
+            false,
+        );
+        let repeated = validate_reminder(
+            &reminder_input("2022-10-30T02:30:00", "Europe/Paris"),
+            now(),
+            false,
+        );
+        let summer = validate_reminder(
+            &reminder_input("2022-07-14T09:00:00", "Europe/Paris"),
+            now(),
+            false,
+        );
+
+        assert!(skipped
+            .unwrap_err()
+            .field_errors()
+            .contains_key("remind_at"));
+        assert_eq!(repeated.unwrap().to_rfc3339(), "2022-10-30T00:30:00+00:00");
+        assert_eq!(summer.unwrap().to_rfc3339(), "2022-07-14T07:00:00+00:00");
+    }
+}
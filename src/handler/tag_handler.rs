@@ -0,0 +1,82 @@
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::ApiError;
+use crate::{
+    db::tag::{create_tag, delete_tag, find_tag_by_id, find_tags_by_user_id, update_tag},
+    domain::tag::{CreateTag, Tag, UpdateTag},
+    extractor::AuthUser,
+    router::State,
+};
+
+#[tracing::instrument(err, skip(state))]
+pub async fn create_tag_handler(
+    Json(tag_input): Json<CreateTag>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(StatusCode, Json<Tag>), ApiError> {
+    // Validating tag_input
+    tag_input.validate()?;
+
+    let tag = create_tag(user.id, tag_input, &state.db_pool)
+        .await
+        .map_err(ApiError::on_unique_violation(ApiError::TagAlreadyExists))?;
+
+    Ok((StatusCode::CREATED, Json(tag)))
+}
+
+pub async fn list_tags_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<Tag>>, ApiError> {
+    let tags = find_tags_by_user_id(user.id, &state.db_pool).await?;
+
+    Ok(Json(tags))
+}
+
+pub async fn get_tag_handler(
+    Path(tag_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Tag>, ApiError> {
+    let tag = find_tag_by_id(tag_id, user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TagNotFound)?;
+
+    Ok(Json(tag))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn update_tag_handler(
+    Path(tag_id): Path<Uuid>,
+    Json(tag_input): Json<UpdateTag>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Tag>, ApiError> {
+    // Validating tag_input
+    tag_input.validate()?;
+
+    let tag = update_tag(tag_id, user.id, tag_input, &state.db_pool)
+        .await
+        .map_err(ApiError::on_unique_violation(ApiError::TagAlreadyExists))?
+        .ok_or(ApiError::TagNotFound)?;
+
+    Ok(Json(tag))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn delete_tag_handler(
+    Path(tag_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = delete_tag(tag_id, user.id, &state.db_pool).await?;
+
+    if !deleted {
+        return Err(ApiError::TagNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,300 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::PgPool;
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{recurrence_handler::create_following_occurrence, ApiError};
+use crate::{
+    db::{
+        list::find_list_by_id,
+        tag::{find_tags_by_ids, find_tags_by_task_ids},
+        task::{
+            create_task, delete_task, find_task_by_id, find_task_subtree, find_tasks_by_filter,
+            reorder_task, set_task_parent, update_task,
+        },
+    },
+    domain::task::{
+        CreateTask, FilterTasks, MoveTask, ReorderTask, SetTaskParent, TaggedTask, Task,
+        TaskPlacement, TaskProgress, TaskTree, UpdateTask, UpdateTaskOptions,
+    },
+    extractor::AuthUser,
+    router::State,
+};
+
+#[tracing::instrument(err, skip(state))]
+pub async fn create_task_handler(
+    Json(task_input): Json<CreateTask>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(StatusCode, Json<TaggedTask>), ApiError> {
+    // Validating task_input
+    task_input.validate()?;
+
+    // Check the destination list and the tags belong to the user
+    if let Some(list_id) = task_input.list_id {
+        find_list_by_id(list_id, user.id, &state.db_pool)
+            .await?
+            .ok_or(ApiError::ListNotFound)?;
+    }
+    check_tags(&task_input.tag_ids, user.id, &state.db_pool).await?;
+
+    // The parent task is checked to belong to the user and not to be nested
+    // too deep along with the insert
+    let task = create_task(user.id, task_input, &state.db_pool).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(with_tag(task, &state.db_pool).await?),
+    ))
+}
+
+/// Lists the tasks of the user, only the ones having any or all of the
+/// `tags` when given
+pub async fn list_tasks_handler(
+    Query(filter): Query<FilterTasks>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<Vec<TaggedTask>>, ApiError> {
+    let tasks = find_tasks_by_filter(user.id, &filter, &state.db_pool).await?;
+
+    Ok(Json(with_tags(tasks, &state.db_pool).await?))
+}
+
+/// Returns a task along with its subtasks, at every depth
+pub async fn get_task_handler(
+    Path(task_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TaskTree>, ApiError> {
+    let tasks = find_task_subtree(task_id, user.id, &state.db_pool).await?;
+    let tasks = with_tags(tasks, &state.db_pool).await?;
+
+    let tree = build_tree(task_id, tasks).ok_or(ApiError::TaskNotFound)?;
+
+    Ok(Json(tree))
+}
+
+/// Updates a task, `?subtasks=cascade` gives the new completion to all its
+/// subtasks as well.
+///
+/// Completing an occurrence of a recurring task creates the next one.
+#[tracing::instrument(err, skip(state))]
+pub async fn update_task_handler(
+    Path(task_id): Path<Uuid>,
+    Query(options): Query<UpdateTaskOptions>,
+    Json(task_input): Json<UpdateTask>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TaggedTask>, ApiError> {
+    // Validating task_input
+    task_input.validate()?;
+
+    // Check the tags belong to the user
+    if let Some(tag_ids) = &task_input.tag_ids {
+        check_tags(tag_ids, user.id, &state.db_pool).await?;
+    }
+
+    let completed = task_input.completed;
+    let task = update_task(
+        task_id,
+        user.id,
+        task_input,
+        options.subtasks,
+        &state.db_pool,
+    )
+    .await?
+    .ok_or(ApiError::TaskNotFound)?;
+
+    if completed == Some(true) {
+        create_following_occurrence(&task, &state.db_pool).await?;
+    }
+
+    Ok(Json(with_tag(task, &state.db_pool).await?))
+}
+
+#[tracing::instrument(err, skip(state))]
+pub async fn move_task_handler(
+    Path(task_id): Path<Uuid>,
+    Json(move_input): Json<MoveTask>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TaggedTask>, ApiError> {
+    // Check the destination list belongs to the user
+    if let Some(list_id) = move_input.list_id {
+        find_list_by_id(list_id, user.id, &state.db_pool)
+            .await?
+            .ok_or(ApiError::ListNotFound)?;
+    }
+
+    let task = reorder_task(
+        task_id,
+        user.id,
+        move_input.list_id,
+        TaskPlacement::End,
+        &state.db_pool,
+    )
+    .await?
+    .ok_or(ApiError::TaskNotFound)?;
+
+    Ok(Json(with_tag(task, &state.db_pool).await?))
+}
+
+/// Moves a task right after or before another one, or at the end of a list
+#[tracing::instrument(err, skip(state))]
+pub async fn reorder_task_handler(
+    Path(task_id): Path<Uuid>,
+    Json(reorder_input): Json<ReorderTask>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TaggedTask>, ApiError> {
+    let placement = match (reorder_input.after, reorder_input.before) {
+        (Some(_), Some(_)) => return Err(ApiError::InvalidTaskPosition),
+        (Some(anchor_id), None) => TaskPlacement::After(anchor_id),
+        (None, Some(anchor_id)) => TaskPlacement::Before(anchor_id),
+        (None, None) => TaskPlacement::End,
+    };
+
+    // The task goes to the list of its anchor
+    let list_id = match reorder_input.after.or(reorder_input.before) {
+        Some(anchor_id) if anchor_id == task_id => return Err(ApiError::InvalidTaskPosition),
+        Some(anchor_id) => {
+            let anchor = find_task_by_id(anchor_id, user.id, &state.db_pool)
+                .await?
+                .ok_or(ApiError::TaskNotFound)?;
+            if reorder_input.list_id.is_some() && reorder_input.list_id != anchor.list_id {
+                return Err(ApiError::InvalidTaskPosition);
+            }
+
+            anchor.list_id
+        }
+        None => {
+            if let Some(list_id) = reorder_input.list_id {
+                find_list_by_id(list_id, user.id, &state.db_pool)
+                    .await?
+                    .ok_or(ApiError::ListNotFound)?;
+            }
+
+            reorder_input.list_id
+        }
+    };
+
+    let task = reorder_task(task_id, user.id, list_id, placement, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TaskNotFound)?;
+
+    Ok(Json(with_tag(task, &state.db_pool).await?))
+}
+
+/// Moves a task, along with its subtasks, under another task or back to the
+/// top level
+#[tracing::instrument(err, skip(state))]
+pub async fn set_task_parent_handler(
+    Path(task_id): Path<Uuid>,
+    Json(parent_input): Json<SetTaskParent>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TaggedTask>, ApiError> {
+    let task = set_task_parent(task_id, user.id, parent_input.parent_id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TaskNotFound)?;
+
+    Ok(Json(with_tag(task, &state.db_pool).await?))
+}
+
+/// Deletes a task along with its subtasks
+#[tracing::instrument(err, skip(state))]
+pub async fn delete_task_handler(
+    Path(task_id): Path<Uuid>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = delete_task(task_id, user.id, &state.db_pool).await?;
+
+    if !deleted {
+        return Err(ApiError::TaskNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Fails unless every tag belongs to the user
+async fn check_tags(tag_ids: &[Uuid], user_id: Uuid, db_pool: &PgPool) -> Result<(), ApiError> {
+    if tag_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut tag_ids = tag_ids.to_vec();
+    tag_ids.sort_unstable();
+    tag_ids.dedup();
+
+    let tags = find_tags_by_ids(&tag_ids, user_id, db_pool).await?;
+    if tags.len() != tag_ids.len() {
+        return Err(ApiError::TagNotFound);
+    }
+
+    Ok(())
+}
+
+/// Loads the tags of the tasks, in one query
+pub(super) async fn with_tags(
+    tasks: Vec<Task>,
+    db_pool: &PgPool,
+) -> Result<Vec<TaggedTask>, sqlx::Error> {
+    let task_ids: Vec<Uuid> = tasks.iter().map(|task| task.id).collect();
+
+    let mut tags_by_task: HashMap<Uuid, Vec<_>> = HashMap::new();
+    for (task_id, tag) in find_tags_by_task_ids(&task_ids, db_pool).await? {
+        tags_by_task.entry(task_id).or_default().push(tag);
+    }
+
+    let tasks = tasks
+        .into_iter()
+        .map(|task| TaggedTask {
+            tags: tags_by_task.remove(&task.id).unwrap_or_default(),
+            task,
+        })
+        .collect();
+
+    Ok(tasks)
+}
+
+async fn with_tag(task: Task, db_pool: &PgPool) -> Result<TaggedTask, sqlx::Error> {
+    let tasks = with_tags(vec![task], db_pool).await?;
+
+    Ok(tasks.into_iter().next().expect("one task in, one task out"))
+}
+
+/// Nests the subtasks of `root_id` under it, `None` when the root is missing
+fn build_tree(root_id: Uuid, tasks: Vec<TaggedTask>) -> Option<TaskTree> {
+    let mut root = None;
+    let mut children: HashMap<Uuid, Vec<TaggedTask>> = HashMap::new();
+    for task in tasks {
+        match task.task.parent_id {
+            _ if task.task.id == root_id => root = Some(task),
+            Some(parent_id) => children.entry(parent_id).or_default().push(task),
+            None => {}
+        }
+    }
+
+    fn nest(task: TaggedTask, children: &mut HashMap<Uuid, Vec<TaggedTask>>) -> TaskTree {
+        let subtasks: Vec<TaskTree> = children
+            .remove(&task.task.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|subtask| nest(subtask, children))
+            .collect();
+
+        TaskTree {
+            task,
+            progress: TaskProgress::of(&subtasks),
+            subtasks,
+        }
+    }
+
+    root.map(|root| nest(root, &mut children))
+}
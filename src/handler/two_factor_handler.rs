@@ -0,0 +1,214 @@
+use axum::{http::StatusCode, response::Response, Extension, Json};
+use axum_extra::extract::cookie::CookieJar;
+use chrono::Utc;
+use std::sync::Arc;
+
+use super::{check_can_login, confirm_password, create_session, session_response, ApiError};
+use crate::{
+    db::{
+        two_factor::{
+            consume_two_factor_challenge, create_pending_totp, create_two_factor_challenge,
+            delete_totp, enable_totp, find_two_factor_challenge, find_unused_recovery_codes,
+            find_user_totp, record_failed_challenge_attempt, use_recovery_code, use_totp_step,
+        },
+        user::find_user_by_id,
+    },
+    domain::{
+        two_factor::{
+            ConfirmTwoFactor, DisableTwoFactor, RecoveryCodes, TwoFactorLogin, TwoFactorRequired,
+            TwoFactorSetup, UserTotp,
+        },
+        user::User,
+    },
+    extractor::AuthUser,
+    rate_limit::{account_lock_remaining, clear_failed_logins, record_failed_login},
+    router::State,
+    utils::{
+        token::{generate_token, hash_token},
+        totp::{generate_recovery_codes, generate_secret, otpauth_uri, verify_code},
+    },
+};
+
+const RECOVERY_CODES_COUNT: usize = 10;
+/// Wrong codes allowed for a challenge before the password must be entered again
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+/// Starts the second step of the login when the user enabled 2FA
+pub(super) async fn start_two_factor_challenge(
+    user: &User,
+    state: &State,
+) -> Result<Option<TwoFactorRequired>, ApiError> {
+    let enabled = find_user_totp(user.id, &state.db_pool)
+        .await?
+        .is_some_and(|totp| totp.is_enabled());
+    if !enabled {
+        return Ok(None);
+    }
+
+    let challenge_token = generate_token();
+    let challenge = create_two_factor_challenge(
+        user.id,
+        &hash_token(&challenge_token),
+        Utc::now() + state.two_factor_challenge_ttl,
+        &state.db_pool,
+    )
+    .await?;
+
+    Ok(Some(TwoFactorRequired {
+        challenge_token,
+        expires_at: challenge.expires_at,
+    }))
+}
+
+/// Checks a code against the secret of the user, a code is only accepted once
+async fn check_totp_code(totp: &UserTotp, code: &str, state: &State) -> Result<bool, ApiError> {
+    let secret = state
+        .totp_cipher
+        .open(&totp.secret, totp.user_id.as_bytes())?;
+    let step = match verify_code(&secret, code, Utc::now()) {
+        Some(step) => step,
+        None => return Ok(false),
+    };
+
+    Ok(use_totp_step(totp.user_id, step, &state.db_pool).await?)
+}
+
+/// Checks a recovery code of the user, consuming it when it matches
+async fn check_recovery_code(user: &User, code: &str, state: &State) -> Result<bool, ApiError> {
+    let code = code.trim().to_lowercase();
+
+    for recovery_code in find_unused_recovery_codes(user.id, &state.db_pool).await? {
+        let is_match = state
+            .hasher
+            .verify_password(code.as_bytes(), &recovery_code.code_hash)
+            .await?;
+        if is_match {
+            return Ok(use_recovery_code(recovery_code.id, &state.db_pool).await?);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Generates a new secret, 2FA is only enabled once a first code is confirmed
+#[tracing::instrument(err, skip_all)]
+pub async fn setup_two_factor_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<TwoFactorSetup>, ApiError> {
+    let secret = generate_secret();
+    let sealed_secret = state.totp_cipher.seal(&secret, user.id.as_bytes());
+    create_pending_totp(user.id, &sealed_secret, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TwoFactorAlreadyEnabled)?;
+
+    let otpauth_uri = otpauth_uri(&state.totp_issuer, &user.username, &secret);
+
+    Ok(Json(TwoFactorSetup {
+        secret,
+        otpauth_uri,
+    }))
+}
+
+/// Enables 2FA with a first code from the authenticator app and returns the
+/// recovery codes, which are never shown again
+#[tracing::instrument(err, skip_all)]
+pub async fn confirm_two_factor_handler(
+    Json(confirm_input): Json<ConfirmTwoFactor>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<RecoveryCodes>, ApiError> {
+    let totp = find_user_totp(user.id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::TwoFactorNotPending)?;
+    if totp.is_enabled() {
+        return Err(ApiError::TwoFactorAlreadyEnabled);
+    }
+
+    let secret = state
+        .totp_cipher
+        .open(&totp.secret, totp.user_id.as_bytes())?;
+    let step = verify_code(&secret, &confirm_input.code, Utc::now())
+        .ok_or(ApiError::InvalidTwoFactorCode)?;
+
+    let recovery_codes = generate_recovery_codes(RECOVERY_CODES_COUNT);
+    let mut recovery_code_hashes = Vec::with_capacity(recovery_codes.len());
+    for code in &recovery_codes {
+        recovery_code_hashes.push(state.hasher.hash_password(code.as_bytes()).await?);
+    }
+
+    if !enable_totp(user.id, step, &recovery_code_hashes, &state.db_pool).await? {
+        return Err(ApiError::TwoFactorAlreadyEnabled);
+    }
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+#[tracing::instrument(err, skip_all)]
+pub async fn disable_two_factor_handler(
+    Json(disable_input): Json<DisableTwoFactor>,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<StatusCode, ApiError> {
+    // Confirming with the password, a stolen access token is not enough
+    confirm_password(&user, &disable_input.password, &state).await?;
+
+    delete_totp(user.id, &state.db_pool).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Second step of the login, exchanging a challenge token and a code (or a
+/// recovery code) for a session
+#[tracing::instrument(err, skip_all)]
+pub async fn login_two_factor_handler(
+    jar: CookieJar,
+    Json(login_input): Json<TwoFactorLogin>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Response, ApiError> {
+    let challenge =
+        find_two_factor_challenge(&hash_token(&login_input.challenge_token), &state.db_pool)
+            .await?
+            .ok_or(ApiError::InvalidTwoFactorChallenge)?;
+
+    let user = find_user_by_id(challenge.user_id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::InvalidTwoFactorChallenge)?;
+    check_can_login(&user)?;
+
+    // Wrong codes count towards the lockout of the account, across challenges
+    let store = state.rate_limiter.as_ref();
+    let lockout = &state.rate_limit.lockout;
+    let account = user.id.to_string();
+    if let Some(remaining) = account_lock_remaining(store, lockout, &account).await? {
+        return Err(ApiError::too_many_requests(remaining));
+    }
+
+    // 2FA may have been disabled since the challenge was issued
+    let totp = find_user_totp(user.id, &state.db_pool)
+        .await?
+        .filter(UserTotp::is_enabled)
+        .ok_or(ApiError::InvalidTwoFactorChallenge)?;
+
+    let is_valid = match (&login_input.code, &login_input.recovery_code) {
+        (Some(code), _) => check_totp_code(&totp, code, &state).await?,
+        (None, Some(recovery_code)) => check_recovery_code(&user, recovery_code, &state).await?,
+        (None, None) => false,
+    };
+    if !is_valid {
+        record_failed_challenge_attempt(challenge.id, MAX_CHALLENGE_ATTEMPTS, &state.db_pool)
+            .await?;
+        record_failed_login(store, lockout, &account).await?;
+        return Err(ApiError::InvalidTwoFactorCode);
+    }
+
+    // A challenge opens a single session
+    if !consume_two_factor_challenge(challenge.id, &state.db_pool).await? {
+        return Err(ApiError::InvalidTwoFactorChallenge);
+    }
+    clear_failed_logins(store, &account).await?;
+
+    let res = create_session(user, &state).await?;
+
+    Ok(session_response(res, jar, &state))
+}
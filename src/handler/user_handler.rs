@@ -1,17 +1,59 @@
-use axum::{http::status, response::IntoResponse, Extension, Json};
-use chrono::Duration;
-use jsonwebtoken::{EncodingKey, Header};
+use axum::{
+    http::{
+        header::{RETRY_AFTER, WWW_AUTHENTICATE},
+        status, HeaderMap,
+    },
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+use chrono::{Duration, Utc};
 use serde::Serialize;
 use std::{collections::HashMap, sync::Arc};
 use thiserror::Error;
+use uuid::Uuid;
 use validator::{Validate, ValidationErrors};
 
+use super::{
+    email_verification_handler::send_verification_email,
+    two_factor_handler::start_two_factor_challenge,
+};
 use crate::{
-    db::user::{create_user, find_user_by_username, user_exists_by_username_or_email},
-    domain::user::{Claims, CreateUser, FindUser, User},
+    configuration::EmailVerificationPolicy,
+    db::{
+        is_unique_violation,
+        refresh_token::{
+            create_refresh_token, find_refresh_token_by_hash, revoke_refresh_token_family,
+            revoke_refresh_tokens_by_user_id, rotate_refresh_token,
+        },
+        revoked_token::revoke_token,
+        task::{TaskParentError, TaskPositionError},
+        user::{
+            cancel_user_deletion, create_user, find_user_by_id, find_user_by_login,
+            rehash_user_password, update_user,
+        },
+    },
+    domain::{
+        refresh_token::RefreshTokenInput,
+        user::{Claims, CreateUser, FindUser, UpdateUser, User},
+    },
     errors::api::ApiErrorResponse,
+    extractor::{AuthUser, Credentials},
+    oidc::OidcError,
+    rate_limit::{
+        account_lock_remaining, clear_failed_logins, record_failed_login, RateLimitError,
+    },
     router::State,
-    utils::hasher::{hash_password, verify_password},
+    utils::{
+        cipher::CipherError,
+        cookie::{
+            csrf_token_matches, remove_session_cookies, session_cookie, ACCESS_TOKEN_COOKIE,
+            CSRF_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE,
+        },
+        hasher::HasherError,
+        jwt::encode_token,
+        token::{generate_token, hash_token},
+    },
 };
 
 #[derive(Error, Debug)]
@@ -20,16 +62,130 @@ pub enum ApiError {
     BadClientData(#[from] ValidationErrors),
     #[error("user already registered")]
     UserAlreadyRegistered,
+    #[error("username already taken")]
+    UsernameTaken,
     #[error("user not found")]
     UserNotFound,
     #[error("wrong username or password")]
     BadCredentials,
-    #[error("could not hash password")]
-    HashError,
+    #[error("missing or invalid token")]
+    Unauthorized,
+    #[error("invalid refresh token")]
+    InvalidRefreshToken,
+    #[error("invalid or expired reset token")]
+    InvalidResetToken,
+    #[error("invalid or expired verification token")]
+    InvalidVerificationToken,
+    #[error("email not verified")]
+    EmailNotVerified,
+    #[error("account disabled")]
+    AccountDisabled,
+    #[error("password reset required")]
+    PasswordResetRequired,
+    #[error("admin role required")]
+    AdminRequired,
+    #[error("admins cannot apply this action to their own account")]
+    SelfManagement,
+    #[error("missing or invalid csrf token")]
+    InvalidCsrfToken,
+    #[error("token lacks the scope required by the request")]
+    InsufficientScope,
+    #[error("api token not found")]
+    ApiTokenNotFound,
+    #[error("two factor authentication already enabled")]
+    TwoFactorAlreadyEnabled,
+    #[error("two factor authentication setup not started")]
+    TwoFactorNotPending,
+    #[error("invalid two factor code")]
+    InvalidTwoFactorCode,
+    #[error("invalid or expired challenge token")]
+    InvalidTwoFactorChallenge,
+    #[error("identity provider not found")]
+    OidcProviderNotFound,
+    #[error("invalid or expired oidc state")]
+    InvalidOidcState,
+    #[error("identity provider did not share an email")]
+    OidcEmailMissing,
+    #[error("an account already uses the email of the identity provider")]
+    OidcAccountExists,
+    #[error("identity already linked")]
+    IdentityAlreadyLinked,
+    #[error("identity not found")]
+    IdentityNotFound,
+    #[error("task not found")]
+    TaskNotFound,
+    #[error("a task cannot be a subtask of itself or of one of its subtasks")]
+    InvalidParentTask,
+    #[error("subtasks are nested too deep")]
+    SubtaskTooDeep,
+    #[error("a task moves either after or before another task of the destination list")]
+    InvalidTaskPosition,
+    #[error("task has no due date")]
+    TaskDueDateRequired,
+    #[error("recurrence not found")]
+    RecurrenceNotFound,
+    #[error("list not found")]
+    ListNotFound,
+    #[error("list already exists")]
+    ListAlreadyExists,
+    #[error("tag not found")]
+    TagNotFound,
+    #[error("tag already exists")]
+    TagAlreadyExists,
+    #[error("reminder not found")]
+    ReminderNotFound,
+    #[error("notification not found")]
+    NotificationNotFound,
+    #[error("too many requests, retry after {retry_after} seconds")]
+    TooManyRequests { retry_after: i64 },
+    #[error(transparent)]
+    HashError(#[from] HasherError),
     #[error(transparent)]
     DbInternalError(#[from] sqlx::Error),
+    #[error(transparent)]
+    TaskPosition(#[from] TaskPositionError),
+    #[error(transparent)]
+    Cipher(#[from] CipherError),
     #[error("error encoding jwt")]
     JWTEncoding(#[from] jsonwebtoken::errors::Error),
+    #[error(transparent)]
+    RateLimit(#[from] RateLimitError),
+    #[error(transparent)]
+    Oidc(#[from] OidcError),
+}
+
+impl From<TaskParentError> for ApiError {
+    fn from(err: TaskParentError) -> Self {
+        match err {
+            TaskParentError::ParentNotFound => ApiError::TaskNotFound,
+            TaskParentError::Cycle => ApiError::InvalidParentTask,
+            TaskParentError::TooDeep => ApiError::SubtaskTooDeep,
+            TaskParentError::Db(err) => ApiError::DbInternalError(err),
+        }
+    }
+}
+
+impl ApiError {
+    /// Maps the violation of a unique constraint to `conflict`, for a name
+    /// taken in between by a concurrent request
+    pub fn on_unique_violation(conflict: ApiError) -> impl FnOnce(sqlx::Error) -> ApiError {
+        move |err| {
+            if is_unique_violation(&err) {
+                conflict
+            } else {
+                err.into()
+            }
+        }
+    }
+
+    pub fn too_many_requests(retry_after: Duration) -> Self {
+        // Rounding up so that clients retrying on time are not rejected again
+        let retry_after = (retry_after.num_milliseconds() + 999) / 1000;
+
+        Self::TooManyRequests {
+            retry_after: retry_after.max(1),
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -41,7 +197,10 @@ impl From<ValidationErrors> for ApiErrorResponse<ResponseErrorObject> {
     fn from(v: ValidationErrors) -> Self {
         let mut hash_map: HashMap<String, String> = HashMap::new();
         v.field_errors().into_iter().for_each(|(k, v)| {
-            let msg = format!("invalid {}", v[0].code);
+            let msg = match &v[0].message {
+                Some(message) => message.to_string(),
+                None => format!("invalid {}", v[0].code),
+            };
 
             hash_map.insert(k.into(), msg);
         });
@@ -68,19 +227,251 @@ impl IntoResponse for ApiError {
                 Json(ApiErrorResponse::<()>::from("user already registered")),
             )
                 .into_response(),
+            ApiError::UsernameTaken => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from("username already taken")),
+            )
+                .into_response(),
             ApiError::BadClientData(err) => (
                 status::StatusCode::BAD_REQUEST,
                 Json(ApiErrorResponse::from(err)),
             )
                 .into_response(),
-            ApiError::HashError | ApiError::DbInternalError(_) | ApiError::JWTEncoding(_) => {
-                status::StatusCode::INTERNAL_SERVER_ERROR.into_response()
-            }
+            ApiError::HashError(HasherError::Saturated) => (
+                status::StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, "1")],
+                Json(ApiErrorResponse::<()>::from("server busy, retry later")),
+            )
+                .into_response(),
+            ApiError::TaskPosition(TaskPositionError::NoRoom) => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from(
+                    "no position left between the neighbours of the task",
+                )),
+            )
+                .into_response(),
+            ApiError::HashError(_)
+            | ApiError::DbInternalError(_)
+            | ApiError::TaskPosition(_)
+            | ApiError::Cipher(_)
+            | ApiError::JWTEncoding(_)
+            | ApiError::RateLimit(_) => status::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            ApiError::TooManyRequests { retry_after } => (
+                status::StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.to_string())],
+                Json(ApiErrorResponse::<()>::from("too many requests")),
+            )
+                .into_response(),
             ApiError::BadCredentials => (
                 status::StatusCode::NOT_ACCEPTABLE,
                 Json(ApiErrorResponse::<()>::from("bad credentials")),
             )
                 .into_response(),
+            ApiError::Unauthorized => (
+                status::StatusCode::UNAUTHORIZED,
+                [(WWW_AUTHENTICATE, "Bearer")],
+                Json(ApiErrorResponse::<()>::from("unauthorized")),
+            )
+                .into_response(),
+            ApiError::InvalidRefreshToken => (
+                status::StatusCode::UNAUTHORIZED,
+                Json(ApiErrorResponse::<()>::from("invalid refresh token")),
+            )
+                .into_response(),
+            ApiError::InvalidResetToken => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from(
+                    "invalid or expired reset token",
+                )),
+            )
+                .into_response(),
+            ApiError::InvalidVerificationToken => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from(
+                    "invalid or expired verification token",
+                )),
+            )
+                .into_response(),
+            ApiError::EmailNotVerified => (
+                status::StatusCode::FORBIDDEN,
+                Json(ApiErrorResponse::<()>::from("email not verified")),
+            )
+                .into_response(),
+            ApiError::AccountDisabled => (
+                status::StatusCode::FORBIDDEN,
+                Json(ApiErrorResponse::<()>::from("account disabled")),
+            )
+                .into_response(),
+            ApiError::PasswordResetRequired => (
+                status::StatusCode::FORBIDDEN,
+                Json(ApiErrorResponse::<()>::from("password reset required")),
+            )
+                .into_response(),
+            ApiError::AdminRequired => (
+                status::StatusCode::FORBIDDEN,
+                Json(ApiErrorResponse::<()>::from("admin role required")),
+            )
+                .into_response(),
+            ApiError::SelfManagement => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from(
+                    "cannot apply this action to your own account",
+                )),
+            )
+                .into_response(),
+            ApiError::InvalidCsrfToken => (
+                status::StatusCode::FORBIDDEN,
+                Json(ApiErrorResponse::<()>::from("invalid csrf token")),
+            )
+                .into_response(),
+            ApiError::InsufficientScope => (
+                status::StatusCode::FORBIDDEN,
+                Json(ApiErrorResponse::<()>::from("insufficient scope")),
+            )
+                .into_response(),
+            ApiError::ApiTokenNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("api token not found")),
+            )
+                .into_response(),
+            ApiError::TwoFactorAlreadyEnabled => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from(
+                    "two factor authentication already enabled",
+                )),
+            )
+                .into_response(),
+            ApiError::TwoFactorNotPending => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from(
+                    "two factor authentication setup not started",
+                )),
+            )
+                .into_response(),
+            ApiError::InvalidTwoFactorCode => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from("invalid two factor code")),
+            )
+                .into_response(),
+            ApiError::InvalidTwoFactorChallenge => (
+                status::StatusCode::UNAUTHORIZED,
+                Json(ApiErrorResponse::<()>::from(
+                    "invalid or expired challenge token",
+                )),
+            )
+                .into_response(),
+            ApiError::OidcProviderNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("identity provider not found")),
+            )
+                .into_response(),
+            ApiError::InvalidOidcState => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from("invalid or expired state")),
+            )
+                .into_response(),
+            ApiError::OidcEmailMissing => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from(
+                    "identity provider did not share an email",
+                )),
+            )
+                .into_response(),
+            ApiError::OidcAccountExists => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from(
+                    "an account already uses this email, log in to link the identity provider",
+                )),
+            )
+                .into_response(),
+            ApiError::IdentityAlreadyLinked => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from("identity already linked")),
+            )
+                .into_response(),
+            ApiError::IdentityNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("identity not found")),
+            )
+                .into_response(),
+            ApiError::Oidc(err) if err.is_rejected_login() => (
+                status::StatusCode::UNAUTHORIZED,
+                Json(ApiErrorResponse::<()>::from(
+                    "invalid login from the identity provider",
+                )),
+            )
+                .into_response(),
+            ApiError::Oidc(_) => (
+                status::StatusCode::BAD_GATEWAY,
+                Json(ApiErrorResponse::<()>::from(
+                    "identity provider unavailable",
+                )),
+            )
+                .into_response(),
+            ApiError::TaskNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("task not found")),
+            )
+                .into_response(),
+            ApiError::InvalidParentTask => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from(
+                    "a task cannot be a subtask of itself or of one of its subtasks",
+                )),
+            )
+                .into_response(),
+            ApiError::SubtaskTooDeep => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from("subtasks are nested too deep")),
+            )
+                .into_response(),
+            ApiError::InvalidTaskPosition => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from(
+                    "a task moves either after or before another task of the destination list",
+                )),
+            )
+                .into_response(),
+            ApiError::TaskDueDateRequired => (
+                status::StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::<()>::from("task has no due date")),
+            )
+                .into_response(),
+            ApiError::RecurrenceNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("recurrence not found")),
+            )
+                .into_response(),
+            ApiError::ListNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("list not found")),
+            )
+                .into_response(),
+            ApiError::ListAlreadyExists => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from("list already exists")),
+            )
+                .into_response(),
+            ApiError::TagNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("tag not found")),
+            )
+                .into_response(),
+            ApiError::TagAlreadyExists => (
+                status::StatusCode::CONFLICT,
+                Json(ApiErrorResponse::<()>::from("tag already exists")),
+            )
+                .into_response(),
+            ApiError::ReminderNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("reminder not found")),
+            )
+                .into_response(),
+            ApiError::NotificationNotFound => (
+                status::StatusCode::NOT_FOUND,
+                Json(ApiErrorResponse::<()>::from("notification not found")),
+            )
+                .into_response(),
         }
     }
 }
@@ -88,31 +479,164 @@ impl IntoResponse for ApiError {
 #[derive(Debug, Serialize)]
 pub struct ApiResponse {
     pub token: String,
+    pub refresh_token: String,
     pub user: User,
 }
 
+fn encode_access_token(user: &User, family_id: Uuid, state: &State) -> Result<String, ApiError> {
+    let now = Utc::now();
+
+    let claims = Claims {
+        sub: user.id.to_string(),
+        jti: Uuid::new_v4(),
+        sid: family_id,
+        role: user.role,
+        iat: now,
+        exp: now + state.access_token_ttl,
+    };
+
+    Ok(encode_token(&claims, &state.jwt_keys)?)
+}
+
+/// Rejects users an admin disabled or asked to reset their password
+pub(super) fn check_can_login(user: &User) -> Result<(), ApiError> {
+    if user.is_disabled() {
+        return Err(ApiError::AccountDisabled);
+    }
+    if user.password_reset_required {
+        return Err(ApiError::PasswordResetRequired);
+    }
+
+    Ok(())
+}
+
+/// Checks the current password of a signed in user confirming a change.
+///
+/// Wrong passwords count towards the lockout of the account, as on login, so
+/// that a stolen access token does not allow guessing the password. Only a
+/// complete login clears the failures.
+pub(super) async fn confirm_password(
+    user: &User,
+    password: &str,
+    state: &State,
+) -> Result<(), ApiError> {
+    let store = state.rate_limiter.as_ref();
+    let lockout = &state.rate_limit.lockout;
+    let account = user.id.to_string();
+    if let Some(remaining) = account_lock_remaining(store, lockout, &account).await? {
+        return Err(ApiError::too_many_requests(remaining));
+    }
+
+    let is_match = state
+        .hasher
+        .verify_password(password.as_bytes(), &user.password_hash)
+        .await?;
+    if !is_match {
+        record_failed_login(store, lockout, &account).await?;
+        return Err(ApiError::BadCredentials);
+    }
+
+    Ok(())
+}
+
+/// Issues an access token along with a refresh token starting a new token family
+///
+/// Logging in during the grace period of an account deletion cancels it.
+pub(super) async fn create_session(mut user: User, state: &State) -> Result<ApiResponse, ApiError> {
+    if user.deletion_scheduled_at.is_some() {
+        cancel_user_deletion(user.id, &state.db_pool).await?;
+        user.deletion_scheduled_at = None;
+    }
+
+    let family_id = Uuid::new_v4();
+    let token = encode_access_token(&user, family_id, state)?;
+
+    let refresh_token = generate_token();
+    create_refresh_token(
+        user.id,
+        family_id,
+        &hash_token(&refresh_token),
+        Utc::now() + state.refresh_token_ttl,
+        &state.db_pool,
+    )
+    .await?;
+
+    Ok(ApiResponse {
+        token,
+        refresh_token,
+        user,
+    })
+}
+
+/// Body of a session response when tokens are only set in cookies
+#[derive(Debug, Serialize)]
+pub struct CookieSessionResponse {
+    pub user: User,
+}
+
+/// Hands a session to the client, in the body and/or in cookies depending on
+/// the session transport
+pub(super) fn session_response(session: ApiResponse, jar: CookieJar, state: &State) -> Response {
+    let transport = state.session_transport;
+    if !transport.uses_cookie() {
+        return Json(session).into_response();
+    }
+
+    let settings = &state.session_cookie;
+    let jar = jar
+        .add(session_cookie(
+            settings,
+            ACCESS_TOKEN_COOKIE,
+            session.token.clone(),
+            state.access_token_ttl,
+        ))
+        .add(session_cookie(
+            settings,
+            REFRESH_TOKEN_COOKIE,
+            session.refresh_token.clone(),
+            state.refresh_token_ttl,
+        ))
+        .add(session_cookie(
+            settings,
+            CSRF_TOKEN_COOKIE,
+            generate_token(),
+            state.refresh_token_ttl,
+        ));
+
+    if transport.uses_header() {
+        (jar, Json(session)).into_response()
+    } else {
+        (jar, Json(CookieSessionResponse { user: session.user })).into_response()
+    }
+}
+
+/// Registers a user and sends them a verification email.
+///
+/// No session is opened when a verified email is required to log in, only the
+/// created user is returned.
 #[tracing::instrument(err)]
 pub async fn register_handler(
+    jar: CookieJar,
     Json(user_input): Json<CreateUser>,
     Extension(state): Extension<Arc<State>>,
-) -> Result<Json<ApiResponse>, ApiError> {
+) -> Result<Response, ApiError> {
     // Validating user_input
-    user_input.validate()?;
+    state
+        .password_policy
+        .validate(
+            user_input.validate(),
+            "password",
+            &user_input.password,
+            &[&user_input.username, &user_input.email],
+        )
+        .await?;
     let state = state.clone();
 
-    // Check if user already exists
-    let count =
-        user_exists_by_username_or_email(&user_input.username, &user_input.email, &state.db_pool)
-            .await?
-            .map_or(0, |x| x);
-
-    if count != 0 {
-        return Err(ApiError::UserAlreadyRegistered);
-    }
-
     // Hash password
-    let hashed_password =
-        hash_password(user_input.password.as_bytes()).map_err(|_| ApiError::HashError)?;
+    let hashed_password = state
+        .hasher
+        .hash_password(user_input.password.as_bytes())
+        .await?;
 
     // Inserting User
     let user_input = CreateUser {
@@ -120,58 +644,284 @@ pub async fn register_handler(
         ..user_input
     };
 
-    let user = create_user(user_input, &state.db_pool).await?;
+    // A taken username or email is only detected by the insert, so that
+    // concurrent registrations are rejected the same way
+    let user =
+        create_user(user_input, &state.db_pool)
+            .await
+            .map_err(ApiError::on_unique_violation(
+                ApiError::UserAlreadyRegistered,
+            ))?;
 
-    let now = chrono::Utc::now();
+    send_verification_email(&user, &state).await?;
 
-    let claims = Claims {
-        sub: user.id.to_string(),
-        iat: now,
-        exp: now + Duration::hours(4),
-    };
+    if state.email_verification == EmailVerificationPolicy::RequiredForLogin {
+        return Ok((status::StatusCode::ACCEPTED, Json(user)).into_response());
+    }
 
-    let token = jsonwebtoken::encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
-    )?;
+    let res = create_session(user, &state).await?;
 
-    let res = ApiResponse { token, user };
+    Ok(session_response(res, jar, &state))
+}
 
-    Ok(Json(res))
+/// Upgrades the hash of a password that was just verified to the configured
+/// parameters, failures are only logged as the login can go on with the old hash
+async fn rehash_password(user: &User, password: &str, state: &State) {
+    let password_hash = match state.hasher.hash_password(password.as_bytes()).await {
+        Ok(password_hash) => password_hash,
+        Err(err) => {
+            tracing::error!(%err, user_id = %user.id, "could not rehash password");
+            return;
+        }
+    };
+
+    match rehash_user_password(user.id, &user.password_hash, &password_hash, &state.db_pool).await {
+        Ok(rehashed) => tracing::debug!(rehashed, user_id = %user.id, "rehashed password"),
+        Err(err) => tracing::error!(%err, user_id = %user.id, "could not store rehashed password"),
+    }
 }
 
+/// Logs a user in with their password.
+///
+/// When 2FA is enabled, no session is opened yet: a challenge token is returned
+/// instead, to exchange along with a code on `/login/2fa`.
 pub async fn login_handler(
+    jar: CookieJar,
     Json(login_input): Json<FindUser>,
     Extension(state): Extension<Arc<State>>,
-) -> Result<Json<ApiResponse>, ApiError> {
+) -> Result<Response, ApiError> {
     let state = state.clone();
+    let store = state.rate_limiter.as_ref();
+    let lockout = &state.rate_limit.lockout;
 
-    let user = find_user_by_username(&login_input.username, &state.db_pool)
-        .await?
-        .ok_or(ApiError::UserNotFound)?;
+    let user = find_user_by_login(&login_input.login, &state.db_pool).await?;
+
+    // Failed logins are counted per account, whether its username or its email
+    // is used, and per login for unknown users
+    let account = match &user {
+        Some(user) => user.id.to_string(),
+        None => login_input.login.clone(),
+    };
+    if let Some(remaining) = account_lock_remaining(store, lockout, &account).await? {
+        return Err(ApiError::too_many_requests(remaining));
+    }
+
+    // Unknown users and wrong passwords are indistinguishable, in response and timing
+    let user = match user {
+        Some(user) => user,
+        None => {
+            state
+                .hasher
+                .dummy_verify_password(login_input.password.as_bytes())
+                .await?;
+            record_failed_login(store, lockout, &account).await?;
+            return Err(ApiError::BadCredentials);
+        }
+    };
 
-    let is_match = verify_password(login_input.password.as_bytes(), &user.password_hash)
-        .map_err(|_| ApiError::HashError)?;
+    let is_match = state
+        .hasher
+        .verify_password(login_input.password.as_bytes(), &user.password_hash)
+        .await?;
     if !is_match {
+        record_failed_login(store, lockout, &account).await?;
         return Err(ApiError::BadCredentials);
     }
 
-    let now = chrono::Utc::now();
+    if state.hasher.needs_rehash(&user.password_hash) {
+        rehash_password(&user, &login_input.password, &state).await;
+    }
 
-    let claims = Claims {
-        sub: user.id.to_string(),
-        iat: now,
-        exp: now + Duration::hours(4),
+    check_can_login(&user)?;
+
+    if state.email_verification == EmailVerificationPolicy::RequiredForLogin
+        && user.email_verified_at.is_none()
+    {
+        return Err(ApiError::EmailNotVerified);
+    }
+
+    // Failed logins are only cleared once the second factor is checked too,
+    // entering the password again does not give more attempts at the code
+    if let Some(challenge) = start_two_factor_challenge(&user, &state).await? {
+        return Ok((status::StatusCode::ACCEPTED, Json(challenge)).into_response());
+    }
+    clear_failed_logins(store, &account).await?;
+
+    let res = create_session(user, &state).await?;
+
+    Ok(session_response(res, jar, &state))
+}
+
+#[tracing::instrument(err, skip_all)]
+pub async fn refresh_token_handler(
+    jar: CookieJar,
+    headers: HeaderMap,
+    refresh_input: Option<Json<RefreshTokenInput>>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Response, ApiError> {
+    let transport = state.session_transport;
+    let presented_token = match refresh_input {
+        Some(Json(refresh_input)) if transport.uses_header() => refresh_input.refresh_token,
+        _ if transport.uses_cookie() => {
+            let cookie = jar
+                .get(REFRESH_TOKEN_COOKIE)
+                .ok_or(ApiError::InvalidRefreshToken)?;
+            if !csrf_token_matches(&jar, &headers) {
+                return Err(ApiError::InvalidCsrfToken);
+            }
+
+            cookie.value().to_owned()
+        }
+        _ => return Err(ApiError::InvalidRefreshToken),
+    };
+
+    let refresh_token = find_refresh_token_by_hash(&hash_token(&presented_token), &state.db_pool)
+        .await?
+        .ok_or(ApiError::InvalidRefreshToken)?;
+
+    // A rotated token being presented again means it leaked, the whole family is revoked
+    if refresh_token.is_used() {
+        tracing::warn!(family_id = %refresh_token.family_id, "refresh token reuse detected");
+        revoke_refresh_token_family(refresh_token.family_id, &state.db_pool).await?;
+        return Err(ApiError::InvalidRefreshToken);
+    }
+
+    if refresh_token.is_expired() {
+        return Err(ApiError::InvalidRefreshToken);
+    }
+
+    // Rotating refresh token
+    let new_refresh_token = generate_token();
+    let rotated = rotate_refresh_token(
+        &refresh_token,
+        &hash_token(&new_refresh_token),
+        Utc::now() + state.refresh_token_ttl,
+        &state.db_pool,
+    )
+    .await?;
+
+    // Lost a race against another rotation of the same token
+    if rotated.is_none() {
+        revoke_refresh_token_family(refresh_token.family_id, &state.db_pool).await?;
+        return Err(ApiError::InvalidRefreshToken);
+    }
+
+    let user = find_user_by_id(refresh_token.user_id, &state.db_pool)
+        .await?
+        .ok_or(ApiError::InvalidRefreshToken)?;
+    check_can_login(&user)?;
+
+    let token = encode_access_token(&user, refresh_token.family_id, &state)?;
+
+    let res = ApiResponse {
+        token,
+        refresh_token: new_refresh_token,
+        user,
+    };
+
+    Ok(session_response(res, jar, &state))
+}
+
+/// Revokes the presented access token and the session it belongs to
+#[tracing::instrument(err, skip_all)]
+pub async fn logout_handler(
+    jar: CookieJar,
+    AuthUser { user, credentials }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(CookieJar, status::StatusCode), ApiError> {
+    // Api tokens are not accepted here, they are revoked on their own
+    let claims = match credentials {
+        Credentials::Session(claims) => claims,
+        Credentials::ApiToken(_) => return Err(ApiError::InsufficientScope),
     };
 
-    let token = jsonwebtoken::encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
-    )?;
+    revoke_token(claims.jti, user.id, claims.exp, &state.db_pool).await?;
+    revoke_refresh_token_family(claims.sid, &state.db_pool).await?;
+
+    Ok((
+        clear_session_cookies(jar, &state),
+        status::StatusCode::NO_CONTENT,
+    ))
+}
+
+/// Revokes every session of the user, along with their access tokens
+#[tracing::instrument(err, skip_all)]
+pub async fn logout_all_handler(
+    jar: CookieJar,
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<(CookieJar, status::StatusCode), ApiError> {
+    revoke_refresh_tokens_by_user_id(user.id, &state.db_pool).await?;
+
+    Ok((
+        clear_session_cookies(jar, &state),
+        status::StatusCode::NO_CONTENT,
+    ))
+}
+
+pub(super) fn clear_session_cookies(jar: CookieJar, state: &State) -> CookieJar {
+    if state.session_transport.uses_cookie() {
+        remove_session_cookies(jar, &state.session_cookie)
+    } else {
+        jar
+    }
+}
 
-    let res = ApiResponse { token, user };
+pub async fn me_handler(AuthUser { user, .. }: AuthUser) -> Json<User> {
+    Json(user)
+}
+
+/// Updates the username and/or the password of the authenticated user.
+///
+/// Changing the password revokes every session, including the current one.
+#[tracing::instrument(err, skip_all)]
+pub async fn update_me_handler(
+    AuthUser { user, .. }: AuthUser,
+    Extension(state): Extension<Arc<State>>,
+    Json(user_input): Json<UpdateUser>,
+) -> Result<Json<User>, ApiError> {
+    // Validating user_input
+    let validation = user_input.validate();
+    match user_input.new_password.as_deref() {
+        Some(new_password) => {
+            let username = user_input.username.as_deref().unwrap_or(&user.username);
+            state
+                .password_policy
+                .validate(
+                    validation,
+                    "new_password",
+                    new_password,
+                    &[&user.username, username, &user.email],
+                )
+                .await?
+        }
+        None => validation?,
+    }
+
+    // Confirming the change with the current password
+    confirm_password(&user, &user_input.old_password, &state).await?;
+
+    let username = user_input
+        .username
+        .as_deref()
+        .filter(|username| *username != user.username);
+
+    // Hash new password
+    let password_hash = match user_input.new_password.as_deref() {
+        Some(password) => Some(state.hasher.hash_password(password.as_bytes()).await?),
+        None => None,
+    };
+
+    // A username taken by another user violates its unique constraint
+    let updated_user = update_user(user.id, username, password_hash.as_deref(), &state.db_pool)
+        .await
+        .map_err(ApiError::on_unique_violation(ApiError::UsernameTaken))?
+        .ok_or(ApiError::UserNotFound)?;
+
+    // Sessions opened with the old password are no longer trusted
+    if password_hash.is_some() {
+        revoke_refresh_tokens_by_user_id(user.id, &state.db_pool).await?;
+    }
 
-    Ok(Json(res))
+    Ok(Json(updated_user))
 }
@@ -2,7 +2,13 @@ pub mod configuration;
 pub mod db;
 pub mod domain;
 pub mod errors;
+pub mod extractor;
 pub mod handler;
+pub mod mailer;
+pub mod notifier;
+pub mod oidc;
+pub mod password_policy;
+pub mod rate_limit;
 pub mod router;
 pub mod server;
 pub mod utils;
@@ -0,0 +1,65 @@
+use axum::async_trait;
+use chrono::Utc;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::{Email, Mailer, MailerError};
+
+/// Drops every email as a text file in a directory, meant for local development
+#[derive(Debug)]
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), MailerError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let file_name = format!(
+            "{}-{}.txt",
+            Utc::now().format("%Y%m%dT%H%M%S"),
+            Uuid::new_v4()
+        );
+        let content = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+
+        tokio::fs::write(self.dir.join(file_name), content).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn send_writes_email_to_file() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let mailer = FileMailer::new(&dir);
+
+        let email = Email {
+            to: "test@email.com".into(),
+            subject: "subject".into(),
+            body: "body".into(),
+        };
+
+        mailer.send(email).await.unwrap();
+
+        let mut entries = std::fs::read_dir(&dir).unwrap();
+        let content = std::fs::read_to_string(entries.next().unwrap().unwrap().path()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(content.starts_with("To: test@email.com\nSubject: subject\n"));
+        assert!(content.contains("body"));
+    }
+}
@@ -0,0 +1,37 @@
+use axum::async_trait;
+use std::sync::Mutex;
+
+use super::{Email, Mailer, MailerError};
+
+/// Keeps sent emails in memory so that they can be inspected, meant for tests
+#[derive(Debug, Default)]
+pub struct InMemoryMailer {
+    emails: Mutex<Vec<Email>>,
+}
+
+impl InMemoryMailer {
+    /// Every email sent so far, oldest first
+    pub fn emails(&self) -> Vec<Email> {
+        self.emails.lock().unwrap().clone()
+    }
+
+    /// Last email sent to `to`
+    pub fn last_email_to(&self, to: &str) -> Option<Email> {
+        self.emails
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|email| email.to == to)
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, email: Email) -> Result<(), MailerError> {
+        self.emails.lock().unwrap().push(email);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+mod file;
+mod memory;
+mod smtp;
+
+pub use file::*;
+pub use memory::*;
+pub use smtp::*;
+
+use axum::async_trait;
+use std::{fmt::Debug, sync::Arc};
+use thiserror::Error;
+
+use crate::configuration::MailerSettings;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Error, Debug)]
+pub enum MailerError {
+    #[error("invalid email address")]
+    Address(#[from] lettre::address::AddressError),
+    #[error(transparent)]
+    Build(#[from] lettre::error::Error),
+    #[error(transparent)]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Sends transactional emails (password reset, email verification...)
+#[async_trait]
+pub trait Mailer: Debug + Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), MailerError>;
+}
+
+/// Builds the mailer configured in `settings`
+pub fn build_mailer(settings: &MailerSettings) -> Result<Arc<dyn Mailer>, MailerError> {
+    let mailer: Arc<dyn Mailer> = match settings {
+        MailerSettings::Smtp {
+            host,
+            port,
+            username,
+            password,
+            from,
+        } => Arc::new(SmtpMailer::new(host, *port, username, password, from)?),
+        MailerSettings::File { dir } => Arc::new(FileMailer::new(dir)),
+        MailerSettings::Memory => Arc::new(InMemoryMailer::default()),
+    };
+
+    Ok(mailer)
+}
@@ -1,8 +1,20 @@
 use lib::configuration;
-use lib::{router::setup_router, server::make_server};
+use lib::{
+    db::two_factor::seal_plaintext_totp_secrets,
+    mailer::{build_mailer, MailerError},
+    notifier::Notifiers,
+    router::setup_router,
+    server::{make_server, spawn_cleanup_task, spawn_reminder_worker},
+    utils::{
+        cipher::{CipherError, SecretCipher},
+        hasher::Hasher,
+        jwt::{JwtKeyError, JwtKeys},
+    },
+};
 use sqlx::PgPool;
 use std::io;
 use std::net::TcpListener;
+use std::time::Duration;
 use thiserror::Error;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
@@ -29,12 +41,43 @@ async fn main() -> Result<(), Error> {
     let db_uri = config.database_settings.connection_string_with_db_name();
     let db_pool = PgPool::connect(&db_uri).await.unwrap();
 
+    // Purge expired token revocations, rate limit counters and oidc states every hour
+    spawn_cleanup_task(db_pool.clone(), Duration::from_secs(60 * 60));
+
     // Setup listener
     let address = config.app_settings.address();
     let listener = TcpListener::bind(address)?;
 
+    // Setup mailer
+    let mailer = build_mailer(&config.mailer_settings)?;
+
+    // Deliver task reminders in the background
+    let reminder_settings = config.app_settings.reminders.clone();
+    let notifiers = Notifiers::new(mailer.clone(), db_pool.clone(), &reminder_settings);
+    spawn_reminder_worker(db_pool.clone(), notifiers, reminder_settings);
+
+    // Load token signing keys
+    let jwt_keys = JwtKeys::from_settings(&config.app_settings)?;
+
+    // Setup password hashing
+    let hasher = Hasher::from_settings(&config.app_settings.password_hashing)
+        .map_err(Error::PasswordHashing)?;
+
+    // Load the key of the totp secrets, sealing the ones stored before they
+    // were encrypted
+    let totp_cipher =
+        SecretCipher::from_key(&config.app_settings.totp_encryption_key).map_err(Error::TotpKey)?;
+    seal_plaintext_totp_secrets(&totp_cipher, &db_pool).await?;
+
     // Setup router
-    let router = setup_router(db_pool, config.app_settings.jwt_secret);
+    let router = setup_router(
+        db_pool,
+        config.app_settings,
+        mailer,
+        jwt_keys,
+        hasher,
+        totp_cipher,
+    );
 
     make_server(listener, router).await?;
     Ok(())
@@ -48,4 +91,14 @@ enum Error {
     Server(#[from] hyper::Error),
     #[error(transparent)]
     Config(#[from] config::ConfigError),
+    #[error(transparent)]
+    Mailer(#[from] MailerError),
+    #[error(transparent)]
+    JwtKeys(#[from] JwtKeyError),
+    #[error("invalid password hashing settings: {0}")]
+    PasswordHashing(argon2::password_hash::Error),
+    #[error("invalid totp_encryption_key: {0}")]
+    TotpKey(CipherError),
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
 }
@@ -0,0 +1,37 @@
+use axum::async_trait;
+use std::sync::Arc;
+
+use super::{reminder_message, Notifier, NotifierError};
+use crate::{
+    domain::reminder::DueReminder,
+    mailer::{Email, Mailer},
+};
+
+/// Sends reminders to the email address of the user
+#[derive(Debug)]
+pub struct EmailNotifier {
+    mailer: Arc<dyn Mailer>,
+}
+
+impl EmailNotifier {
+    pub fn new(mailer: Arc<dyn Mailer>) -> Self {
+        Self { mailer }
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, reminder: &DueReminder) -> Result<(), NotifierError> {
+        let (subject, body) = reminder_message(reminder);
+
+        self.mailer
+            .send(Email {
+                to: reminder.email.clone(),
+                subject,
+                body,
+            })
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use axum::async_trait;
+use sqlx::PgPool;
+
+use super::{reminder_message, Notifier, NotifierError};
+use crate::{db::notification::create_notification, domain::reminder::DueReminder};
+
+/// Stores reminders as notifications listed by the api, at most one per
+/// reminder however many times it is delivered
+#[derive(Debug)]
+pub struct InAppNotifier {
+    db_pool: PgPool,
+}
+
+impl InAppNotifier {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl Notifier for InAppNotifier {
+    async fn notify(&self, reminder: &DueReminder) -> Result<(), NotifierError> {
+        let (title, body) = reminder_message(reminder);
+
+        create_notification(
+            reminder.user_id,
+            Some(reminder.id),
+            &title,
+            &body,
+            &self.db_pool,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! Delivery of task reminders, by email, webhook or in-app notification
+mod email;
+mod in_app;
+mod webhook;
+
+pub use email::*;
+pub use in_app::*;
+pub use webhook::*;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use hyper::StatusCode;
+use sqlx::PgPool;
+use std::{fmt::Debug, sync::Arc};
+use thiserror::Error;
+
+use crate::{
+    configuration::ReminderSettings,
+    db::reminder::{claim_due_reminders, mark_reminder_delivered, record_reminder_failure},
+    domain::reminder::{DueReminder, NotificationChannel},
+    mailer::{Mailer, MailerError},
+};
+
+#[derive(Error, Debug)]
+pub enum NotifierError {
+    #[error(transparent)]
+    Mailer(#[from] MailerError),
+    #[error("invalid webhook url: {0}")]
+    InvalidUrl(#[from] hyper::http::Error),
+    #[error("could not reach the webhook: {0}")]
+    Http(#[from] hyper::Error),
+    #[error("webhook did not answer in time")]
+    Timeout,
+    #[error("webhook answered {0}")]
+    Status(StatusCode),
+    #[error("reminder has no webhook url")]
+    MissingWebhookUrl,
+    #[error("webhook url is not a public https url")]
+    ForbiddenUrl,
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+/// Notifies a user of a due reminder.
+///
+/// Reminders may be delivered more than once, e.g. when the worker stops before
+/// recording the delivery, notifiers dedupe on the reminder id when they can.
+#[async_trait]
+pub trait Notifier: Debug + Send + Sync {
+    async fn notify(&self, reminder: &DueReminder) -> Result<(), NotifierError>;
+}
+
+/// Notifier of each channel
+#[derive(Debug, Clone)]
+pub struct Notifiers {
+    pub email: Arc<dyn Notifier>,
+    pub webhook: Arc<dyn Notifier>,
+    pub in_app: Arc<dyn Notifier>,
+}
+
+impl Notifiers {
+    pub fn new(mailer: Arc<dyn Mailer>, db_pool: PgPool, settings: &ReminderSettings) -> Self {
+        Self {
+            email: Arc::new(EmailNotifier::new(mailer)),
+            webhook: Arc::new(WebhookNotifier::new(
+                settings.webhook_secret.clone(),
+                std::time::Duration::from_secs(settings.webhook_timeout_seconds),
+                settings.allow_private_webhooks,
+            )),
+            in_app: Arc::new(InAppNotifier::new(db_pool)),
+        }
+    }
+
+    pub fn get(&self, channel: NotificationChannel) -> &dyn Notifier {
+        match channel {
+            NotificationChannel::Email => self.email.as_ref(),
+            NotificationChannel::Webhook => self.webhook.as_ref(),
+            NotificationChannel::InApp => self.in_app.as_ref(),
+        }
+    }
+}
+
+/// Delivers the reminders that are due, returns how many were delivered.
+///
+/// Up to `concurrency` reminders are delivered at once. Failed deliveries are
+/// retried with an exponential backoff until `max_attempts`, reminders of tasks
+/// completed since are dropped.
+pub async fn dispatch_due_reminders(
+    db_pool: &PgPool,
+    notifiers: &Notifiers,
+    settings: &ReminderSettings,
+) -> Result<usize, sqlx::Error> {
+    let reminders =
+        claim_due_reminders(settings.batch_size, settings.lease_seconds as f64, db_pool).await?;
+
+    stream::iter(reminders)
+        .map(|reminder| deliver_reminder(reminder, db_pool, notifiers, settings))
+        .buffer_unordered(settings.concurrency.max(1))
+        .try_fold(0, |delivered, sent| async move {
+            Ok(delivered + usize::from(sent))
+        })
+        .await
+}
+
+/// Delivers a claimed reminder, returns whether it was sent.
+///
+/// Once the lease is over the reminder belongs to the worker that claims it
+/// next, it is neither sent nor recorded anymore.
+async fn deliver_reminder(
+    reminder: DueReminder,
+    db_pool: &PgPool,
+    notifiers: &Notifiers,
+    settings: &ReminderSettings,
+) -> Result<bool, sqlx::Error> {
+    if reminder.task_completed {
+        mark_reminder_delivered(reminder.id, reminder.lease_until, db_pool).await?;
+        return Ok(false);
+    }
+    if reminder.lease_until <= Utc::now() {
+        tracing::warn!(reminder_id = %reminder.id, "lease over before the reminder was sent");
+        return Ok(false);
+    }
+
+    match notifiers.get(reminder.channel).notify(&reminder).await {
+        Ok(()) => {
+            if !mark_reminder_delivered(reminder.id, reminder.lease_until, db_pool).await? {
+                tracing::warn!(reminder_id = %reminder.id, "reminder sent once its lease was over");
+            }
+            Ok(true)
+        }
+        Err(err) => {
+            tracing::warn!(reminder_id = %reminder.id, attempts = reminder.attempts, %err, "could not deliver reminder");
+            let retry_at = (reminder.attempts < settings.max_attempts)
+                .then(|| Utc::now() + retry_delay(reminder.attempts, settings));
+            let recorded = record_reminder_failure(
+                reminder.id,
+                reminder.lease_until,
+                &err.to_string(),
+                retry_at,
+                db_pool,
+            )
+            .await?;
+            if !recorded {
+                tracing::warn!(reminder_id = %reminder.id, "lease over before the failure was recorded");
+            }
+            Ok(false)
+        }
+    }
+}
+
+/// Doubles the base delay on each attempt, an hour at most
+fn retry_delay(attempts: i32, settings: &ReminderSettings) -> Duration {
+    let factor = 2u64.saturating_pow(attempts.saturating_sub(1) as u32);
+    let seconds = settings
+        .retry_base_seconds
+        .saturating_mul(factor)
+        .min(60 * 60);
+
+    Duration::seconds(seconds as i64)
+}
+
+/// Title and body shown to the user, times are given in the time zone the
+/// reminder was set in
+pub fn reminder_message(reminder: &DueReminder) -> (String, String) {
+    let title = format!("Reminder: {}", reminder.task_title);
+    let body = match reminder.due_at {
+        Some(due_at) => format!(
+            "\"{}\" is due on {}.",
+            reminder.task_title,
+            local_time(due_at, &reminder.timezone)
+        ),
+        None => format!(
+            "You asked to be reminded of \"{}\" on {}.",
+            reminder.task_title,
+            local_time(reminder.remind_at, &reminder.timezone)
+        ),
+    };
+
+    (title, body)
+}
+
+fn local_time(time: DateTime<Utc>, timezone: &str) -> String {
+    let tz: Tz = timezone.parse().unwrap_or(Tz::UTC);
+
+    time.with_timezone(&tz)
+        .format("%Y-%m-%d %H:%M %Z")
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    fn due_reminder(due_at: Option<DateTime<Utc>>) -> DueReminder {
+        DueReminder {
+            id: Uuid::new_v4(),
+            task_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            email: "email@gmail.com".into(),
+            task_title: "pay rent".into(),
+            task_completed: false,
+            due_at,
+            remind_at: Utc.ymd(2022, 12, 20).and_hms(8, 0, 0),
+            timezone: "Europe/Paris".into(),
+            channel: NotificationChannel::Email,
+            webhook_url: None,
+            attempts: 1,
+            lease_until: Utc.ymd(2022, 12, 20).and_hms(8, 5, 0),
+        }
+    }
+
+    #[test]
+    fn message_shows_times_in_the_reminder_time_zone() {
+        let (title, body) = reminder_message(&due_reminder(None));
+        let (_, due_body) = reminder_message(&due_reminder(Some(
+            Utc.ymd(2022, 12, 31).and_hms(23, 30, 0),
+        )));
+
+        assert_eq!(title, "Reminder: pay rent");
+        assert_eq!(
+            body,
+            "You asked to be reminded of \"pay rent\" on 2022-12-20 09:00 CET."
+        );
+        assert_eq!(due_body, "\"pay rent\" is due on 2023-01-01 00:30 CET.");
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_an_hour() {
+        let settings = ReminderSettings::default();
+
+        let delays: Vec<i64> = [1, 2, 3, 10]
+            .into_iter()
+            .map(|attempts| retry_delay(attempts, &settings).num_seconds())
+            .collect();
+
+        assert_eq!(delays, vec![60, 120, 240, 3600]);
+    }
+}
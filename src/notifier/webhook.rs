@@ -0,0 +1,287 @@
+use axum::async_trait;
+use hmac::{Hmac, Mac};
+use hyper::{
+    client::{
+        connect::dns::{GaiResolver, Name},
+        HttpConnector,
+    },
+    header::CONTENT_TYPE,
+    service::Service,
+    Body, Client, Method, Request, Uri,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use serde::Serialize;
+use sha2::Sha256;
+use std::{
+    fmt,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use super::{reminder_message, Notifier, NotifierError};
+use crate::domain::reminder::DueReminder;
+
+/// Receivers dedupe deliveries of the same reminder on this header
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// HMAC-SHA256 of the body with the webhook secret, as `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    reminder_id: Uuid,
+    task_id: Uuid,
+    title: &'a str,
+    body: &'a str,
+    remind_at: chrono::DateTime<chrono::Utc>,
+    due_at: Option<chrono::DateTime<chrono::Utc>>,
+    timezone: &'a str,
+}
+
+/// Posts reminders as JSON to the webhook url of the reminder, any answer
+/// but a 2xx is a failed delivery.
+///
+/// Webhooks only reach public https urls, the host is resolved when the
+/// reminder is delivered and the connection goes to the checked addresses.
+/// Redirects are not followed.
+pub struct WebhookNotifier {
+    client: Client<HttpsConnector<HttpConnector<PublicResolver>>>,
+    secret: Option<String>,
+    timeout: Duration,
+    allow_private: bool,
+}
+
+impl fmt::Debug for WebhookNotifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The secret stays out of the logs
+        f.debug_struct("WebhookNotifier")
+            .field("timeout", &self.timeout)
+            .field("allow_private", &self.allow_private)
+            .finish()
+    }
+}
+
+impl WebhookNotifier {
+    /// `allow_private` lets webhooks use http and private addresses, for local
+    /// development only
+    pub fn new(secret: Option<String>, timeout: Duration, allow_private: bool) -> Self {
+        let mut http = HttpConnector::new_with_resolver(PublicResolver {
+            inner: GaiResolver::new(),
+            allow_private,
+        });
+        http.enforce_http(false);
+
+        let connector = HttpsConnectorBuilder::new().with_webpki_roots();
+        let connector = if allow_private {
+            connector.https_or_http()
+        } else {
+            connector.https_only()
+        };
+
+        Self {
+            client: Client::builder().build(connector.enable_http1().wrap_connector(http)),
+            secret,
+            timeout,
+            allow_private,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, reminder: &DueReminder) -> Result<(), NotifierError> {
+        let url = reminder
+            .webhook_url
+            .as_deref()
+            .ok_or(NotifierError::MissingWebhookUrl)?;
+        let url = check_webhook_url(url, self.allow_private)?;
+
+        let (title, body) = reminder_message(reminder);
+        let payload = serde_json::to_vec(&WebhookPayload {
+            reminder_id: reminder.id,
+            task_id: reminder.task_id,
+            title: &title,
+            body: &body,
+            remind_at: reminder.remind_at,
+            due_at: reminder.due_at,
+            timezone: &reminder.timezone,
+        })
+        .expect("serializing the payload is infallible");
+
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(IDEMPOTENCY_KEY_HEADER, reminder.id.to_string());
+        if let Some(secret) = &self.secret {
+            req = req.header(SIGNATURE_HEADER, signature(secret, &payload));
+        }
+        let req = req.body(Body::from(payload))?;
+
+        let res = tokio::time::timeout(self.timeout, self.client.request(req))
+            .await
+            .map_err(|_| NotifierError::Timeout)??;
+        if !res.status().is_success() {
+            return Err(NotifierError::Status(res.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Value of the signature header of `payload`
+pub fn signature(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(payload);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Parses a webhook url, which must be https and must not name a local or
+/// private host, unless `allow_private`.
+///
+/// Host names are checked again once resolved, by the resolver of the client.
+pub fn check_webhook_url(url: &str, allow_private: bool) -> Result<Uri, NotifierError> {
+    let uri: Uri = url.parse().map_err(|_| NotifierError::ForbiddenUrl)?;
+    let host = uri.host().ok_or(NotifierError::ForbiddenUrl)?;
+    if allow_private {
+        return Ok(uri);
+    }
+
+    if uri.scheme_str() != Some("https") {
+        return Err(NotifierError::ForbiddenUrl);
+    }
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    if host == "localhost" || host.ends_with(".localhost") {
+        return Err(NotifierError::ForbiddenUrl);
+    }
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        if !is_public_ip(ip) {
+            return Err(NotifierError::ForbiddenUrl);
+        }
+    }
+
+    Ok(uri)
+}
+
+/// Whether an address is reachable on the internet, loopback, private,
+/// link-local, unique local, shared and metadata addresses are not
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        // Including the 169.254.169.254 metadata service of cloud providers
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // This network, 0.0.0.0/8
+        || a == 0
+        // Shared address space, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved, 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, fc00::/7, such as the fd00:ec2::254 metadata service
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // Documentation, 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// Resolves host names to their public addresses only, so that a webhook host
+/// cannot point to the internal network, even by changing its records after
+/// the reminder was created
+#[derive(Clone)]
+pub struct PublicResolver {
+    inner: GaiResolver,
+    allow_private: bool,
+}
+
+impl Service<Name> for PublicResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.inner.call(name);
+        let allow_private = self.allow_private;
+
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = resolving.await?.collect();
+            if !allow_private && addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "webhook host resolves to a private address",
+                ));
+            }
+
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_public_https_urls_are_webhooks() {
+        let forbidden = [
+            "http://example.com/hook",
+            "https://localhost/hook",
+            "https://api.localhost/hook",
+            "https://127.0.0.1/hook",
+            "https://10.0.0.1/hook",
+            "https://172.16.4.2/hook",
+            "https://192.168.1.1/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.100.100.200/hook",
+            "https://0.0.0.0/hook",
+            "https://[::1]/hook",
+            "https://[fd00:ec2::254]/hook",
+            "https://[fe80::1]/hook",
+            "https://[::ffff:127.0.0.1]/hook",
+            "not a url",
+        ];
+
+        for url in forbidden {
+            assert!(check_webhook_url(url, false).is_err(), "{}", url);
+        }
+        assert!(check_webhook_url("https://example.com/hook", false).is_ok());
+        assert!(check_webhook_url("https://93.184.216.34/hook", false).is_ok());
+        assert!(check_webhook_url("http://127.0.0.1:8080/hook", true).is_ok());
+    }
+}
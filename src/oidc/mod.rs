@@ -0,0 +1,473 @@
+//! Logging users in with an identity provider, through the OpenID Connect
+//! authorization code flow with PKCE
+use hyper::{
+    body::Bytes,
+    client::HttpConnector,
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    Body, Client, Method, Request, StatusCode,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use thiserror::Error;
+
+use crate::{
+    configuration::OidcProviderSettings,
+    utils::jwt::{Jwk, Jwks},
+};
+
+/// Time given to the identity provider to answer a request
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Clock drift tolerated with the identity provider on the `exp` claim
+const ID_TOKEN_LEEWAY_SECONDS: u64 = 60;
+/// Bytes escaped by the `application/x-www-form-urlencoded` serializer, which
+/// client credentials are encoded with before being sent as basic auth
+const FORM_URLENCODED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'*')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_');
+
+#[derive(Error, Debug)]
+pub enum OidcError {
+    #[error("invalid identity provider url: {0}")]
+    InvalidUrl(#[from] hyper::http::Error),
+    #[error("could not reach the identity provider: {0}")]
+    Http(#[from] hyper::Error),
+    #[error("identity provider did not answer in time")]
+    Timeout,
+    #[error("identity provider answered {status} to {url}")]
+    Status { url: String, status: StatusCode },
+    #[error("invalid identity provider response: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+    #[error("identity provider metadata is for issuer {0}")]
+    IssuerMismatch(String),
+    #[error("authorization code rejected by the identity provider")]
+    InvalidGrant,
+    #[error("invalid id token: {0}")]
+    InvalidIdToken(#[from] jsonwebtoken::errors::Error),
+    #[error("id token signed by an unknown key")]
+    UnknownKey,
+    #[error("id token nonce does not match")]
+    NonceMismatch,
+}
+
+impl OidcError {
+    /// Whether the identity provider could be reached, but the login it
+    /// returned is not acceptable
+    pub fn is_rejected_login(&self) -> bool {
+        matches!(
+            self,
+            OidcError::InvalidGrant
+                | OidcError::InvalidIdToken(_)
+                | OidcError::UnknownKey
+                | OidcError::NonceMismatch
+        )
+    }
+}
+
+/// Subset of the provider metadata (OpenID Connect Discovery) the flow needs
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Claims of an ID token, once its signature, issuer, audience, expiration and
+/// nonce were checked
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    /// A boolean, though some providers send it as a string
+    pub email_verified: Option<Value>,
+    pub preferred_username: Option<String>,
+    pub nonce: Option<String>,
+}
+
+impl IdTokenClaims {
+    pub fn is_email_verified(&self) -> bool {
+        match &self.email_verified {
+            Some(Value::Bool(verified)) => *verified,
+            Some(Value::String(verified)) => verified == "true",
+            _ => false,
+        }
+    }
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>>;
+
+/// Identity provider of the settings, its metadata and keys are fetched on
+/// first use and cached
+pub struct OidcProvider {
+    settings: OidcProviderSettings,
+    client: HttpClient,
+    metadata: RwLock<Option<Arc<ProviderMetadata>>>,
+    jwks: RwLock<Arc<Jwks>>,
+}
+
+impl OidcProvider {
+    fn new(settings: OidcProviderSettings, client: HttpClient) -> Self {
+        Self {
+            settings,
+            client,
+            metadata: RwLock::default(),
+            jwks: RwLock::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.settings.name
+    }
+
+    /// Url to send the user to, the provider redirects them back to the
+    /// `redirect_uri` of the settings along with a code and `state`
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+
+        let scope = self.settings.scopes.join(" ");
+        let code_challenge = pkce_challenge(code_verifier);
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", &self.settings.client_id),
+            ("redirect_uri", &self.settings.redirect_uri),
+            ("scope", &scope),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", &code_challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .expect("encoding strings is infallible");
+
+        let separator = if metadata.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+
+        Ok(format!(
+            "{}{}{}",
+            metadata.authorization_endpoint, separator, query
+        ))
+    }
+
+    /// Exchanges an authorization code for an ID token and validates it
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.settings.redirect_uri),
+            ("code_verifier", code_verifier),
+        ];
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri(&metadata.token_endpoint)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(ACCEPT, "application/json");
+        // Confidential clients authenticate with client_secret_basic, the
+        // method every provider supports
+        match &self.settings.client_secret {
+            Some(secret) => {
+                let credentials = format!(
+                    "{}:{}",
+                    utf8_percent_encode(&self.settings.client_id, FORM_URLENCODED),
+                    utf8_percent_encode(secret, FORM_URLENCODED)
+                );
+                req = req.header(
+                    AUTHORIZATION,
+                    format!("Basic {}", base64::encode(credentials)),
+                );
+            }
+            None => form.push(("client_id", &self.settings.client_id)),
+        }
+        let body = serde_urlencoded::to_string(form).expect("encoding strings is infallible");
+
+        let (status, body) = self.send(req.body(Body::from(body))?).await?;
+        if status.is_client_error() {
+            return Err(OidcError::InvalidGrant);
+        }
+        if !status.is_success() {
+            return Err(OidcError::Status {
+                url: metadata.token_endpoint.clone(),
+                status,
+            });
+        }
+        let token_response: TokenResponse = serde_json::from_slice(&body)?;
+
+        self.validate_id_token(&token_response.id_token, nonce, &metadata)
+            .await
+    }
+
+    async fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+        metadata: &ProviderMetadata,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+        let (algorithm, key) = self.decoding_key(header.kid.as_deref(), metadata).await?;
+
+        // The algorithm comes from the key, never from the token
+        if header.alg != algorithm {
+            return Err(jsonwebtoken::errors::Error::from(ErrorKind::InvalidAlgorithm).into());
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.settings.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        validation.leeway = ID_TOKEN_LEEWAY_SECONDS;
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+
+        // The nonce ties the token to the login started by this client
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::NonceMismatch);
+        }
+
+        Ok(claims)
+    }
+
+    /// Key verifying tokens signed by `kid`, the keys are fetched again when
+    /// it is unknown as the provider may have rotated them
+    async fn decoding_key(
+        &self,
+        kid: Option<&str>,
+        metadata: &ProviderMetadata,
+    ) -> Result<(Algorithm, DecodingKey), OidcError> {
+        let cached = self.jwks.read().expect("jwks lock poisoned").clone();
+        if let Some(key) = find_jwk(&cached, kid) {
+            return decoding_key(key).ok_or(OidcError::UnknownKey);
+        }
+
+        let jwks: Arc<Jwks> = Arc::new(self.get_json(&metadata.jwks_uri).await?);
+        *self.jwks.write().expect("jwks lock poisoned") = jwks.clone();
+
+        find_jwk(&jwks, kid)
+            .and_then(decoding_key)
+            .ok_or(OidcError::UnknownKey)
+    }
+
+    async fn metadata(&self) -> Result<Arc<ProviderMetadata>, OidcError> {
+        if let Some(metadata) = self
+            .metadata
+            .read()
+            .expect("metadata lock poisoned")
+            .clone()
+        {
+            return Ok(metadata);
+        }
+
+        let issuer = self.settings.issuer.trim_end_matches('/');
+        let metadata: ProviderMetadata = self
+            .get_json(&format!("{}/.well-known/openid-configuration", issuer))
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(OidcError::IssuerMismatch(metadata.issuer));
+        }
+
+        let metadata = Arc::new(metadata);
+        *self.metadata.write().expect("metadata lock poisoned") = Some(metadata.clone());
+
+        Ok(metadata)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, OidcError> {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(url)
+            .header(ACCEPT, "application/json")
+            .body(Body::empty())?;
+
+        let (status, body) = self.send(req).await?;
+        if !status.is_success() {
+            return Err(OidcError::Status {
+                url: url.into(),
+                status,
+            });
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn send(&self, req: Request<Body>) -> Result<(StatusCode, Bytes), OidcError> {
+        let exchange = async {
+            let res = self.client.request(req).await?;
+            let status = res.status();
+            let body = hyper::body::to_bytes(res.into_body()).await?;
+
+            Ok::<_, hyper::Error>((status, body))
+        };
+
+        Ok(tokio::time::timeout(HTTP_TIMEOUT, exchange)
+            .await
+            .map_err(|_| OidcError::Timeout)??)
+    }
+}
+
+/// Signing key named `kid`, or the only signing key when the token names none
+fn find_jwk<'a>(jwks: &'a Jwks, kid: Option<&str>) -> Option<&'a Jwk> {
+    let mut keys = jwks.keys.iter().filter(|key| key.use_ != "enc");
+
+    match kid {
+        Some(kid) => keys.find(|key| key.kid == kid),
+        None => match (keys.next(), keys.next()) {
+            (Some(key), None) => Some(key),
+            _ => None,
+        },
+    }
+}
+
+/// Only RSA and Ed25519 keys are supported, like for our own tokens
+fn decoding_key(jwk: &Jwk) -> Option<(Algorithm, DecodingKey)> {
+    match (jwk.kty.as_str(), jwk.crv.as_deref()) {
+        ("RSA", _) => {
+            let key = DecodingKey::from_rsa_components(jwk.n.as_ref()?, jwk.e.as_ref()?).ok()?;
+
+            Some((Algorithm::RS256, key))
+        }
+        ("OKP", Some("Ed25519")) => {
+            let x = base64::decode_config(jwk.x.as_ref()?, base64::URL_SAFE_NO_PAD).ok()?;
+
+            Some((Algorithm::EdDSA, DecodingKey::from_ed_der(&x)))
+        }
+        _ => None,
+    }
+}
+
+/// PKCE code challenge of `code_verifier` with the S256 method (RFC 7636)
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+/// Identity providers of the settings, by name
+pub struct OidcProviders {
+    providers: Vec<OidcProvider>,
+}
+
+impl fmt::Debug for OidcProviders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Settings hold client secrets
+        f.debug_list()
+            .entries(self.providers.iter().map(OidcProvider::name))
+            .finish()
+    }
+}
+
+impl OidcProviders {
+    pub fn from_settings(settings: &[OidcProviderSettings]) -> Self {
+        // Plain http is accepted for providers running next to the api
+        let client = Client::builder().build(
+            HttpsConnectorBuilder::new()
+                .with_webpki_roots()
+                .https_or_http()
+                .enable_http1()
+                .build(),
+        );
+
+        Self {
+            providers: settings
+                .iter()
+                .map(|settings| OidcProvider::new(settings.clone(), client.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn jwk(kid: &str, use_: &str) -> Jwk {
+        Jwk {
+            kty: "OKP".into(),
+            kid: kid.into(),
+            use_: use_.into(),
+            alg: "EdDSA".into(),
+            n: None,
+            e: None,
+            crv: Some("Ed25519".into()),
+            x: Some(base64::encode_config([0u8; 32], base64::URL_SAFE_NO_PAD)),
+        }
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_example() {
+        // Appendix B of RFC 7636
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn find_jwk_by_kid_or_single_signing_key() {
+        let single = Jwks {
+            keys: vec![jwk("sig", "sig"), jwk("enc", "enc")],
+        };
+        let several = Jwks {
+            keys: vec![jwk("first", "sig"), jwk("second", "")],
+        };
+
+        assert_eq!(find_jwk(&single, None).map(|key| &*key.kid), Some("sig"));
+        assert!(find_jwk(&single, Some("enc")).is_none());
+        assert!(find_jwk(&several, None).is_none());
+        assert_eq!(
+            find_jwk(&several, Some("second")).map(|key| &*key.kid),
+            Some("second")
+        );
+        assert!(matches!(
+            decoding_key(&single.keys[0]),
+            Some((Algorithm::EdDSA, _))
+        ));
+    }
+
+    #[test]
+    fn email_verified_as_boolean_or_string() {
+        let claims = |email_verified: Value| -> IdTokenClaims {
+            serde_json::from_value(json!({ "sub": "subject", "email_verified": email_verified }))
+                .unwrap()
+        };
+
+        assert!(claims(json!(true)).is_email_verified());
+        assert!(claims(json!("true")).is_email_verified());
+        assert!(!claims(json!(false)).is_email_verified());
+        assert!(!claims(Value::Null).is_email_verified());
+    }
+}
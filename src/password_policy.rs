@@ -0,0 +1,311 @@
+//! Rules new passwords have to follow, checked on registration, password
+//! change and password reset
+use sha1::{Digest, Sha1};
+use std::{io, path::PathBuf};
+use validator::{ValidationError, ValidationErrors};
+
+use crate::{configuration::PasswordPolicySettings, utils::validation::field_error};
+
+/// Parts of the username or email shorter than this are not looked for in
+/// passwords, they would reject too many of them
+const MIN_USER_INPUT_LENGTH: usize = 5;
+
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    settings: PasswordPolicySettings,
+    breached_passwords_dir: Option<PathBuf>,
+}
+
+impl PasswordPolicy {
+    pub fn from_settings(settings: &PasswordPolicySettings) -> Self {
+        Self {
+            settings: settings.clone(),
+            breached_passwords_dir: settings.breached_passwords_dir.as_ref().map(PathBuf::from),
+        }
+    }
+
+    /// Adds the rule `password` breaks, if any, to the validation result of
+    /// the input it comes from, under `field`.
+    ///
+    /// `user_inputs` are the username and email of the user, which the
+    /// password must not contain.
+    pub async fn validate(
+        &self,
+        validation: Result<(), ValidationErrors>,
+        field: &'static str,
+        password: &str,
+        user_inputs: &[&str],
+    ) -> Result<(), ValidationErrors> {
+        let mut errors = validation.err().unwrap_or_default();
+
+        if let Err(err) = self.check(password, user_inputs).await {
+            errors.add(field, err);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    async fn check(&self, password: &str, user_inputs: &[&str]) -> Result<(), ValidationError> {
+        let settings = &self.settings;
+        let length = password.chars().count();
+
+        if length < settings.min_length {
+            return Err(field_error(
+                "password_too_short",
+                format!("must be at least {} characters", settings.min_length),
+            ));
+        }
+        if length > settings.max_length {
+            return Err(field_error(
+                "password_too_long",
+                format!("must be at most {} characters", settings.max_length),
+            ));
+        }
+
+        let classes = [
+            (
+                settings.require_lowercase,
+                char::is_lowercase as fn(char) -> bool,
+                "a lowercase letter",
+            ),
+            (
+                settings.require_uppercase,
+                char::is_uppercase,
+                "an uppercase letter",
+            ),
+            (settings.require_digit, |c| c.is_ascii_digit(), "a digit"),
+            (settings.require_symbol, is_symbol, "a symbol"),
+        ];
+        for (required, is_class, name) in classes {
+            if required && !password.chars().any(is_class) {
+                return Err(field_error(
+                    "password_missing_character_class",
+                    format!("must contain {}", name),
+                ));
+            }
+        }
+
+        if settings.reject_user_info && contains_user_input(password, user_inputs) {
+            return Err(field_error(
+                "password_contains_user_info",
+                "must not contain your username or email",
+            ));
+        }
+
+        if estimate_strength(password) < settings.min_strength {
+            return Err(field_error("password_too_weak", "is too easy to guess"));
+        }
+
+        if self.is_breached(password).await {
+            return Err(field_error(
+                "password_breached",
+                "appeared in a data breach, choose another one",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Looks the SHA-1 of the password up in the file of its 5 characters
+    /// prefix, laid out like the responses of the HaveIBeenPwned range api
+    /// (`SUFFIX:COUNT` lines), so that only one small file is read per check
+    async fn is_breached(&self, password: &str) -> bool {
+        let dir = match &self.breached_passwords_dir {
+            Some(dir) => dir,
+            None => return false,
+        };
+
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let path = dir.join(prefix);
+        let range = match tokio::fs::read_to_string(&path).await {
+            Ok(range) => range,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return false,
+            // Registrations are not blocked because the list is unreadable
+            Err(err) => {
+                tracing::error!(%err, path = %path.display(), "could not read breached passwords");
+                return false;
+            }
+        };
+
+        range.lines().any(|line| match line.trim().split_once(':') {
+            // Padding entries have a count of 0
+            Some((line_suffix, count)) => {
+                line_suffix.eq_ignore_ascii_case(suffix) && count.trim() != "0"
+            }
+            None => line.trim().eq_ignore_ascii_case(suffix),
+        })
+    }
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace()
+}
+
+/// Whether the password contains one of the inputs, or the local part of an
+/// email, ignoring case
+fn contains_user_input(password: &str, user_inputs: &[&str]) -> bool {
+    let password = password.to_lowercase();
+
+    user_inputs
+        .iter()
+        .flat_map(|input| {
+            let local_part = input.split_once('@').map(|(local_part, _)| local_part);
+            std::iter::once(*input).chain(local_part)
+        })
+        .map(str::to_lowercase)
+        .filter(|input| input.chars().count() >= MIN_USER_INPUT_LENGTH)
+        .any(|input| password.contains(&input))
+}
+
+/// Rough guessability score, from 0 (too guessable) to 4 (very unguessable).
+///
+/// Estimates the entropy from the character classes used, characters
+/// repeating the previous one or following it in a sequence (`aaa`, `abc`,
+/// `321`) only counting for a quarter.
+pub fn estimate_strength(password: &str) -> u8 {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii() && !c.is_ascii_alphanumeric())
+    {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0;
+    }
+
+    let mut length = 0.0;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        length += match previous {
+            Some(previous) if (c as u32).abs_diff(previous as u32) <= 1 => 0.25,
+            _ => 1.0,
+        };
+        previous = Some(c);
+    }
+
+    let bits = length * f64::from(pool).log2();
+    match bits {
+        bits if bits < 28.0 => 0,
+        bits if bits < 36.0 => 1,
+        bits if bits < 60.0 => 2,
+        bits if bits < 80.0 => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy(settings: PasswordPolicySettings) -> PasswordPolicy {
+        PasswordPolicy::from_settings(&settings)
+    }
+
+    async fn error_code(policy: &PasswordPolicy, password: &str) -> Option<String> {
+        policy
+            .check(password, &["test_username", "someone@email.com"])
+            .await
+            .err()
+            .map(|err| err.code.into_owned())
+    }
+
+    #[test]
+    fn strength_of_common_shapes() {
+        assert_eq!(estimate_strength(""), 0);
+        assert_eq!(estimate_strength("aaaaaaaaaaaa"), 0);
+        assert_eq!(estimate_strength("12345678"), 0);
+        assert_eq!(estimate_strength("password"), 1);
+        assert!(estimate_strength("test_password") >= 3);
+        assert_eq!(estimate_strength("T4k3-th3 l0ng way h0me!"), 4);
+    }
+
+    #[tokio::test]
+    async fn policy_rules_are_checked_in_order() {
+        let policy = policy(PasswordPolicySettings {
+            require_uppercase: true,
+            require_digit: true,
+            ..PasswordPolicySettings::default()
+        });
+
+        assert_eq!(
+            error_code(&policy, "short").await.as_deref(),
+            Some("password_too_short")
+        );
+        assert_eq!(
+            error_code(&policy, "no uppercase 1").await.as_deref(),
+            Some("password_missing_character_class")
+        );
+        assert_eq!(
+            error_code(&policy, "No digit here").await.as_deref(),
+            Some("password_missing_character_class")
+        );
+        assert_eq!(
+            error_code(&policy, "My Test_Username 1").await.as_deref(),
+            Some("password_contains_user_info")
+        );
+        assert_eq!(
+            error_code(&policy, "1SOMEONE!").await.as_deref(),
+            Some("password_contains_user_info")
+        );
+        assert_eq!(
+            error_code(&policy, "Aaaaaaaaaa1").await.as_deref(),
+            Some("password_too_weak")
+        );
+        assert_eq!(error_code(&policy, "Correct Horse 42").await, None);
+    }
+
+    #[tokio::test]
+    async fn breached_passwords_are_looked_up_by_prefix() {
+        let policy = policy(PasswordPolicySettings {
+            min_strength: 0,
+            breached_passwords_dir: Some("config/breached_passwords".into()),
+            ..PasswordPolicySettings::default()
+        });
+
+        assert_eq!(
+            error_code(&policy, "password").await.as_deref(),
+            Some("password_breached")
+        );
+        // Listed with a count of 0, as padding
+        assert_eq!(error_code(&policy, "padding password").await, None);
+        assert_eq!(error_code(&policy, "not breached password").await, None);
+    }
+
+    #[tokio::test]
+    async fn validate_adds_policy_error_to_field() {
+        let policy = policy(PasswordPolicySettings::default());
+
+        let valid = policy
+            .validate(Ok(()), "password", "Correct Horse 42", &[])
+            .await;
+        let invalid = policy
+            .validate(Ok(()), "new_password", "short", &[])
+            .await
+            .unwrap_err();
+
+        assert!(valid.is_ok());
+        assert_eq!(
+            invalid.field_errors()["new_password"][0].message.as_deref(),
+            Some("must be at least 8 characters")
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use axum::async_trait;
+use chrono::{Duration, Utc};
+use std::{collections::HashMap, sync::Mutex};
+
+use super::{RateLimitError, RateLimitStore};
+use crate::domain::rate_limit::RateLimitCounter;
+
+/// Past this many counters, expired ones are pruned on the next increment
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Keeps counters in the memory of the instance
+#[derive(Debug, Default)]
+pub struct InMemoryRateLimitStore {
+    counters: Mutex<HashMap<String, RateLimitCounter>>,
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn increment(
+        &self,
+        key: &str,
+        window: Duration,
+    ) -> Result<RateLimitCounter, RateLimitError> {
+        let now = Utc::now();
+        let mut counters = self.counters.lock().unwrap();
+
+        if counters.len() > PRUNE_THRESHOLD {
+            counters.retain(|_, counter| counter.expires_at > now);
+        }
+
+        let counter = counters
+            .entry(key.into())
+            .and_modify(|counter| {
+                if counter.expires_at <= now {
+                    counter.count = 0;
+                    counter.expires_at = now + window;
+                }
+                counter.count += 1;
+                counter.updated_at = now;
+            })
+            .or_insert_with(|| RateLimitCounter {
+                key: key.into(),
+                count: 1,
+                expires_at: now + window,
+                updated_at: now,
+            });
+
+        Ok(counter.clone())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<RateLimitCounter>, RateLimitError> {
+        let counters = self.counters.lock().unwrap();
+
+        Ok(counters
+            .get(key)
+            .filter(|counter| counter.expires_at > Utc::now())
+            .cloned())
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), RateLimitError> {
+        self.counters.lock().unwrap().remove(key);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn increment_counts_hits_in_window() {
+        let store = InMemoryRateLimitStore::default();
+
+        store.increment("key", Duration::minutes(1)).await.unwrap();
+        let counter = store.increment("key", Duration::minutes(1)).await.unwrap();
+
+        assert_eq!(counter.count, 2);
+        assert_eq!(store.get("key").await.unwrap(), Some(counter));
+    }
+
+    #[tokio::test]
+    async fn increment_restarts_expired_window() {
+        let store = InMemoryRateLimitStore::default();
+
+        store.increment("key", Duration::zero()).await.unwrap();
+        let expired = store.get("key").await.unwrap();
+        let counter = store.increment("key", Duration::minutes(1)).await.unwrap();
+
+        assert!(expired.is_none());
+        assert_eq!(counter.count, 1);
+    }
+
+    #[tokio::test]
+    async fn reset_forgets_counter() {
+        let store = InMemoryRateLimitStore::default();
+
+        store.increment("key", Duration::minutes(1)).await.unwrap();
+        store.reset("key").await.unwrap();
+
+        assert!(store.get("key").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,283 @@
+mod memory;
+mod postgres;
+
+pub use memory::*;
+pub use postgres::*;
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::ConnectInfo,
+    http::{header::CONTENT_LENGTH, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use http_body::{LengthLimitError, Limited};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{fmt::Debug, net::SocketAddr, sync::Arc};
+use thiserror::Error;
+
+use crate::{
+    configuration::{LockoutSettings, RateLimitBackend, RateLimitBucket},
+    domain::rate_limit::RateLimitCounter,
+    handler::ApiError,
+    router::State,
+};
+
+/// Largest body buffered to find the account, authentication requests are small
+pub const MAX_BODY_BYTES: usize = 16 * 1024;
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+/// Storage of the rate limiting counters
+#[async_trait]
+pub trait RateLimitStore: Debug + Send + Sync {
+    /// Counts a hit of `key`, the count restarts once `window` is over
+    async fn increment(
+        &self,
+        key: &str,
+        window: Duration,
+    ) -> Result<RateLimitCounter, RateLimitError>;
+
+    /// Current counter of `key`, if its window is not over
+    async fn get(&self, key: &str) -> Result<Option<RateLimitCounter>, RateLimitError>;
+
+    async fn reset(&self, key: &str) -> Result<(), RateLimitError>;
+}
+
+pub fn build_store(backend: RateLimitBackend, db_pool: &PgPool) -> Arc<dyn RateLimitStore> {
+    match backend {
+        RateLimitBackend::Memory => Arc::new(InMemoryRateLimitStore::default()),
+        RateLimitBackend::Postgres => Arc::new(PostgresRateLimitStore::new(db_pool.clone())),
+    }
+}
+
+/// Counts a hit of `key` in `bucket`, returns how long to wait when the bucket is exhausted
+async fn hit(
+    store: &dyn RateLimitStore,
+    key: &str,
+    bucket: &RateLimitBucket,
+) -> Result<Option<Duration>, RateLimitError> {
+    let counter = store
+        .increment(key, Duration::seconds(bucket.window_seconds))
+        .await?;
+
+    if counter.count > bucket.max_requests {
+        return Ok(Some(counter.expires_at - Utc::now()));
+    }
+
+    Ok(None)
+}
+
+/// Account targeted by an authentication request, read from its json body
+fn account_from_body(body: &[u8]) -> Option<String> {
+    let value: Value = serde_json::from_slice(body).ok()?;
+
+    ["login", "username", "email"]
+        .iter()
+        .find_map(|field| value.get(field)?.as_str())
+        .map(normalize_account)
+}
+
+/// Same counter whatever the case or the surrounding spaces of the account
+fn normalize_account(account: &str) -> String {
+    account.trim().to_lowercase()
+}
+
+/// Middleware limiting requests per client ip and per targeted account.
+///
+/// Counters that cannot be read let the request through rather than locking
+/// everybody out.
+pub async fn rate_limit(req: Request<Body>, next: Next<Body>) -> Result<Response, ApiError> {
+    let state = req
+        .extensions()
+        .get::<Arc<State>>()
+        .cloned()
+        .expect("state extension is missing");
+    let store = state.rate_limiter.as_ref();
+    let settings = &state.rate_limit;
+
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    if let Some(ip) = ip {
+        match hit(store, &format!("ip:{}", ip), &settings.per_ip).await {
+            Ok(Some(retry_after)) => return Err(ApiError::too_many_requests(retry_after)),
+            Ok(None) => {}
+            Err(err) => tracing::error!(%err, "could not rate limit ip"),
+        }
+    }
+
+    let content_length = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > MAX_BODY_BYTES) {
+        return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+    }
+
+    // Buffering the body to find the account, then handing it over to the handler
+    let (parts, body) = req.into_parts();
+    let bytes = match hyper::body::to_bytes(Limited::new(body, MAX_BODY_BYTES)).await {
+        Ok(bytes) => bytes,
+        Err(err) if err.is::<LengthLimitError>() => {
+            return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response())
+        }
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    if let Some(account) = account_from_body(&bytes) {
+        match hit(
+            store,
+            &format!("account:{}", account),
+            &settings.per_account,
+        )
+        .await
+        {
+            Ok(Some(retry_after)) => return Err(ApiError::too_many_requests(retry_after)),
+            Ok(None) => {}
+            Err(err) => tracing::error!(%err, "could not rate limit account"),
+        }
+    }
+
+    Ok(next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await)
+}
+
+fn lockout_key(account: &str) -> String {
+    format!("lockout:{}", normalize_account(account))
+}
+
+/// How long an account stays locked after `failures` consecutive failed logins
+fn lock_duration(settings: &LockoutSettings, failures: i32) -> Option<Duration> {
+    if failures < settings.threshold {
+        return None;
+    }
+
+    let doublings = (failures - settings.threshold).min(30) as u32;
+    let seconds = settings
+        .base_seconds
+        .saturating_mul(1 << doublings)
+        .min(settings.max_seconds);
+
+    Some(Duration::seconds(seconds))
+}
+
+/// Remaining lock time of `account`, if it is locked
+pub async fn account_lock_remaining(
+    store: &dyn RateLimitStore,
+    settings: &LockoutSettings,
+    account: &str,
+) -> Result<Option<Duration>, RateLimitError> {
+    let counter = match store.get(&lockout_key(account)).await? {
+        Some(counter) => counter,
+        None => return Ok(None),
+    };
+
+    let remaining = lock_duration(settings, counter.count)
+        .map(|duration| counter.updated_at + duration - Utc::now())
+        .filter(|remaining| *remaining > Duration::zero());
+
+    Ok(remaining)
+}
+
+pub async fn record_failed_login(
+    store: &dyn RateLimitStore,
+    settings: &LockoutSettings,
+    account: &str,
+) -> Result<(), RateLimitError> {
+    store
+        .increment(
+            &lockout_key(account),
+            Duration::seconds(settings.reset_after_seconds),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn clear_failed_logins(
+    store: &dyn RateLimitStore,
+    account: &str,
+) -> Result<(), RateLimitError> {
+    store.reset(&lockout_key(account)).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lockout_settings() -> LockoutSettings {
+        LockoutSettings {
+            threshold: 3,
+            base_seconds: 10,
+            max_seconds: 60,
+            reset_after_seconds: 3600,
+        }
+    }
+
+    #[test]
+    fn lock_duration_doubles_up_to_max() {
+        let settings = lockout_settings();
+
+        assert_eq!(lock_duration(&settings, 2), None);
+        assert_eq!(lock_duration(&settings, 3), Some(Duration::seconds(10)));
+        assert_eq!(lock_duration(&settings, 4), Some(Duration::seconds(20)));
+        assert_eq!(lock_duration(&settings, 5), Some(Duration::seconds(40)));
+        assert_eq!(lock_duration(&settings, 6), Some(Duration::seconds(60)));
+        assert_eq!(lock_duration(&settings, 100), Some(Duration::seconds(60)));
+    }
+
+    #[test]
+    fn account_from_body_reads_identifier() {
+        assert_eq!(
+            account_from_body(br#"{"login": " User ", "password": "pass"}"#),
+            Some("user".into())
+        );
+        assert_eq!(
+            account_from_body(br#"{"email": "user@email.com"}"#),
+            Some("user@email.com".into())
+        );
+        assert_eq!(account_from_body(b"not json"), None);
+    }
+
+    #[tokio::test]
+    async fn account_is_locked_after_threshold() {
+        let store = InMemoryRateLimitStore::default();
+        let settings = lockout_settings();
+
+        for _ in 0..2 {
+            record_failed_login(&store, &settings, "user")
+                .await
+                .unwrap();
+        }
+        let before_threshold = account_lock_remaining(&store, &settings, "user")
+            .await
+            .unwrap();
+
+        record_failed_login(&store, &settings, "USER")
+            .await
+            .unwrap();
+        let locked = account_lock_remaining(&store, &settings, "user")
+            .await
+            .unwrap();
+
+        clear_failed_logins(&store, "user").await.unwrap();
+        let cleared = account_lock_remaining(&store, &settings, "user")
+            .await
+            .unwrap();
+
+        assert!(before_threshold.is_none());
+        assert!(locked.is_some());
+        assert!(cleared.is_none());
+    }
+}
@@ -0,0 +1,42 @@
+use axum::async_trait;
+use chrono::Duration;
+use sqlx::PgPool;
+
+use super::{RateLimitError, RateLimitStore};
+use crate::{
+    db::rate_limit::{
+        delete_rate_limit_counter, find_rate_limit_counter, increment_rate_limit_counter,
+    },
+    domain::rate_limit::RateLimitCounter,
+};
+
+/// Keeps counters in Postgres so that they are shared between instances
+#[derive(Debug)]
+pub struct PostgresRateLimitStore {
+    db_pool: PgPool,
+}
+
+impl PostgresRateLimitStore {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresRateLimitStore {
+    async fn increment(
+        &self,
+        key: &str,
+        window: Duration,
+    ) -> Result<RateLimitCounter, RateLimitError> {
+        Ok(increment_rate_limit_counter(key, window, &self.db_pool).await?)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<RateLimitCounter>, RateLimitError> {
+        Ok(find_rate_limit_counter(key, &self.db_pool).await?)
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), RateLimitError> {
+        Ok(delete_rate_limit_counter(key, &self.db_pool).await?)
+    }
+}
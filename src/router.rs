@@ -1,8 +1,40 @@
-use crate::handler::{login_handler, register_handler, status_handler};
+use crate::{
+    configuration::{
+        AppSettings, EmailVerificationPolicy, RateLimitSettings, SessionCookieSettings,
+        SessionTransport,
+    },
+    extractor::{AdminUser, AuthUser, VerifiedUser},
+    handler::{
+        confirm_two_factor_handler, create_api_token_handler, create_list_handler,
+        create_reminder_handler, create_tag_handler, create_task_handler, delete_list_handler,
+        delete_me_handler, delete_recurrence_handler, delete_reminder_handler, delete_tag_handler,
+        delete_task_handler, delete_user_handler, disable_two_factor_handler, disable_user_handler,
+        enable_user_handler, export_me_handler, force_password_reset_handler,
+        forgot_password_handler, get_list_handler, get_recurrence_handler, get_tag_handler,
+        get_task_handler, get_user_handler, jwks_handler, link_identity_authorize_handler,
+        link_identity_callback_handler, list_api_tokens_handler, list_identities_handler,
+        list_lists_handler, list_notifications_handler, list_occurrences_handler,
+        list_reminders_handler, list_tags_handler, list_tasks_handler, list_tasks_of_list_handler,
+        list_users_handler, login_handler, login_two_factor_handler, logout_all_handler,
+        logout_handler, me_handler, move_task_handler, oidc_authorize_handler,
+        oidc_callback_handler, read_notification_handler, refresh_token_handler, register_handler,
+        reorder_task_handler, resend_verification_email_handler, reset_password_handler,
+        revoke_api_token_handler, set_recurrence_handler, set_task_parent_handler,
+        setup_two_factor_handler, status_handler, unlink_identity_handler, update_list_handler,
+        update_me_handler, update_tag_handler, update_task_handler, verify_email_handler,
+    },
+    mailer::Mailer,
+    oidc::OidcProviders,
+    password_policy::PasswordPolicy,
+    rate_limit::{build_store, rate_limit, RateLimitStore},
+    utils::{cipher::SecretCipher, hasher::Hasher, jwt::JwtKeys},
+};
 use axum::{
-    routing::{get, post},
+    middleware::{from_extractor, from_fn},
+    routing::{delete, get, post, put},
     Extension, Router,
 };
+use chrono::Duration;
 use sqlx::PgPool;
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
@@ -10,24 +42,217 @@ use tower_http::trace::TraceLayer;
 #[derive(Debug)]
 pub struct State {
     pub db_pool: PgPool,
-    pub jwt_secret: String,
+    pub jwt_keys: JwtKeys,
+    pub hasher: Hasher,
+    pub password_policy: PasswordPolicy,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+    pub password_reset_ttl: Duration,
+    pub public_url: String,
+    pub email_verification: EmailVerificationPolicy,
+    pub email_verification_ttl: Duration,
+    pub email_verification_resend_cooldown: Duration,
+    pub mailer: Arc<dyn Mailer>,
+    pub rate_limit: RateLimitSettings,
+    pub rate_limiter: Arc<dyn RateLimitStore>,
+    pub totp_issuer: String,
+    pub totp_cipher: SecretCipher,
+    pub two_factor_challenge_ttl: Duration,
+    pub account_deletion_grace_period: Duration,
+    pub oidc: OidcProviders,
+    pub oidc_state_ttl: Duration,
+    pub session_transport: SessionTransport,
+    pub session_cookie: SessionCookieSettings,
+    /// Lets reminder webhooks use http and private addresses
+    pub allow_private_webhooks: bool,
 }
 
-pub fn setup_router(db_pool: PgPool, jwt_secret: String) -> Router {
+pub fn setup_router(
+    db_pool: PgPool,
+    settings: AppSettings,
+    mailer: Arc<dyn Mailer>,
+    jwt_keys: JwtKeys,
+    hasher: Hasher,
+    totp_cipher: SecretCipher,
+) -> Router {
+    let rate_limiter = build_store(settings.rate_limit.backend, &db_pool);
     let state = Arc::new(State {
         db_pool,
-        jwt_secret,
+        jwt_keys,
+        hasher,
+        password_policy: PasswordPolicy::from_settings(&settings.password_policy),
+        access_token_ttl: Duration::minutes(settings.access_token_ttl_minutes),
+        refresh_token_ttl: Duration::days(settings.refresh_token_ttl_days),
+        password_reset_ttl: Duration::minutes(settings.password_reset_ttl_minutes),
+        public_url: settings.public_url,
+        email_verification: settings.email_verification,
+        email_verification_ttl: Duration::hours(settings.email_verification_ttl_hours),
+        email_verification_resend_cooldown: Duration::seconds(
+            settings.email_verification_resend_cooldown_seconds,
+        ),
+        mailer,
+        rate_limit: settings.rate_limit,
+        rate_limiter,
+        totp_issuer: settings.totp_issuer,
+        totp_cipher,
+        two_factor_challenge_ttl: Duration::minutes(settings.two_factor_challenge_ttl_minutes),
+        account_deletion_grace_period: Duration::days(settings.account_deletion_grace_period_days),
+        oidc: OidcProviders::from_settings(&settings.oidc_providers),
+        oidc_state_ttl: Duration::minutes(settings.oidc_state_ttl_minutes),
+        session_transport: settings.session_transport,
+        session_cookie: settings.session_cookie,
+        allow_private_webhooks: settings.reminders.allow_private_webhooks,
     });
 
     let user_routes = Router::new()
-        .route("/register", post(register_handler))
-        .route("/login", get(login_handler));
+        .route("/token/refresh", post(refresh_token_handler))
+        .route("/password/reset", post(reset_password_handler))
+        .route("/verify-email", post(verify_email_handler))
+        .merge(rate_limited(
+            Router::new()
+                .route("/register", post(register_handler))
+                .route("/login", post(login_handler))
+                .route("/login/2fa", post(login_two_factor_handler))
+                .route("/oidc/:provider/authorize", post(oidc_authorize_handler))
+                .route("/oidc/:provider/callback", post(oidc_callback_handler))
+                .route("/password/forgot", post(forgot_password_handler))
+                .route(
+                    "/verify-email/resend",
+                    post(resend_verification_email_handler),
+                ),
+        ))
+        .merge(authenticated(
+            Router::new()
+                .route(
+                    "/me",
+                    get(me_handler)
+                        .patch(update_me_handler)
+                        .delete(delete_me_handler),
+                )
+                .route("/me/export", get(export_me_handler))
+                .route("/logout", post(logout_handler))
+                .route("/logout/all", post(logout_all_handler))
+                .route(
+                    "/me/tokens",
+                    get(list_api_tokens_handler).post(create_api_token_handler),
+                )
+                .route("/me/tokens/:id", delete(revoke_api_token_handler))
+                .route("/me/2fa", delete(disable_two_factor_handler))
+                .route("/me/2fa/setup", post(setup_two_factor_handler))
+                .route("/me/2fa/confirm", post(confirm_two_factor_handler))
+                .route("/me/identities", get(list_identities_handler))
+                .route("/me/identities/:id", delete(unlink_identity_handler))
+                .route("/me/notifications", get(list_notifications_handler))
+                .route(
+                    "/me/notifications/:id/read",
+                    post(read_notification_handler),
+                )
+                .route(
+                    "/oidc/:provider/link/authorize",
+                    post(link_identity_authorize_handler),
+                )
+                .route(
+                    "/oidc/:provider/link/callback",
+                    post(link_identity_callback_handler),
+                ),
+        ));
+
+    let task_routes = Router::new()
+        .route("/", get(list_tasks_handler).post(create_task_handler))
+        .route(
+            "/:id",
+            get(get_task_handler)
+                .patch(update_task_handler)
+                .delete(delete_task_handler),
+        )
+        .route("/:id/list", put(move_task_handler))
+        .route("/:id/move", post(reorder_task_handler))
+        .route("/:id/parent", put(set_task_parent_handler))
+        .route(
+            "/:id/reminders",
+            get(list_reminders_handler).post(create_reminder_handler),
+        )
+        .route(
+            "/:id/reminders/:reminder_id",
+            delete(delete_reminder_handler),
+        )
+        .route(
+            "/:id/recurrence",
+            get(get_recurrence_handler)
+                .put(set_recurrence_handler)
+                .delete(delete_recurrence_handler),
+        )
+        .route("/:id/recurrence/occurrences", get(list_occurrences_handler));
+
+    let list_routes = Router::new()
+        .route("/", get(list_lists_handler).post(create_list_handler))
+        .route(
+            "/:id",
+            get(get_list_handler)
+                .patch(update_list_handler)
+                .delete(delete_list_handler),
+        )
+        .route("/:id/tasks", get(list_tasks_of_list_handler));
+
+    let tag_routes = Router::new()
+        .route("/", get(list_tags_handler).post(create_tag_handler))
+        .route(
+            "/:id",
+            get(get_tag_handler)
+                .patch(update_tag_handler)
+                .delete(delete_tag_handler),
+        );
 
-    let api_routes = Router::new().nest("/users", user_routes);
+    let admin_routes = Router::new()
+        .route("/users", get(list_users_handler))
+        .route(
+            "/users/:id",
+            get(get_user_handler).delete(delete_user_handler),
+        )
+        .route("/users/:id/disable", post(disable_user_handler))
+        .route("/users/:id/enable", post(enable_user_handler))
+        .route(
+            "/users/:id/password-reset",
+            post(force_password_reset_handler),
+        );
+
+    let api_routes = Router::new()
+        .nest("/users", user_routes)
+        .nest("/admin", admin(admin_routes))
+        .nest("/tasks", verified(task_routes))
+        .nest("/lists", verified(list_routes))
+        .nest("/tags", verified(tag_routes));
 
     Router::new()
         .route("/status", get(status_handler))
+        .route("/.well-known/jwks.json", get(jwks_handler))
         .nest("/api", api_routes)
         .layer(Extension(state))
         .layer(TraceLayer::new_for_http())
 }
+
+/// Rejects every request to `router` that does not carry a valid bearer token,
+/// or an api token lacking the scope required by the request.
+///
+/// Handlers of the returned router can still take [`AuthUser`] as an argument
+/// to get the authenticated user.
+pub fn authenticated(router: Router) -> Router {
+    router.route_layer(from_extractor::<AuthUser>())
+}
+
+/// Like [`authenticated`], additionally rejecting users whose email is not
+/// verified when the email verification policy restricts them.
+pub fn verified(router: Router) -> Router {
+    router.route_layer(from_extractor::<VerifiedUser>())
+}
+
+/// Like [`authenticated`], additionally rejecting users who are not admins.
+pub fn admin(router: Router) -> Router {
+    router.route_layer(from_extractor::<AdminUser>())
+}
+
+/// Limits requests to `router` per client ip and per targeted account, see
+/// [`rate_limit`].
+pub fn rate_limited(router: Router) -> Router {
+    router.route_layer(from_fn(rate_limit))
+}
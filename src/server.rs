@@ -1,11 +1,82 @@
-use std::net::TcpListener;
+use std::{
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
 
 use axum::Router;
 use hyper::Error;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use crate::{
+    configuration::ReminderSettings,
+    db::{
+        oidc::delete_expired_oidc_states, rate_limit::delete_expired_rate_limit_counters,
+        revoked_token::delete_expired_revoked_tokens, task::rebalance_task_positions,
+        user::delete_users_scheduled_for_deletion,
+    },
+    notifier::{dispatch_due_reminders, Notifiers},
+    utils::rank::MAX_RANK_LENGTH,
+};
 
 pub async fn make_server(listener: TcpListener, router: Router) -> Result<(), Error> {
     axum::Server::from_tcp(listener)?
-        .serve(router.into_make_service())
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
         .await?;
     Ok(())
 }
+
+/// Periodically purges revocations of access tokens that have expired since,
+/// along with the rate limiting counters whose window is over and the states of
+/// abandoned OpenID Connect logins, and deletes the accounts whose deletion
+/// grace period is over
+pub fn spawn_cleanup_task(db_pool: PgPool, period: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            match delete_expired_revoked_tokens(&db_pool).await {
+                Ok(count) => tracing::debug!(count, "purged expired revoked tokens"),
+                Err(err) => tracing::error!(%err, "could not purge expired revoked tokens"),
+            }
+            match delete_expired_rate_limit_counters(&db_pool).await {
+                Ok(count) => tracing::debug!(count, "purged expired rate limit counters"),
+                Err(err) => tracing::error!(%err, "could not purge expired rate limit counters"),
+            }
+            match delete_expired_oidc_states(&db_pool).await {
+                Ok(count) => tracing::debug!(count, "purged expired oidc states"),
+                Err(err) => tracing::error!(%err, "could not purge expired oidc states"),
+            }
+            match delete_users_scheduled_for_deletion(&db_pool).await {
+                Ok(count) => tracing::debug!(count, "deleted accounts scheduled for deletion"),
+                Err(err) => {
+                    tracing::error!(%err, "could not delete accounts scheduled for deletion")
+                }
+            }
+            match rebalance_task_positions(MAX_RANK_LENGTH, &db_pool).await {
+                Ok(count) => tracing::debug!(count, "rebalanced task positions"),
+                Err(err) => tracing::error!(%err, "could not rebalance task positions"),
+            }
+        }
+    })
+}
+
+/// Periodically delivers the reminders that are due, several workers can run
+/// side by side as each reminder is claimed by one of them
+pub fn spawn_reminder_worker(
+    db_pool: PgPool,
+    notifiers: Notifiers,
+    settings: ReminderSettings,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(settings.poll_interval_seconds));
+        loop {
+            interval.tick().await;
+            match dispatch_due_reminders(&db_pool, &notifiers, &settings).await {
+                Ok(count) => tracing::debug!(count, "delivered due reminders"),
+                Err(err) => tracing::error!(%err, "could not deliver due reminders"),
+            }
+        }
+    })
+}
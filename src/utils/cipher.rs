@@ -0,0 +1,117 @@
+//! Encryption of the secrets the server has to read back, such as TOTP
+//! secrets, which cannot be hashed
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use rand_core::{OsRng, RngCore};
+use std::fmt;
+use thiserror::Error;
+
+/// Prefix of the values sealed by [`SecretCipher`], plaintext secrets stored
+/// before encryption do not have it
+pub const SEALED_PREFIX: &str = "v1.";
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum CipherError {
+    #[error("encryption key must be 32 bytes encoded in base64")]
+    InvalidKey,
+    #[error("sealed secret is malformed or was not sealed with this key")]
+    InvalidSealedSecret,
+}
+
+/// Seals secrets with AES-256-GCM, bound to the record they belong to so that
+/// a sealed secret copied to another record does not open
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl fmt::Debug for SecretCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The key stays out of the logs
+        f.debug_struct("SecretCipher").finish_non_exhaustive()
+    }
+}
+
+impl SecretCipher {
+    /// `key` is 32 bytes encoded in base64, such as the output of
+    /// `openssl rand -base64 32`
+    pub fn from_key(key: &str) -> Result<Self, CipherError> {
+        let key = base64::decode(key.trim()).map_err(|_| CipherError::InvalidKey)?;
+        let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| CipherError::InvalidKey)?;
+
+        Ok(Self { cipher })
+    }
+
+    /// Seals `secret` for the record identified by `context`
+    pub fn seal(&self, secret: &str, context: &[u8]) -> String {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+
+        let payload = Payload {
+            msg: secret.as_bytes(),
+            aad: context,
+        };
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            self.cipher
+                .encrypt(Nonce::from_slice(&nonce), payload)
+                .expect("encrypting a secret is infallible"),
+        );
+
+        format!("{}{}", SEALED_PREFIX, base64::encode(sealed))
+    }
+
+    /// Opens a secret sealed for the record identified by `context`
+    pub fn open(&self, sealed: &str, context: &[u8]) -> Result<String, CipherError> {
+        let sealed = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .and_then(|sealed| base64::decode(sealed).ok())
+            .filter(|sealed| sealed.len() > NONCE_LENGTH)
+            .ok_or(CipherError::InvalidSealedSecret)?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+
+        let payload = Payload {
+            msg: ciphertext,
+            aad: context,
+        };
+        let secret = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| CipherError::InvalidSealedSecret)?;
+
+        String::from_utf8(secret).map_err(|_| CipherError::InvalidSealedSecret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: &str = "qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqo=";
+
+    #[test]
+    fn sealed_secrets_open_for_their_record_only() {
+        let cipher = SecretCipher::from_key(KEY).unwrap();
+        let other_cipher =
+            SecretCipher::from_key("u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7s=").unwrap();
+
+        let sealed = cipher.seal("JBSWY3DPEHPK3PXP", b"record");
+
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("JBSWY3DPEHPK3PXP"));
+        assert_ne!(sealed, cipher.seal("JBSWY3DPEHPK3PXP", b"record"));
+        assert_eq!(cipher.open(&sealed, b"record").unwrap(), "JBSWY3DPEHPK3PXP");
+        assert!(cipher.open(&sealed, b"other_record").is_err());
+        assert!(other_cipher.open(&sealed, b"record").is_err());
+        assert!(cipher.open("JBSWY3DPEHPK3PXP", b"record").is_err());
+    }
+
+    #[test]
+    fn keys_must_be_32_bytes() {
+        assert!(SecretCipher::from_key("c2hvcnQ=").is_err());
+        assert!(SecretCipher::from_key("not base64").is_err());
+        assert!(SecretCipher::from_key(KEY).is_ok());
+    }
+}
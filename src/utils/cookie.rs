@@ -0,0 +1,75 @@
+use axum::http::HeaderMap;
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::Duration;
+
+use crate::configuration::{CookieSameSite, SessionCookieSettings};
+
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+/// Readable by scripts, its value must be sent back in [`CSRF_TOKEN_HEADER`]
+pub const CSRF_TOKEN_COOKIE: &str = "csrf_token";
+pub const CSRF_TOKEN_HEADER: &str = "x-csrf-token";
+
+/// Builds a session cookie, the refresh token is only sent to the refresh endpoint
+pub fn session_cookie(
+    settings: &SessionCookieSettings,
+    name: &'static str,
+    value: String,
+    max_age: Duration,
+) -> Cookie<'static> {
+    let path = match name {
+        REFRESH_TOKEN_COOKIE => "/api/users/token/refresh",
+        CSRF_TOKEN_COOKIE => "/",
+        _ => "/api",
+    };
+    let same_site = match settings.same_site {
+        CookieSameSite::Strict => SameSite::Strict,
+        CookieSameSite::Lax => SameSite::Lax,
+        CookieSameSite::None => SameSite::None,
+    };
+
+    let mut cookie = Cookie::build(name, value)
+        .path(path)
+        .http_only(name != CSRF_TOKEN_COOKIE)
+        .secure(settings.secure)
+        .same_site(same_site)
+        .max_age(time::Duration::seconds(max_age.num_seconds()))
+        .finish();
+    if let Some(domain) = &settings.domain {
+        cookie.set_domain(domain.clone());
+    }
+
+    cookie
+}
+
+/// Clears the session cookies, whether the request carried them or not
+pub fn remove_session_cookies(jar: CookieJar, settings: &SessionCookieSettings) -> CookieJar {
+    [ACCESS_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE, CSRF_TOKEN_COOKIE]
+        .into_iter()
+        .fold(jar, |jar, name| {
+            let mut cookie = session_cookie(settings, name, String::new(), Duration::zero());
+            cookie.make_removal();
+            jar.add(cookie)
+        })
+}
+
+/// Double-submit check: a cross-site request carries the cookies, but cannot
+/// read them to set the header
+pub fn csrf_token_matches(jar: &CookieJar, headers: &HeaderMap) -> bool {
+    let cookie = jar.get(CSRF_TOKEN_COOKIE).map(|cookie| cookie.value());
+    let header = headers
+        .get(CSRF_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    match (cookie, header) {
+        (Some(cookie), Some(header)) if !cookie.is_empty() && cookie.len() == header.len() => {
+            // Comparing in constant time
+            cookie
+                .bytes()
+                .zip(header.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+        }
+        _ => false,
+    }
+}
@@ -1,52 +1,351 @@
 use argon2::{
     password_hash::{
-        rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, Result, SaltString,
+        self, rand_core::OsRng, Error, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
     },
-    Argon2,
+    Algorithm, Argon2, Params, ParamsBuilder, Version,
 };
-pub fn hash_password(password: &[u8]) -> Result<String> {
-    let salt = SaltString::generate(&mut OsRng);
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{sync::Semaphore, task::JoinError};
 
-    // Argon2 with default params (Argon2id v19)
-    let argon2 = Argon2::default();
+use crate::configuration::PasswordHashingSettings;
 
-    // Hash password to PHC string ($argon2id$v=19$...)
+/// Key id set on the hashes made with the pepper, the hash string does not
+/// tell otherwise whether a secret was used
+const PEPPER_KEY_ID: &[u8] = b"pepper";
 
-    Ok(argon2.hash_password(password, &salt)?.to_string())
+#[derive(Error, Debug)]
+pub enum HasherError {
+    /// Every hashing slot stayed busy for the whole queue timeout
+    #[error("password hashing is saturated")]
+    Saturated,
+    #[error("could not hash password: {0}")]
+    Hash(Error),
+    #[error("password hashing task failed: {0}")]
+    Task(#[from] JoinError),
 }
 
-pub fn verify_password(password: &[u8], password_hash: &str) -> Result<bool> {
-    // Argon2 with default params (Argon2id v19)
-    let argon2 = Argon2::default();
+impl From<Error> for HasherError {
+    fn from(err: Error) -> Self {
+        HasherError::Hash(err)
+    }
+}
 
-    // Verify password against PHC string.
-    //
-    // NOTE: hash params from `parsed_hash` are used instead of what is configured in the
-    // `Argon2` instance.
-    let parsed_hash = PasswordHash::new(password_hash)?;
+/// Hashes passwords with Argon2id, using the costs and the pepper of the
+/// settings.
+///
+/// Hashing runs on the blocking thread pool, at most `max_concurrency` at a
+/// time, so that login bursts do not stall the async runtime.
+///
+/// Hashes made with other costs, or before the pepper was set, are still
+/// verified with what their PHC string records, see [`Hasher::needs_rehash`].
+pub struct Hasher {
+    argon2: Arc<Argon2Hasher>,
+    permits: Arc<Semaphore>,
+    queue_timeout: Duration,
+}
 
-    Ok(argon2.verify_password(password, &parsed_hash).is_ok())
+impl fmt::Debug for Hasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hasher")
+            .field("m_cost", &self.argon2.params.m_cost())
+            .field("t_cost", &self.argon2.params.t_cost())
+            .field("p_cost", &self.argon2.params.p_cost())
+            .field("pepper", &self.argon2.pepper.is_some())
+            .field("available_permits", &self.permits.available_permits())
+            .field("queue_timeout", &self.queue_timeout)
+            .finish()
+    }
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Self::from_settings(&PasswordHashingSettings::default())
+            .expect("default argon2 params are valid")
+    }
+}
+
+impl Hasher {
+    /// Fails when the costs are out of the bounds of Argon2, `max_concurrency`
+    /// is checked along with the rest of the configuration
+    pub fn from_settings(settings: &PasswordHashingSettings) -> password_hash::Result<Self> {
+        Ok(Self {
+            argon2: Arc::new(Argon2Hasher::from_settings(settings)?),
+            permits: Arc::new(Semaphore::new(settings.max_concurrency)),
+            queue_timeout: Duration::from_millis(settings.queue_timeout_ms),
+        })
+    }
+
+    /// Runs `f` on the blocking thread pool once a hashing slot is free
+    async fn run<T, F>(&self, f: F) -> Result<T, HasherError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Argon2Hasher) -> password_hash::Result<T> + Send + 'static,
+    {
+        let permit = tokio::time::timeout(self.queue_timeout, self.permits.clone().acquire_owned())
+            .await
+            .map_err(|_| HasherError::Saturated)?
+            .expect("hashing semaphore is never closed");
+
+        let argon2 = self.argon2.clone();
+        // The permit moves along, a request dropped meanwhile still holds its
+        // slot until the hash is done
+        let result = tokio::task::spawn_blocking(move || {
+            let result = f(&argon2);
+            drop(permit);
+            result
+        })
+        .await?;
+
+        Ok(result?)
+    }
+
+    pub async fn hash_password(&self, password: &[u8]) -> Result<String, HasherError> {
+        let password = password.to_vec();
+
+        self.run(move |argon2| argon2.hash_password(&password))
+            .await
+    }
+
+    pub async fn verify_password(
+        &self,
+        password: &[u8],
+        password_hash: &str,
+    ) -> Result<bool, HasherError> {
+        let password = password.to_vec();
+        let password_hash = password_hash.to_owned();
+
+        self.run(move |argon2| argon2.verify_password(&password, &password_hash))
+            .await
+    }
+
+    /// Runs a verification against a dummy hash, so that a login for an unknown
+    /// user takes as long as one with a wrong password.
+    pub async fn dummy_verify_password(&self, password: &[u8]) -> Result<(), HasherError> {
+        let password = password.to_vec();
+
+        self.run(move |argon2| {
+            let dummy_hash = argon2.dummy_hash()?;
+            let _ = argon2.verify_password(&password, dummy_hash);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Whether a hash was made with other costs than the configured ones, or
+    /// before the pepper was set.
+    ///
+    /// It can only be replaced when the password is known, once it was verified.
+    pub fn needs_rehash(&self, password_hash: &str) -> bool {
+        self.argon2.needs_rehash(password_hash)
+    }
+}
+
+/// Blocking side of [`Hasher`]
+struct Argon2Hasher {
+    params: Params,
+    pepper: Option<Vec<u8>>,
+    dummy_hash: OnceLock<String>,
+}
+
+impl Argon2Hasher {
+    fn from_settings(settings: &PasswordHashingSettings) -> password_hash::Result<Self> {
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(settings.memory_cost_kib)?
+            .t_cost(settings.time_cost)?
+            .p_cost(settings.parallelism)?;
+        if settings.pepper.is_some() {
+            builder.keyid(PEPPER_KEY_ID)?;
+        }
+        let params = builder.params()?;
+
+        let pepper = settings
+            .pepper
+            .as_ref()
+            .map(|pepper| pepper.as_bytes().to_vec());
+        // Checks the pepper length once, rather than on every hash
+        if let Some(pepper) = &pepper {
+            Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, params.clone())?;
+        }
+
+        Ok(Self {
+            params,
+            pepper,
+            dummy_hash: OnceLock::new(),
+        })
+    }
+
+    fn hash_password(&self, password: &[u8]) -> password_hash::Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2 = argon2(self.pepper.as_deref(), self.params.clone())?;
+
+        // Hash password to PHC string ($argon2id$v=19$...)
+        Ok(argon2.hash_password(password, &salt)?.to_string())
+    }
+
+    fn verify_password(&self, password: &[u8], password_hash: &str) -> password_hash::Result<bool> {
+        let parsed_hash = PasswordHash::new(password_hash)?;
+
+        // NOTE: hash params from `parsed_hash` are used instead of what is
+        // configured in the `Argon2` instance, only the pepper is ours to pick.
+        let pepper = if is_peppered(&parsed_hash) {
+            // The pepper was removed from the settings, these hashes cannot be
+            // verified anymore
+            Some(self.pepper.as_deref().ok_or(Error::Crypto)?)
+        } else {
+            None
+        };
+        let argon2 = argon2(pepper, Params::default())?;
+
+        Ok(argon2.verify_password(password, &parsed_hash).is_ok())
+    }
+
+    fn needs_rehash(&self, password_hash: &str) -> bool {
+        let parsed_hash = match PasswordHash::new(password_hash) {
+            Ok(parsed_hash) => parsed_hash,
+            Err(_) => return false,
+        };
+        let params = match Params::try_from(&parsed_hash) {
+            Ok(params) => params,
+            Err(_) => return false,
+        };
+
+        parsed_hash.algorithm != Algorithm::Argon2id.ident()
+            || parsed_hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.params.m_cost()
+            || params.t_cost() != self.params.t_cost()
+            || params.p_cost() != self.params.p_cost()
+            || is_peppered(&parsed_hash) != self.pepper.is_some()
+    }
+
+    fn dummy_hash(&self) -> password_hash::Result<&str> {
+        if let Some(dummy_hash) = self.dummy_hash.get() {
+            return Ok(dummy_hash);
+        }
+
+        let dummy_hash = self.hash_password(b"dummy_password")?;
+
+        Ok(self.dummy_hash.get_or_init(|| dummy_hash))
+    }
+}
+
+fn argon2(pepper: Option<&[u8]>, params: Params) -> password_hash::Result<Argon2<'_>> {
+    Ok(match pepper {
+        Some(pepper) => {
+            Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, params)?
+        }
+        None => Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+    })
+}
+
+fn is_peppered(password_hash: &PasswordHash) -> bool {
+    password_hash.params.get("keyid").is_some()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    #[test]
-    fn hash_and_verify_correct_password() {
+    fn settings(memory_cost_kib: u32, pepper: Option<&str>) -> PasswordHashingSettings {
+        PasswordHashingSettings {
+            memory_cost_kib,
+            pepper: pepper.map(Into::into),
+            ..PasswordHashingSettings::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn hash_and_verify_correct_password() {
+        let hasher = Hasher::default();
         let password = "it_should_work";
 
-        let hashed_password = hash_password(password.as_bytes()).unwrap();
+        let hashed_password = hasher.hash_password(password.as_bytes()).await.unwrap();
 
-        assert!(verify_password(password.as_bytes(), hashed_password.as_str()).unwrap())
+        assert!(hasher
+            .verify_password(password.as_bytes(), hashed_password.as_str())
+            .await
+            .unwrap())
     }
 
-    #[test]
-    fn hash_and_verify_wrong_password() {
+    #[tokio::test]
+    async fn hash_and_verify_wrong_password() {
+        let hasher = Hasher::default();
         let password = "it_should_not_work";
 
-        let hashed_password = hash_password(password.as_bytes()).unwrap();
+        let hashed_password = hasher.hash_password(password.as_bytes()).await.unwrap();
+
+        assert!(!hasher
+            .verify_password("wrong_password".as_bytes(), hashed_password.as_str())
+            .await
+            .unwrap())
+    }
+
+    #[tokio::test]
+    async fn hash_with_other_costs_needs_rehash() {
+        let weak = Hasher::from_settings(&settings(1024, None)).unwrap();
+        let hasher = Hasher::from_settings(&settings(2048, None)).unwrap();
+
+        let weak_hash = weak.hash_password(b"password").await.unwrap();
+        let hash = hasher.hash_password(b"password").await.unwrap();
+
+        assert!(hasher
+            .verify_password(b"password", &weak_hash)
+            .await
+            .unwrap());
+        assert!(hasher.needs_rehash(&weak_hash));
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[tokio::test]
+    async fn pepper_is_required_to_verify() {
+        let plain = Hasher::from_settings(&settings(1024, None)).unwrap();
+        let peppered = Hasher::from_settings(&settings(1024, Some("pepper"))).unwrap();
+        let other_pepper = Hasher::from_settings(&settings(1024, Some("other"))).unwrap();
+
+        let plain_hash = plain.hash_password(b"password").await.unwrap();
+        let peppered_hash = peppered.hash_password(b"password").await.unwrap();
+
+        // Hashes made before the pepper was set are still accepted
+        assert!(peppered
+            .verify_password(b"password", &plain_hash)
+            .await
+            .unwrap());
+        assert!(peppered.needs_rehash(&plain_hash));
+        assert!(peppered
+            .verify_password(b"password", &peppered_hash)
+            .await
+            .unwrap());
+        assert!(!peppered.needs_rehash(&peppered_hash));
+        assert!(!other_pepper
+            .verify_password(b"password", &peppered_hash)
+            .await
+            .unwrap());
+        assert!(plain
+            .verify_password(b"password", &peppered_hash)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn saturated_hasher_gives_up_after_queue_timeout() {
+        let hasher = Hasher::from_settings(&PasswordHashingSettings {
+            max_concurrency: 1,
+            queue_timeout_ms: 10,
+            ..settings(1024, None)
+        })
+        .unwrap();
+
+        let busy = hasher.permits.clone().acquire_owned().await.unwrap();
+        let saturated = hasher.hash_password(b"password").await;
+        drop(busy);
+        let freed = hasher.hash_password(b"password").await;
 
-        assert!(!verify_password("wrong_password".as_bytes(), hashed_password.as_str()).unwrap())
+        assert!(matches!(saturated, Err(HasherError::Saturated)));
+        assert!(freed.is_ok());
     }
 }
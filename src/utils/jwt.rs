@@ -0,0 +1,475 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{
+    errors::{ErrorKind, Result},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use simple_asn1::ASN1Block;
+use std::{fmt, fs};
+use thiserror::Error;
+
+use crate::{
+    configuration::{AppSettings, JwtAlgorithm, JwtKeySettings, JwtKeyStatus},
+    domain::user::Claims,
+};
+
+#[derive(Error, Debug)]
+pub enum JwtKeyError {
+    #[error("could not read key file {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid key {kid}: {source}")]
+    InvalidKey {
+        kid: String,
+        source: jsonwebtoken::errors::Error,
+    },
+    #[error("unsupported public key {kid}")]
+    UnsupportedPublicKey { kid: String },
+    #[error("no key to sign tokens, set a jwt_secret or an active key with a private key")]
+    NoSigningKey,
+}
+
+/// Public key in the JSON Web Key format (RFC 7517)
+///
+/// Only `kty` is required when reading keys published by other parties.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default)]
+    pub kid: String,
+    #[serde(rename = "use", default)]
+    pub use_: String,
+    #[serde(default)]
+    pub alg: String,
+    /// Modulus of RSA keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// Exponent of RSA keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    /// Curve of OKP keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    /// Public key of OKP keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+struct SigningKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerifyingKey {
+    kid: String,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Keys signing and verifying access tokens.
+///
+/// Tokens are signed by the first active key having a private key, falling back
+/// to the HS256 secret, and verified by the key named by their `kid` header.
+/// Tokens without `kid` are verified with the HS256 secret, if any, until
+/// `secret_verify_until` when the secret does not sign tokens anymore.
+pub struct JwtKeys {
+    signing: SigningKey,
+    verifying: Vec<VerifyingKey>,
+    secret: Option<DecodingKey>,
+    secret_verify_until: Option<DateTime<Utc>>,
+    jwks: Jwks,
+}
+
+impl fmt::Debug for JwtKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKeys")
+            .field("signing_kid", &self.signing.kid)
+            .field(
+                "verifying_kids",
+                &self
+                    .verifying
+                    .iter()
+                    .map(|key| &key.kid)
+                    .collect::<Vec<_>>(),
+            )
+            .field("secret", &self.secret.is_some())
+            .field("secret_verify_until", &self.secret_verify_until)
+            .finish()
+    }
+}
+
+impl JwtKeys {
+    /// Loads the keys of the settings, reading the PEM files
+    pub fn from_settings(settings: &AppSettings) -> std::result::Result<Self, JwtKeyError> {
+        let mut signing = None;
+        let mut verifying = Vec::new();
+        let mut jwks = Jwks::default();
+
+        for key_settings in settings
+            .jwt_keys
+            .iter()
+            .filter(|key| key.status == JwtKeyStatus::Active)
+        {
+            let algorithm = match key_settings.algorithm {
+                JwtAlgorithm::RS256 => Algorithm::RS256,
+                JwtAlgorithm::EdDSA => Algorithm::EdDSA,
+            };
+            let invalid_key = |source| JwtKeyError::InvalidKey {
+                kid: key_settings.kid.clone(),
+                source,
+            };
+
+            if let (None, Some(path)) = (&signing, &key_settings.private_key_path) {
+                let pem = read_key_file(path)?;
+                let key = match key_settings.algorithm {
+                    JwtAlgorithm::RS256 => EncodingKey::from_rsa_pem(&pem),
+                    JwtAlgorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+                }
+                .map_err(invalid_key)?;
+
+                signing = Some(SigningKey {
+                    kid: Some(key_settings.kid.clone()),
+                    algorithm,
+                    key,
+                });
+            }
+
+            let pem = read_key_file(&key_settings.public_key_path)?;
+            let key = match key_settings.algorithm {
+                JwtAlgorithm::RS256 => DecodingKey::from_rsa_pem(&pem),
+                JwtAlgorithm::EdDSA => DecodingKey::from_ed_pem(&pem),
+            }
+            .map_err(invalid_key)?;
+
+            jwks.keys
+                .push(public_jwk(key_settings, &pem).ok_or_else(|| {
+                    JwtKeyError::UnsupportedPublicKey {
+                        kid: key_settings.kid.clone(),
+                    }
+                })?);
+            verifying.push(VerifyingKey {
+                kid: key_settings.kid.clone(),
+                algorithm,
+                key,
+            });
+        }
+
+        let secret = settings.jwt_secret.as_deref();
+        let signing = signing
+            .or_else(|| {
+                secret.map(|secret| SigningKey {
+                    kid: None,
+                    algorithm: Algorithm::HS256,
+                    key: EncodingKey::from_secret(secret.as_bytes()),
+                })
+            })
+            .ok_or(JwtKeyError::NoSigningKey)?;
+
+        // Legacy HS256 tokens are only accepted during the switch to the keys
+        let secret_verify_until = settings.jwt_secret_verify_until;
+        let secret = match (&signing.kid, secret_verify_until) {
+            (Some(_), None) => None,
+            _ => secret.map(|secret| DecodingKey::from_secret(secret.as_bytes())),
+        };
+
+        Ok(Self {
+            secret_verify_until: signing.kid.as_ref().and(secret_verify_until),
+            signing,
+            verifying,
+            secret,
+            jwks,
+        })
+    }
+
+    /// Public keys verifying tokens, for other services to verify them
+    pub fn jwks(&self) -> &Jwks {
+        &self.jwks
+    }
+}
+
+fn read_key_file(path: &str) -> std::result::Result<Vec<u8>, JwtKeyError> {
+    fs::read(path).map_err(|source| JwtKeyError::Io {
+        path: path.into(),
+        source,
+    })
+}
+
+/// Builds the JWK of a public key in the PEM (SubjectPublicKeyInfo) format
+fn public_jwk(settings: &JwtKeySettings, pem: &[u8]) -> Option<Jwk> {
+    let pem = pem::parse(pem).ok()?;
+    let blocks = simple_asn1::from_der(&pem.contents).ok()?;
+
+    // SubjectPublicKeyInfo ::= SEQUENCE { algorithm, subjectPublicKey BIT STRING }
+    let public_key = match blocks.first()? {
+        ASN1Block::Sequence(_, items) => match items.get(1)? {
+            ASN1Block::BitString(_, _, bits) => bits,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let jwk = Jwk {
+        kty: String::new(),
+        kid: settings.kid.clone(),
+        use_: "sig".into(),
+        alg: String::new(),
+        n: None,
+        e: None,
+        crv: None,
+        x: None,
+    };
+
+    match settings.algorithm {
+        JwtAlgorithm::RS256 => {
+            // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+            let blocks = simple_asn1::from_der(public_key).ok()?;
+            let (n, e) = match blocks.first()? {
+                ASN1Block::Sequence(_, items) => match (items.first()?, items.get(1)?) {
+                    (ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)) => {
+                        (n.to_bytes_be().1, e.to_bytes_be().1)
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            };
+
+            Some(Jwk {
+                kty: "RSA".into(),
+                alg: "RS256".into(),
+                n: Some(base64::encode_config(n, base64::URL_SAFE_NO_PAD)),
+                e: Some(base64::encode_config(e, base64::URL_SAFE_NO_PAD)),
+                ..jwk
+            })
+        }
+        JwtAlgorithm::EdDSA => Some(Jwk {
+            kty: "OKP".into(),
+            alg: "EdDSA".into(),
+            crv: Some("Ed25519".into()),
+            x: Some(base64::encode_config(public_key, base64::URL_SAFE_NO_PAD)),
+            ..jwk
+        }),
+    }
+}
+
+pub fn encode_token(claims: &Claims, keys: &JwtKeys) -> Result<String> {
+    let mut header = Header::new(keys.signing.algorithm);
+    header.kid = keys.signing.kid.clone();
+
+    jsonwebtoken::encode(&header, claims, &keys.signing.key)
+}
+
+pub fn decode_token(token: &str, keys: &JwtKeys) -> Result<Claims> {
+    let header = jsonwebtoken::decode_header(token)?;
+
+    // The algorithm comes from the key, never from the token
+    let (algorithm, key) = match &header.kid {
+        Some(kid) => keys
+            .verifying
+            .iter()
+            .find(|key| &key.kid == kid)
+            .map(|key| (key.algorithm, &key.key)),
+        None => keys
+            .secret
+            .as_ref()
+            .filter(|_| {
+                keys.secret_verify_until
+                    .is_none_or(|verify_until| Utc::now() < verify_until)
+            })
+            .map(|key| (Algorithm::HS256, key)),
+    }
+    .ok_or(ErrorKind::InvalidToken)?;
+
+    // Validation checks the signature and the `exp` claim
+    let token_data = jsonwebtoken::decode::<Claims>(token, key, &Validation::new(algorithm))?;
+
+    Ok(token_data.claims)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::configuration::AppConfig;
+    use crate::domain::user::Role;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn claims(expires_in: Duration) -> Claims {
+        let now = Utc::now();
+
+        Claims {
+            sub: "subject".into(),
+            jti: Uuid::new_v4(),
+            sid: Uuid::new_v4(),
+            role: Role::User,
+            iat: now,
+            exp: now + expires_in,
+        }
+    }
+
+    /// Test settings, with the Ed25519 and RS256 keys of `config/keys`
+    fn settings() -> AppSettings {
+        AppConfig::build("TEST".into()).unwrap().app_settings
+    }
+
+    fn secret_settings(secret: &str) -> AppSettings {
+        let mut settings = settings();
+        settings.jwt_secret = Some(secret.into());
+        settings.jwt_keys.clear();
+
+        settings
+    }
+
+    #[test]
+    fn encode_and_decode_token() {
+        let keys = JwtKeys::from_settings(&settings()).unwrap();
+        let claims = claims(Duration::hours(1));
+
+        let token = encode_token(&claims, &keys).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        let decoded = decode_token(&token, &keys).unwrap();
+
+        assert_eq!(header.alg, Algorithm::EdDSA);
+        assert_eq!(header.kid.as_deref(), Some("test-ed25519"));
+        assert_eq!(decoded.sub, claims.sub);
+        assert_eq!(decoded.exp.timestamp(), claims.exp.timestamp());
+    }
+
+    #[test]
+    fn decode_token_with_wrong_secret() {
+        let keys = JwtKeys::from_settings(&secret_settings("secret")).unwrap();
+        let other_keys = JwtKeys::from_settings(&secret_settings("wrong_secret")).unwrap();
+
+        let token = encode_token(&claims(Duration::hours(1)), &keys).unwrap();
+
+        assert!(decode_token(&token, &other_keys).is_err());
+    }
+
+    #[test]
+    fn decode_expired_token() {
+        let keys = JwtKeys::from_settings(&settings()).unwrap();
+
+        let token = encode_token(&claims(Duration::hours(-1)), &keys).unwrap();
+
+        assert!(decode_token(&token, &keys).is_err());
+    }
+
+    #[test]
+    fn decode_token_signed_by_non_retired_keys_only() {
+        let keys = JwtKeys::from_settings(&settings()).unwrap();
+        let token = encode_token(&claims(Duration::hours(1)), &keys).unwrap();
+
+        // Rotating to the RS256 key, the Ed25519 key stays active until retired
+        let mut rotated_settings = settings();
+        rotated_settings.jwt_keys.swap(0, 1);
+        let rotated_keys = JwtKeys::from_settings(&rotated_settings).unwrap();
+        let rotated_token = encode_token(&claims(Duration::hours(1)), &rotated_keys).unwrap();
+
+        let mut retired_settings = rotated_settings.clone();
+        retired_settings.jwt_keys[1].status = JwtKeyStatus::Retired;
+        let retired_keys = JwtKeys::from_settings(&retired_settings).unwrap();
+
+        assert_eq!(
+            jsonwebtoken::decode_header(&rotated_token).unwrap().alg,
+            Algorithm::RS256
+        );
+        assert!(decode_token(&token, &rotated_keys).is_ok());
+        assert!(decode_token(&rotated_token, &keys).is_ok());
+        assert!(decode_token(&token, &retired_keys).is_err());
+        assert!(decode_token(&rotated_token, &retired_keys).is_ok());
+        assert_eq!(retired_keys.jwks().keys.len(), 1);
+    }
+
+    #[test]
+    fn decode_hs256_token_after_switching_to_keys() {
+        let secret_keys = JwtKeys::from_settings(&secret_settings("jwt-test-secret")).unwrap();
+
+        let token = encode_token(&claims(Duration::hours(1)), &secret_keys).unwrap();
+
+        let mut switching = settings();
+        switching.jwt_secret_verify_until = Some(Utc::now() + Duration::days(1));
+        let switching_keys = JwtKeys::from_settings(&switching).unwrap();
+
+        let mut switched = switching.clone();
+        switched.jwt_secret_verify_until = Some(Utc::now() - Duration::days(1));
+        let switched_keys = JwtKeys::from_settings(&switched).unwrap();
+
+        let mut without_secret = switching.clone();
+        without_secret.jwt_secret = None;
+        let keys_without_secret = JwtKeys::from_settings(&without_secret).unwrap();
+
+        let keys_without_cutoff = JwtKeys::from_settings(&settings()).unwrap();
+
+        assert!(decode_token(&token, &switching_keys).is_ok());
+        assert!(decode_token(&token, &switched_keys).is_err());
+        assert!(decode_token(&token, &keys_without_secret).is_err());
+        assert!(decode_token(&token, &keys_without_cutoff).is_err());
+    }
+
+    #[test]
+    fn decode_hs256_token_signed_by_the_secret() {
+        // The cutoff only applies once the keys sign tokens
+        let mut settings = secret_settings("jwt-test-secret");
+        settings.jwt_secret_verify_until = Some(Utc::now() - Duration::days(1));
+        let keys = JwtKeys::from_settings(&settings).unwrap();
+
+        let token = encode_token(&claims(Duration::hours(1)), &keys).unwrap();
+
+        assert!(decode_token(&token, &keys).is_ok());
+    }
+
+    #[test]
+    fn jwks_publishes_active_public_keys() {
+        let keys = JwtKeys::from_settings(&settings()).unwrap();
+        let jwks = keys.jwks();
+
+        let ed25519 = &jwks.keys[0];
+        let rsa = &jwks.keys[1];
+
+        // The published RSA key verifies the tokens it signed
+        let mut rsa_settings = settings();
+        rsa_settings.jwt_keys.remove(0);
+        let rsa_keys = JwtKeys::from_settings(&rsa_settings).unwrap();
+        let token = encode_token(&claims(Duration::hours(1)), &rsa_keys).unwrap();
+        let key =
+            DecodingKey::from_rsa_components(rsa.n.as_ref().unwrap(), rsa.e.as_ref().unwrap())
+                .unwrap();
+
+        assert_eq!(ed25519.kid, "test-ed25519");
+        assert_eq!(ed25519.kty, "OKP");
+        assert_eq!(
+            base64::decode_config(ed25519.x.as_ref().unwrap(), base64::URL_SAFE_NO_PAD)
+                .unwrap()
+                .len(),
+            32
+        );
+        assert_eq!(rsa.kid, "test-rs256");
+        assert_eq!(rsa.e.as_deref(), Some("AQAB"));
+        assert!(
+            jsonwebtoken::decode::<Claims>(&token, &key, &Validation::new(Algorithm::RS256))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn missing_signing_key() {
+        let mut settings = settings();
+        settings.jwt_secret = None;
+        settings
+            .jwt_keys
+            .iter_mut()
+            .for_each(|key| key.private_key_path = None);
+
+        assert!(matches!(
+            JwtKeys::from_settings(&settings),
+            Err(JwtKeyError::NoSigningKey)
+        ));
+    }
+}
@@ -1 +1,9 @@
+pub mod cipher;
+pub mod cookie;
 pub mod hasher;
+pub mod jwt;
+pub mod rank;
+pub mod rrule;
+pub mod token;
+pub mod totp;
+pub mod validation;
@@ -0,0 +1,202 @@
+//! Lexicographic ranks ordering tasks in their list.
+//!
+//! A rank can always be made between two others, so moving a task only
+//! updates its own row. Ranks are base 62 fractions compared byte by byte,
+//! without trailing zeros so that there is room before any of them.
+const DIGITS: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const MIDDLE_DIGIT: u8 = 31;
+
+/// Ranks longer than this are respread by the cleanup task
+pub const MAX_RANK_LENGTH: usize = 24;
+
+/// Rank strictly between `lower` and `upper`, the list starts or ends there
+/// when `None`.
+///
+/// Returns `None` when `lower` is not below `upper` or either is not a valid
+/// rank.
+pub fn between(lower: Option<&str>, upper: Option<&str>) -> Option<String> {
+    let lower = lower.map(digits).unwrap_or(Some(vec![]))?;
+    let upper = match upper {
+        Some(upper) => Some(digits(upper)?),
+        None => None,
+    };
+    if upper.as_ref().is_some_and(|upper| lower >= *upper) {
+        return None;
+    }
+
+    Some(encode(&midpoint(&lower, upper.as_deref())))
+}
+
+/// `count` ranks spread evenly, as short as possible while leaving room
+/// around each of them
+pub fn spread(count: usize) -> Vec<String> {
+    let slots = (count as u128 + 1) * 16;
+    let mut width = 1;
+    let mut space = 62u128;
+    while space < slots {
+        width += 1;
+        space *= 62;
+    }
+    let step = space / (count as u128 + 1);
+
+    (1..=count as u128)
+        .map(|i| {
+            let mut value = i * step;
+            let mut rank = vec![0; width];
+            for digit in rank.iter_mut().rev() {
+                *digit = (value % 62) as u8;
+                value /= 62;
+            }
+            while rank.last() == Some(&0) {
+                rank.pop();
+            }
+
+            encode(&rank)
+        })
+        .collect()
+}
+
+/// Digits between `lower` and `upper`, which is above `lower`
+fn midpoint(lower: &[u8], upper: Option<&[u8]>) -> Vec<u8> {
+    let Some(upper) = upper else {
+        return increment(lower);
+    };
+
+    // Common prefix, `lower` being padded with zeros
+    let common = upper
+        .iter()
+        .enumerate()
+        .take_while(|(i, digit)| lower.get(*i).copied().unwrap_or(0) == **digit)
+        .count();
+    if common > 0 {
+        let mut rank = upper[..common].to_vec();
+        rank.extend(midpoint(
+            lower.get(common..).unwrap_or_default(),
+            Some(&upper[common..]),
+        ));
+        return rank;
+    }
+
+    let digit_lower = lower.first().copied().unwrap_or(0);
+    let digit_upper = upper[0];
+    if digit_upper - digit_lower > 1 {
+        vec![(digit_lower + digit_upper).div_ceil(2)]
+    } else if upper.len() > 1 {
+        vec![digit_upper]
+    } else {
+        let mut rank = vec![digit_lower];
+        rank.extend(increment(lower.get(1..).unwrap_or_default()));
+        rank
+    }
+}
+
+/// Short rank above `lower`, rather than halfway to the end, so that ranks
+/// grow slowly as tasks are added at the end of a list
+fn increment(lower: &[u8]) -> Vec<u8> {
+    match lower.iter().position(|digit| *digit < 61) {
+        Some(i) => {
+            let mut rank = lower[..i].to_vec();
+            rank.push(lower[i] + 1);
+            rank
+        }
+        None => {
+            let mut rank = lower.to_vec();
+            rank.push(MIDDLE_DIGIT);
+            rank
+        }
+    }
+}
+
+fn digits(rank: &str) -> Option<Vec<u8>> {
+    let digits = rank
+        .bytes()
+        .map(|byte| {
+            DIGITS
+                .iter()
+                .position(|digit| *digit == byte)
+                .map(|i| i as u8)
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    (!digits.is_empty() && digits.last() != Some(&0)).then_some(digits)
+}
+
+fn encode(digits: &[u8]) -> String {
+    digits
+        .iter()
+        .map(|digit| DIGITS[*digit as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranks_fit_between_their_neighbours() {
+        let cases = [
+            (None, None, "V"),
+            (Some("V"), None, "W"),
+            (Some("z"), None, "zV"),
+            (Some("zz1"), None, "zz2"),
+            (None, Some("1"), "0V"),
+            (None, Some("01"), "00V"),
+            (Some("A"), Some("C"), "B"),
+            (Some("A"), Some("B"), "AV"),
+            (Some("A"), Some("B1"), "B"),
+            (Some("AV"), Some("AW"), "AVV"),
+            (Some("000000001V"), Some("000000002V"), "000000002"),
+        ];
+
+        for (lower, upper, expected) in cases {
+            assert_eq!(
+                between(lower, upper).as_deref(),
+                Some(expected),
+                "between {:?} and {:?}",
+                lower,
+                upper
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_bounds_have_no_rank() {
+        assert_eq!(between(Some("B"), Some("A")), None);
+        assert_eq!(between(Some("A"), Some("A")), None);
+        assert_eq!(between(Some("A0"), None), None);
+        assert_eq!(between(Some("A-"), None), None);
+        assert_eq!(between(Some(""), None), None);
+    }
+
+    #[test]
+    fn repeated_moves_keep_the_order() {
+        // Always moving a task right after the first one
+        let mut ranks = vec![between(None, None).unwrap()];
+        ranks.push(between(Some(&ranks[0]), None).unwrap());
+        for _ in 0..200 {
+            let rank = between(Some(&ranks[0]), Some(&ranks[1])).unwrap();
+            ranks.insert(1, rank);
+        }
+
+        let mut sorted = ranks.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        assert_eq!(sorted, ranks);
+        assert!(ranks.iter().all(|rank| rank.len() <= 40));
+    }
+
+    #[test]
+    fn spread_ranks_are_short_and_ordered() {
+        let ranks = spread(1000);
+
+        let mut sorted = ranks.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        assert_eq!(sorted, ranks);
+        assert!(ranks.iter().all(|rank| rank.len() <= 3));
+        assert!(ranks.iter().all(|rank| digits(rank).is_some()));
+        assert_eq!(spread(0), Vec::<String>::new());
+    }
+}
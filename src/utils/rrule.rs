@@ -0,0 +1,699 @@
+//! Recurrence rules (RFC 5545 RRULE), limited to the parts repeating tasks
+//! need: FREQ (daily to yearly), INTERVAL, BYDAY, BYMONTHDAY, BYMONTH, COUNT,
+//! UNTIL and WKST
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use std::{collections::VecDeque, fmt, str::FromStr};
+use thiserror::Error;
+
+/// Periods in a row without any occurrence after which a rule is deemed over,
+/// such as `FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30` which never occurs
+const MAX_EMPTY_PERIODS: u32 = 10_000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RRuleError {
+    #[error("invalid rule part `{0}`")]
+    InvalidPart(String),
+    #[error("unsupported rule part `{0}`")]
+    UnsupportedPart(String),
+    #[error("rule part `{0}` is given twice")]
+    DuplicatePart(String),
+    #[error("rule has no FREQ")]
+    MissingFrequency,
+    #[error("rule cannot have both COUNT and UNTIL")]
+    CountAndUntil,
+    #[error("BYDAY with an ordinal needs a MONTHLY frequency, or YEARLY with BYMONTH")]
+    OrdinalWeekday,
+    #[error("BYMONTHDAY cannot be used with a WEEKLY frequency")]
+    WeeklyMonthDay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Day of the week, the `n`th one of the month when `ordinal` is set, counted
+/// from the end when negative
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekdayNum {
+    pub ordinal: Option<i8>,
+    pub weekday: Weekday,
+}
+
+/// End of a rule, inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Until {
+    /// Last day of the series, in its time zone
+    Date(NaiveDate),
+    /// Last local time of the series, in its time zone
+    Local(NaiveDateTime),
+    Utc(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<WeekdayNum>,
+    pub by_month_day: Vec<i8>,
+    pub by_month: Vec<u32>,
+    pub count: Option<u32>,
+    pub until: Option<Until>,
+    pub week_start: Weekday,
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = RRuleError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut seen = Vec::new();
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut by_month_day = Vec::new();
+        let mut by_month = Vec::new();
+        let mut count = None;
+        let mut until = None;
+        let mut week_start = Weekday::Mon;
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let invalid = || RRuleError::InvalidPart(part.into());
+            let (name, value) = part.split_once('=').ok_or_else(invalid)?;
+            let name = name.to_ascii_uppercase();
+            let value = value.to_ascii_uppercase();
+
+            if seen.contains(&name) {
+                return Err(RRuleError::DuplicatePart(name));
+            }
+            seen.push(name.clone());
+
+            match name.as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        "SECONDLY" | "MINUTELY" | "HOURLY" => {
+                            return Err(RRuleError::UnsupportedPart(part.into()))
+                        }
+                        _ => return Err(invalid()),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(invalid)?
+                }
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|count| *count > 0)
+                            .ok_or_else(invalid)?,
+                    )
+                }
+                "UNTIL" => until = Some(parse_until(&value).ok_or_else(invalid)?),
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(parse_weekday_num)
+                        .collect::<Option<_>>()
+                        .ok_or_else(invalid)?
+                }
+                "BYMONTHDAY" => {
+                    by_month_day = value
+                        .split(',')
+                        .map(|day| {
+                            day.parse()
+                                .ok()
+                                .filter(|day: &i8| matches!(day.abs(), 1..=31))
+                        })
+                        .collect::<Option<_>>()
+                        .ok_or_else(invalid)?
+                }
+                "BYMONTH" => {
+                    by_month = value
+                        .split(',')
+                        .map(|month| month.parse().ok().filter(|month| matches!(month, 1..=12)))
+                        .collect::<Option<_>>()
+                        .ok_or_else(invalid)?
+                }
+                "WKST" => week_start = parse_weekday(&value).ok_or_else(invalid)?,
+                "BYSETPOS" | "BYYEARDAY" | "BYWEEKNO" | "BYHOUR" | "BYMINUTE" | "BYSECOND" => {
+                    return Err(RRuleError::UnsupportedPart(part.into()))
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let frequency = frequency.ok_or(RRuleError::MissingFrequency)?;
+        if count.is_some() && until.is_some() {
+            return Err(RRuleError::CountAndUntil);
+        }
+        let ordinal_allowed = frequency == Frequency::Monthly
+            || (frequency == Frequency::Yearly && !by_month.is_empty());
+        if !ordinal_allowed && by_day.iter().any(|day| day.ordinal.is_some()) {
+            return Err(RRuleError::OrdinalWeekday);
+        }
+        if frequency == Frequency::Weekly && !by_month_day.is_empty() {
+            return Err(RRuleError::WeeklyMonthDay);
+        }
+
+        Ok(Self {
+            frequency,
+            interval,
+            by_day,
+            by_month_day,
+            by_month,
+            count,
+            until,
+            week_start,
+        })
+    }
+}
+
+impl fmt::Display for RecurrenceRule {
+    /// Canonical form of the rule, which is how it is stored
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_month.is_empty() {
+            write!(f, ";BYMONTH={}", join(&self.by_month))?;
+        }
+        if !self.by_month_day.is_empty() {
+            write!(f, ";BYMONTHDAY={}", join(&self.by_month_day))?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|day| match day.ordinal {
+                    Some(ordinal) => format!("{}{}", ordinal, weekday_code(day.weekday)),
+                    None => weekday_code(day.weekday).into(),
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if self.week_start != Weekday::Mon {
+            write!(f, ";WKST={}", weekday_code(self.week_start))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        match self.until {
+            Some(Until::Date(date)) => write!(f, ";UNTIL={}", date.format("%Y%m%d")),
+            Some(Until::Local(time)) => write!(f, ";UNTIL={}", time.format("%Y%m%dT%H%M%S")),
+            Some(Until::Utc(time)) => write!(f, ";UNTIL={}", time.format("%Y%m%dT%H%M%SZ")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl RecurrenceRule {
+    /// Occurrences of a series starting at `start`, which is always the first
+    /// one, computed on the wall clock of `tz` so that they keep their local
+    /// time across daylight saving time changes
+    pub fn occurrences(&self, start: DateTime<Utc>, tz: Tz) -> Occurrences<'_> {
+        Occurrences {
+            rule: self,
+            tz,
+            start,
+            local_start: start.with_timezone(&tz).naive_local(),
+            period: 0,
+            pending: VecDeque::new(),
+            emitted: 0,
+            done: false,
+        }
+    }
+
+    /// First occurrence of the series strictly after `after`
+    pub fn next_after(
+        &self,
+        start: DateTime<Utc>,
+        tz: Tz,
+        after: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        self.occurrences(start, tz).find(|at| *at > after)
+    }
+
+    /// Local times of the `period`th period of the series, in order
+    fn expand(&self, start: NaiveDateTime, period: u32) -> Option<Vec<NaiveDateTime>> {
+        let step = period.checked_mul(self.interval)?;
+        let date = start.date();
+
+        let mut days: Vec<NaiveDate> = match self.frequency {
+            Frequency::Daily => {
+                let day = date.checked_add_signed(Duration::days(step.into()))?;
+                let matches = self.matches_month(day)
+                    && (self.by_day.is_empty()
+                        || self
+                            .by_day
+                            .iter()
+                            .any(|by_day| by_day.weekday == day.weekday()))
+                    && (self.by_month_day.is_empty()
+                        || self.by_month_day.iter().any(|month_day| {
+                            resolve_month_day(day, *month_day) == Some(day.day())
+                        }));
+
+                if matches {
+                    vec![day]
+                } else {
+                    vec![]
+                }
+            }
+            Frequency::Weekly => {
+                let offset = (7 + date.weekday().num_days_from_monday()
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let week = date
+                    .checked_sub_signed(Duration::days(offset.into()))?
+                    .checked_add_signed(Duration::weeks(step.into()))?;
+
+                (0..7)
+                    .filter_map(|day| week.checked_add_signed(Duration::days(day)))
+                    .filter(|day| match self.by_day.is_empty() {
+                        true => day.weekday() == date.weekday(),
+                        false => self
+                            .by_day
+                            .iter()
+                            .any(|by_day| by_day.weekday == day.weekday()),
+                    })
+                    .filter(|day| self.matches_month(*day))
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = i64::from(date.month0()) + i64::from(step);
+                let year = i32::try_from(i64::from(date.year()) + months / 12).ok()?;
+                let month = (months % 12) as u32 + 1;
+
+                match self.by_month.is_empty() || self.by_month.contains(&month) {
+                    true => self.month_days(year, month, date.day())?,
+                    false => vec![],
+                }
+            }
+            Frequency::Yearly => {
+                let year = date.year().checked_add(i32::try_from(step).ok()?)?;
+                let months = match (
+                    self.by_month.is_empty(),
+                    self.by_day.is_empty() && self.by_month_day.is_empty(),
+                ) {
+                    (false, _) => self.by_month.clone(),
+                    (true, false) => (1..=12).collect(),
+                    (true, true) => vec![date.month()],
+                };
+
+                let mut days = Vec::new();
+                for month in months {
+                    days.extend(self.month_days(year, month, date.day())?);
+                }
+                days
+            }
+        };
+        days.sort_unstable();
+        days.dedup();
+
+        Some(
+            days.into_iter()
+                .map(|day| day.and_time(start.time()))
+                .collect(),
+        )
+    }
+
+    /// Days of a month matching BYMONTHDAY and BYDAY, the day of the start of
+    /// the series when neither is given
+    fn month_days(&self, year: i32, month: u32, start_day: u32) -> Option<Vec<NaiveDate>> {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let last = days_in_month(first)?;
+
+        let days: Vec<u32> = if !self.by_month_day.is_empty() {
+            self.by_month_day
+                .iter()
+                .filter_map(|month_day| resolve_month_day(first, *month_day))
+                .collect()
+        } else if !self.by_day.is_empty() {
+            (1..=last).collect()
+        } else {
+            // Months without that day are skipped, as the RFC requires
+            (start_day <= last)
+                .then_some(start_day)
+                .into_iter()
+                .collect()
+        };
+
+        Some(
+            days.into_iter()
+                .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+                .filter(|day| {
+                    self.by_day.is_empty()
+                        || self.by_day.iter().any(|by_day| {
+                            by_day.weekday == day.weekday()
+                                && match by_day.ordinal {
+                                    Some(ordinal) if ordinal > 0 => {
+                                        (day.day() - 1) / 7 + 1 == ordinal as u32
+                                    }
+                                    Some(ordinal) => {
+                                        (last - day.day()) / 7 + 1 == ordinal.unsigned_abs() as u32
+                                    }
+                                    None => true,
+                                }
+                        })
+                })
+                .collect(),
+        )
+    }
+
+    fn matches_month(&self, day: NaiveDate) -> bool {
+        self.by_month.is_empty() || self.by_month.contains(&day.month())
+    }
+}
+
+/// Occurrences of a series, see [`RecurrenceRule::occurrences`]
+#[derive(Debug)]
+pub struct Occurrences<'a> {
+    rule: &'a RecurrenceRule,
+    tz: Tz,
+    start: DateTime<Utc>,
+    local_start: NaiveDateTime,
+    period: u32,
+    pending: VecDeque<NaiveDateTime>,
+    emitted: u32,
+    done: bool,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = DateTime<Utc>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.rule.count.is_some_and(|count| self.emitted >= count) {
+            return None;
+        }
+
+        let (local, at) = if self.emitted == 0 {
+            (self.local_start, self.start)
+        } else {
+            let mut empty_periods = 0;
+            loop {
+                if let Some(local) = self.pending.pop_front() {
+                    if let Some(at) = to_utc(self.tz, local) {
+                        break (local, at);
+                    }
+                    continue;
+                }
+
+                if empty_periods >= MAX_EMPTY_PERIODS {
+                    self.done = true;
+                    return None;
+                }
+                let Some(locals) = self.rule.expand(self.local_start, self.period) else {
+                    self.done = true;
+                    return None;
+                };
+                self.period += 1;
+                self.pending
+                    .extend(locals.into_iter().filter(|local| *local > self.local_start));
+                empty_periods += 1;
+            }
+        };
+
+        let after_until = match self.rule.until {
+            Some(Until::Date(date)) => local.date() > date,
+            Some(Until::Local(until)) => local > until,
+            Some(Until::Utc(until)) => at > until,
+            None => false,
+        };
+        if after_until {
+            self.done = true;
+            return None;
+        }
+
+        self.emitted += 1;
+        Some(at)
+    }
+}
+
+/// Instant of a local time of `tz`, the earliest one when clocks go back.
+/// Local times skipped when clocks go forward are pushed forward by the gap,
+/// like calendar applications do.
+fn to_utc(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(local + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Day of the month of `month_day` in the month of `date`, counted from the
+/// end when negative, `None` when the month is too short
+fn resolve_month_day(date: NaiveDate, month_day: i8) -> Option<u32> {
+    let last = days_in_month(date)?;
+    let day = if month_day > 0 {
+        month_day as u32
+    } else {
+        (last + 1).checked_sub(month_day.unsigned_abs() as u32)?
+    };
+
+    (1..=last).contains(&day).then_some(day)
+}
+
+fn days_in_month(date: NaiveDate) -> Option<u32> {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+
+    Some(NaiveDate::from_ymd_opt(year, month, 1)?.pred_opt()?.day())
+}
+
+fn parse_until(value: &str) -> Option<Until> {
+    if let Some(time) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(time, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Until::Utc(DateTime::from_utc(time, Utc)));
+    }
+    if value.contains('T') {
+        return NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(Until::Local);
+    }
+
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .map(Until::Date)
+}
+
+fn parse_weekday_num(value: &str) -> Option<WeekdayNum> {
+    let split = value.len().checked_sub(2)?;
+    let (ordinal, weekday) = value.split_at(split);
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => Some(
+            ordinal
+                .trim_start_matches('+')
+                .parse::<i8>()
+                .ok()
+                .filter(|ordinal| matches!(ordinal.abs(), 1..=5))?,
+        ),
+    };
+
+    Some(WeekdayNum {
+        ordinal,
+        weekday: parse_weekday(weekday)?,
+    })
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn occurrences(rule: &str, start: &str, tz: Tz, count: usize) -> Vec<String> {
+        let rule: RecurrenceRule = rule.parse().unwrap();
+        let start = tz
+            .from_local_datetime(&start.parse().unwrap())
+            .unwrap()
+            .with_timezone(&Utc);
+
+        rule.occurrences(start, tz)
+            .take(count)
+            .map(|at| {
+                at.with_timezone(&tz)
+                    .format("%a %Y-%m-%d %H:%M %Z")
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_and_print_canonical_rules() {
+        let rule: RecurrenceRule = "RRULE:freq=weekly;byday=mo,we;interval=2;count=10"
+            .parse()
+            .unwrap();
+        let until: RecurrenceRule = "FREQ=MONTHLY;BYDAY=-1FR;UNTIL=20231231T235959Z"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;COUNT=10"
+        );
+        assert_eq!(
+            until.to_string(),
+            "FREQ=MONTHLY;BYDAY=-1FR;UNTIL=20231231T235959Z"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_and_unsupported_rules() {
+        let errors: Vec<RRuleError> = [
+            "BYDAY=MO",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;COUNT=2;UNTIL=20231231",
+            "FREQ=WEEKLY;BYDAY=1MO",
+            "FREQ=WEEKLY;BYMONTHDAY=1",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;FREQ=WEEKLY",
+            "FREQ=MONTHLY;BYSETPOS=-1",
+            "FREQ=MONTHLY;BYDAY=6MO",
+        ]
+        .into_iter()
+        .map(|rule| rule.parse::<RecurrenceRule>().unwrap_err())
+        .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                RRuleError::MissingFrequency,
+                RRuleError::UnsupportedPart("FREQ=HOURLY".into()),
+                RRuleError::CountAndUntil,
+                RRuleError::OrdinalWeekday,
+                RRuleError::WeeklyMonthDay,
+                RRuleError::InvalidPart("INTERVAL=0".into()),
+                RRuleError::DuplicatePart("FREQ".into()),
+                RRuleError::UnsupportedPart("BYSETPOS=-1".into()),
+                RRuleError::InvalidPart("BYDAY=6MO".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn weekly_occurrences_keep_their_local_time_across_dst() {
+        assert_eq!(
+            occurrences(
+                "FREQ=WEEKLY;BYDAY=TU,SA",
+                "2022-10-25T09:00:00",
+                chrono_tz::Europe::Paris,
+                4
+            ),
+            vec![
+                "Tue 2022-10-25 09:00 CEST",
+                "Sat 2022-10-29 09:00 CEST",
+                "Tue 2022-11-01 09:00 CET",
+                "Sat 2022-11-05 09:00 CET",
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_occurrences_skip_short_months_and_count_from_the_end() {
+        assert_eq!(
+            occurrences("FREQ=MONTHLY", "2023-01-31T08:00:00", Tz::UTC, 3),
+            vec![
+                "Tue 2023-01-31 08:00 UTC",
+                "Fri 2023-03-31 08:00 UTC",
+                "Wed 2023-05-31 08:00 UTC",
+            ]
+        );
+        assert_eq!(
+            occurrences(
+                "FREQ=MONTHLY;BYDAY=-1FR;COUNT=3",
+                "2023-01-27T08:00:00",
+                Tz::UTC,
+                10
+            ),
+            vec![
+                "Fri 2023-01-27 08:00 UTC",
+                "Fri 2023-02-24 08:00 UTC",
+                "Fri 2023-03-31 08:00 UTC",
+            ]
+        );
+    }
+
+    #[test]
+    fn occurrences_stop_at_until_and_skip_dst_gaps_forward() {
+        assert_eq!(
+            occurrences(
+                "FREQ=DAILY;UNTIL=20230327",
+                "2023-03-25T02:30:00",
+                chrono_tz::Europe::Paris,
+                10
+            ),
+            vec![
+                "Sat 2023-03-25 02:30 CET",
+                "Sun 2023-03-26 03:30 CEST",
+                "Mon 2023-03-27 02:30 CEST",
+            ]
+        );
+        assert_eq!(
+            occurrences(
+                "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30",
+                "2023-01-01T08:00:00",
+                Tz::UTC,
+                2
+            ),
+            vec!["Sun 2023-01-01 08:00 UTC"]
+        );
+    }
+}
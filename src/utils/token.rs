@@ -0,0 +1,38 @@
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Generates an opaque random token (256 bits, hex encoded)
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
+
+/// Hashes an opaque token before storing or looking it up.
+///
+/// Tokens are high entropy random values, a fast hash is enough here unlike passwords.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_unique() {
+        let token = generate_token();
+
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn hash_token_is_deterministic() {
+        let token = generate_token();
+
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+    }
+}
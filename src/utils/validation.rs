@@ -0,0 +1,9 @@
+use std::borrow::Cow;
+use validator::ValidationError;
+
+/// Validation error of a field, along with the message shown to the client
+pub fn field_error(code: &'static str, message: impl Into<Cow<'static, str>>) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+    error
+}
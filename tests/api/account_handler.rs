@@ -0,0 +1,186 @@
+use assert_json_diff::assert_json_include;
+use hyper::{header::CONTENT_DISPOSITION, Method, StatusCode};
+use lib::domain::user::User;
+use serde_json::{json, Value};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+#[tokio::test]
+async fn delete_me_handler_schedules_deletion_and_revokes_sessions() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let delete_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            "/api/users/me",
+            Some(&token),
+            Some(&json!({ "password": "test_password" })),
+        )
+        .await;
+    let delete_status = delete_response.status();
+    let deletion: Value = delete_response.json_from_body().await;
+
+    let me_response = app
+        .request(&client, Method::GET, "/api/users/me", Some(&token), None)
+        .await;
+
+    let email = app.mailer.last_email_to("test@email.com");
+
+    app.teardown().await;
+
+    assert_eq!(delete_status, StatusCode::ACCEPTED);
+    assert!(deletion["deletion_scheduled_at"].is_string());
+    assert_eq!(me_response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        email.expect("no email sent").subject,
+        "Your account will be deleted"
+    );
+}
+
+#[tokio::test]
+async fn delete_me_handler_with_wrong_password() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let delete_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            "/api/users/me",
+            Some(&token),
+            Some(&json!({ "password": "wrong_password" })),
+        )
+        .await;
+    let delete_status = delete_response.status();
+    let api_response: Value = delete_response.json_from_body().await;
+
+    let user: User = app
+        .request(&client, Method::GET, "/api/users/me", Some(&token), None)
+        .await
+        .json_from_body()
+        .await;
+
+    app.teardown().await;
+
+    assert!(delete_status.is_client_error());
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "bad credentials",
+        })
+    );
+    assert!(user.deletion_scheduled_at.is_none());
+}
+
+#[tokio::test]
+async fn login_during_grace_period_cancels_deletion() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    app.request(
+        &client,
+        Method::DELETE,
+        "/api/users/me",
+        Some(&token),
+        Some(&json!({ "password": "test_password" })),
+    )
+    .await;
+
+    let login_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login",
+            None,
+            Some(&json!({ "username": "test_username", "password": "test_password" })),
+        )
+        .await;
+    let login_status = login_response.status();
+    let login_body: Value = login_response.json_from_body().await;
+    let new_token = login_body["token"].as_str().expect("could not find token");
+
+    let user: User = app
+        .request(&client, Method::GET, "/api/users/me", Some(new_token), None)
+        .await
+        .json_from_body()
+        .await;
+
+    app.teardown().await;
+
+    assert!(login_status.is_success());
+    assert!(user.deletion_scheduled_at.is_none());
+}
+
+#[tokio::test]
+async fn export_me_handler_with_owned_data() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let list = app
+        .create_list(&client, &token, &json!({ "name": "groceries" }))
+        .await;
+    let task = app
+        .create_task(&client, &token, &json!({ "title": "buy milk" }))
+        .await;
+
+    let export_response = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me/export",
+            Some(&token),
+            None,
+        )
+        .await;
+    let export_status = export_response.status();
+    let content_disposition = export_response
+        .headers()
+        .get(CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let export: Value = export_response.json_from_body().await;
+
+    let anonymous = app
+        .request(&client, Method::GET, "/api/users/me/export", None, None)
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(export_status, StatusCode::OK);
+    assert!(content_disposition
+        .expect("no content disposition")
+        .starts_with("attachment"));
+    assert_json_include!(
+        actual: export.clone(),
+        expected: json!({
+            "user": { "username": "test_username", "email": "test@email.com" },
+            "two_factor_enabled": false,
+            "lists": [{ "id": list.id, "name": "groceries" }],
+            "tasks": [{ "id": task.id, "title": "buy milk" }],
+            "api_tokens": [],
+        })
+    );
+    assert!(export["user"].get("password_hash").is_none());
+    assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+}
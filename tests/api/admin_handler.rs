@@ -0,0 +1,374 @@
+use assert_json_diff::assert_json_include;
+use hyper::{client::HttpConnector, Body, Method, Response, StatusCode};
+use lib::domain::{api_token::CreatedApiToken, user::User};
+use serde_json::{json, Value};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+async fn login(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+    username: &str,
+    password: &str,
+) -> Response<Body> {
+    let login_input = json!({ "username": username, "password": password });
+
+    app.request(
+        client,
+        Method::POST,
+        "/api/users/login",
+        None,
+        Some(&login_input),
+    )
+    .await
+}
+
+/// Registers an admin and a regular user, returning their tokens and the user
+async fn setup_users(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+) -> (String, String, User) {
+    let admin_token = app
+        .create_user(
+            client,
+            &json!({
+                "email": "admin@email.com",
+                "username": "admin_username",
+                "password": "superuser_password"
+            }),
+        )
+        .await;
+    app.promote_to_admin("admin_username").await;
+
+    let user_token = app.register_user(client).await;
+    let user: User = app
+        .request(
+            client,
+            Method::GET,
+            "/api/users/me",
+            Some(&user_token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    (admin_token, user_token, user)
+}
+
+#[tokio::test]
+async fn admin_routes_require_admin_role() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (_, user_token, _) = setup_users(&app, &client).await;
+
+    let as_user = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/admin/users",
+            Some(&user_token),
+            None,
+        )
+        .await;
+    let anonymous = app
+        .request(&client, Method::GET, "/api/admin/users", None, None)
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(as_user.status(), StatusCode::FORBIDDEN);
+    assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn list_and_search_users() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (admin_token, _, _) = setup_users(&app, &client).await;
+
+    let all: Vec<User> = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/admin/users",
+            Some(&admin_token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+    let searched: Vec<User> = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/admin/users?search=TEST@",
+            Some(&admin_token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+    let paginated: Vec<User> = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/admin/users?limit=1&offset=1",
+            Some(&admin_token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+    let bad_limit = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/admin/users?limit=1000",
+            Some(&admin_token),
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(all.len(), 2);
+    assert_eq!(searched.len(), 1);
+    assert_eq!(searched[0].username, "test_username");
+    assert_eq!(paginated.len(), 1);
+    assert_eq!(paginated[0].username, "test_username");
+    assert_eq!(bad_limit.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn disabled_user_cannot_login_until_enabled() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (admin_token, user_token, user) = setup_users(&app, &client).await;
+
+    let disable_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/admin/users/{}/disable", user.id),
+            Some(&admin_token),
+            None,
+        )
+        .await;
+    let disable_status = disable_response.status();
+    let disabled: Value = disable_response.json_from_body().await;
+
+    // Sessions are revoked on disable
+    let me_response = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me",
+            Some(&user_token),
+            None,
+        )
+        .await;
+    let me_status = me_response.status();
+    let login_response = login(&app, &client, "test_username", "test_password").await;
+    let login_status = login_response.status();
+    let login_body: Value = login_response.json_from_body().await;
+
+    let enable_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/admin/users/{}/enable", user.id),
+            Some(&admin_token),
+            None,
+        )
+        .await;
+    let enable_status = enable_response.status();
+    let enabled: User = enable_response.json_from_body().await;
+    let relogin_response = login(&app, &client, "test_username", "test_password").await;
+
+    app.teardown().await;
+
+    assert_eq!(disable_status, StatusCode::OK);
+    assert!(!disabled["disabled_at"].is_null());
+    assert_eq!(me_status, StatusCode::UNAUTHORIZED);
+    assert_eq!(login_status, StatusCode::FORBIDDEN);
+    assert_json_include!(actual: login_body, expected: json!({ "message": "account disabled" }));
+    assert_eq!(enable_status, StatusCode::OK);
+    assert!(enabled.disabled_at.is_none());
+    assert_eq!(relogin_response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn forced_password_reset_blocks_login_until_reset() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (admin_token, user_token, user) = setup_users(&app, &client).await;
+    let api_token: CreatedApiToken = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/me/tokens",
+            Some(&user_token),
+            Some(&json!({ "name": "backup script", "scopes": ["tasks:read"] })),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    let reset_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/admin/users/{}/password-reset", user.id),
+            Some(&admin_token),
+            None,
+        )
+        .await;
+
+    // Credentials issued before the reset no longer work
+    let session_response = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me",
+            Some(&user_token),
+            None,
+        )
+        .await;
+    let api_token_response = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/tasks",
+            Some(&api_token.token),
+            None,
+        )
+        .await;
+
+    let login_response = login(&app, &client, "test_username", "test_password").await;
+    let login_status = login_response.status();
+    let login_body: Value = login_response.json_from_body().await;
+
+    let email = app
+        .mailer
+        .last_email_to("test@email.com")
+        .expect("no email sent");
+    let token = email
+        .body
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("no token in email")
+        .to_string();
+    let password_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/reset",
+            None,
+            Some(&json!({ "token": token, "new_password": "new_password" })),
+        )
+        .await;
+    let relogin_response = login(&app, &client, "test_username", "new_password").await;
+
+    app.teardown().await;
+
+    assert_eq!(reset_response.status(), StatusCode::ACCEPTED);
+    assert!(session_response.status().is_client_error());
+    assert!(api_token_response.status().is_client_error());
+    assert_eq!(login_status, StatusCode::FORBIDDEN);
+    assert_json_include!(
+        actual: login_body,
+        expected: json!({ "message": "password reset required" })
+    );
+    assert_eq!(password_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(relogin_response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn delete_user_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (admin_token, _, user) = setup_users(&app, &client).await;
+    let path = format!("/api/admin/users/{}", user.id);
+
+    let delete_response = app
+        .request(&client, Method::DELETE, &path, Some(&admin_token), None)
+        .await;
+    let get_response = app
+        .request(&client, Method::GET, &path, Some(&admin_token), None)
+        .await;
+    let login_response = login(&app, &client, "test_username", "test_password").await;
+
+    app.teardown().await;
+
+    assert_eq!(delete_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(get_response.status(), StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(login_response.status(), StatusCode::NOT_ACCEPTABLE);
+}
+
+#[tokio::test]
+async fn admin_cannot_disable_or_delete_themselves() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (admin_token, _, _) = setup_users(&app, &client).await;
+    let admin: User = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me",
+            Some(&admin_token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    let disable_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/admin/users/{}/disable", admin.id),
+            Some(&admin_token),
+            None,
+        )
+        .await;
+    let delete_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/admin/users/{}", admin.id),
+            Some(&admin_token),
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(disable_response.status(), StatusCode::CONFLICT);
+    assert_eq!(delete_response.status(), StatusCode::CONFLICT);
+}
@@ -0,0 +1,231 @@
+use hyper::{Method, StatusCode};
+use lib::domain::api_token::{ApiToken, CreatedApiToken};
+use serde_json::json;
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+#[tokio::test]
+async fn create_api_token_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let token_input = json!({
+        "name": "backup script",
+        "scopes": ["tasks:read", "lists:read"],
+        "expires_in_days": 30
+    });
+    let res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/me/tokens",
+            Some(&token),
+            Some(&token_input),
+        )
+        .await;
+    let status = res.status();
+    let created: CreatedApiToken = res.json_from_body().await;
+
+    let listed: Vec<ApiToken> = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me/tokens",
+            Some(&token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(created.token.starts_with("tdo_"));
+    assert_eq!(created.api_token.name, "backup script");
+    assert_eq!(created.api_token.scopes, vec!["lists:read", "tasks:read"]);
+    assert!(created.api_token.expires_at.is_some());
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, created.api_token.id);
+}
+
+#[tokio::test]
+async fn create_api_token_with_unknown_scope() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let token_input = json!({ "name": "script", "scopes": ["admin"] });
+    let res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/me/tokens",
+            Some(&token),
+            Some(&token_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert!(res.status().is_client_error());
+}
+
+#[tokio::test]
+async fn api_token_is_limited_to_its_scopes() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let token_input = json!({ "name": "reader", "scopes": ["tasks:read"] });
+    let created: CreatedApiToken = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/me/tokens",
+            Some(&token),
+            Some(&token_input),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    let read_res = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/tasks",
+            Some(&created.token),
+            None,
+        )
+        .await;
+    let write_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tasks",
+            Some(&created.token),
+            Some(&json!({ "title": "title", "description": "description" })),
+        )
+        .await;
+    let lists_res = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/lists",
+            Some(&created.token),
+            None,
+        )
+        .await;
+    // Api tokens cannot create other api tokens
+    let tokens_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/me/tokens",
+            Some(&created.token),
+            Some(&token_input),
+        )
+        .await;
+
+    let listed: Vec<ApiToken> = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me/tokens",
+            Some(&token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(read_res.status(), StatusCode::OK);
+    assert_eq!(write_res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(lists_res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(tokens_res.status(), StatusCode::FORBIDDEN);
+    assert!(listed[0].last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn revoked_api_token_is_rejected() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let token_input = json!({ "name": "profile", "scopes": ["user:read"] });
+    let created: CreatedApiToken = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/me/tokens",
+            Some(&token),
+            Some(&token_input),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    let before_res = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me",
+            Some(&created.token),
+            None,
+        )
+        .await;
+    let revoke_res = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/users/me/tokens/{}", created.api_token.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let after_res = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me",
+            Some(&created.token),
+            None,
+        )
+        .await;
+    let revoke_again_res = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/users/me/tokens/{}", created.api_token.id),
+            Some(&token),
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(before_res.status(), StatusCode::OK);
+    assert_eq!(revoke_res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(after_res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(revoke_again_res.status(), StatusCode::NOT_FOUND);
+}
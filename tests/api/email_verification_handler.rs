@@ -0,0 +1,267 @@
+use hyper::{Body, Method, Request, StatusCode};
+use lib::{configuration::EmailVerificationPolicy, domain::user::User};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::helpers::{
+    app::{user_input, TestApp},
+    ParseJson,
+};
+
+/// Extracts the verification token from the link of the last email sent to `to`
+fn verification_token_sent_to(app: &TestApp, to: &str) -> String {
+    let email = app.mailer.last_email_to(to).expect("no email sent");
+
+    email
+        .body
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("no token in email")
+        .to_string()
+}
+
+/// Token of the verification email resent in the background to `to`, once it
+/// replaces `previous_token`
+async fn resent_verification_token(app: &TestApp, to: &str, previous_token: &str) -> String {
+    for _ in 0..50 {
+        let token = verification_token_sent_to(app, to);
+        if token != previous_token {
+            return token;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    panic!("no verification email resent");
+}
+
+#[tokio::test]
+async fn verify_email_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let verification_token = verification_token_sent_to(&app, "test@email.com");
+
+    let verify_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/verify-email",
+            None,
+            Some(&json!({ "token": verification_token })),
+        )
+        .await;
+
+    let me_response = app
+        .request(&client, Method::GET, "/api/users/me", Some(&token), None)
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(verify_response.status(), StatusCode::NO_CONTENT);
+
+    // Getting json data
+
+    let user: User = me_response.json_from_body().await;
+
+    assert!(user.email_verified_at.is_some());
+}
+
+#[tokio::test]
+async fn login_requires_verified_email() {
+    let mut app = TestApp::build();
+    app.config.app_settings.email_verification = EmailVerificationPolicy::RequiredForLogin;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let register_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/register",
+            None,
+            Some(&user_input()),
+        )
+        .await;
+
+    let login_input = json!({
+        "username": "test_username",
+        "password": "test_password"
+    });
+    let login = || {
+        Request::builder()
+            .method(Method::POST)
+            .uri(app.get_http_uri("/api/users/login"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(login_input.to_string()))
+            .expect("could not create request")
+    };
+
+    let unverified_response = client
+        .request(login())
+        .await
+        .expect("could not send request");
+
+    let verification_token = verification_token_sent_to(&app, "test@email.com");
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/verify-email",
+        None,
+        Some(&json!({ "token": verification_token })),
+    )
+    .await;
+
+    let verified_response = client
+        .request(login())
+        .await
+        .expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(register_response.status(), StatusCode::ACCEPTED);
+    assert_eq!(unverified_response.status(), StatusCode::FORBIDDEN);
+    assert!(verified_response.status().is_success());
+
+    // Getting json data
+
+    let registered: Value = register_response.json_from_body().await;
+
+    assert!(registered.get("token").is_none());
+    assert_eq!(registered["username"], "test_username");
+}
+
+#[tokio::test]
+async fn restricted_user_cannot_access_tasks() {
+    let mut app = TestApp::build();
+    app.config.app_settings.email_verification = EmailVerificationPolicy::Restricted;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let list_tasks = || {
+        Request::builder()
+            .method(Method::GET)
+            .uri(app.get_http_uri("/api/tasks"))
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .expect("could not create request")
+    };
+
+    let unverified_response = client
+        .request(list_tasks())
+        .await
+        .expect("could not send request");
+
+    let verification_token = verification_token_sent_to(&app, "test@email.com");
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/verify-email",
+        None,
+        Some(&json!({ "token": verification_token })),
+    )
+    .await;
+
+    let verified_response = client
+        .request(list_tasks())
+        .await
+        .expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(unverified_response.status(), StatusCode::FORBIDDEN);
+    assert!(verified_response.status().is_success());
+}
+
+#[tokio::test]
+async fn resend_verification_email_is_throttled() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    let response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/verify-email/resend",
+            None,
+            Some(&json!({ "email": "test@email.com" })),
+        )
+        .await;
+
+    // Leaving time for a background send
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let emails = app.mailer.emails();
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(emails.len(), 1);
+}
+
+#[tokio::test]
+async fn resend_verification_email_after_cooldown() {
+    let mut app = TestApp::build();
+    app.config
+        .app_settings
+        .email_verification_resend_cooldown_seconds = 0;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+    let first_token = verification_token_sent_to(&app, "test@email.com");
+
+    let response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/verify-email/resend",
+            None,
+            Some(&json!({ "email": "test@email.com" })),
+        )
+        .await;
+
+    let second_token = resent_verification_token(&app, "test@email.com", &first_token).await;
+
+    // Only the last token sent is valid
+    let first_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/verify-email",
+            None,
+            Some(&json!({ "token": first_token })),
+        )
+        .await;
+    let second_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/verify-email",
+            None,
+            Some(&json!({ "token": second_token })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(second_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(first_response.status(), StatusCode::BAD_REQUEST);
+}
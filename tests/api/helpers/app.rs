@@ -1,24 +1,35 @@
 use axum::Router;
-use hyper::{client::HttpConnector, Body, Method, Request};
-use lib::configuration::{AppConfig, DatabaseSettings};
-use serde_json::Value;
+use hyper::{client::HttpConnector, http::request, Body, Method, Request, Response};
+use lib::{
+    configuration::{AppConfig, DatabaseSettings},
+    domain::{list::List, tag::Tag, task::Task},
+    mailer::InMemoryMailer,
+    notifier::{dispatch_due_reminders, Notifiers},
+    utils::{cipher::SecretCipher, hasher::Hasher, jwt::JwtKeys},
+};
+use serde_json::{json, Value};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 
-use std::net::TcpListener;
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+};
 
 use super::ParseJson;
 
 #[derive(Debug)]
 pub struct TestApp {
     pub config: AppConfig,
+    pub mailer: Arc<InMemoryMailer>,
 }
 
 impl TestApp {
     pub fn build() -> Self {
         let config = AppConfig::build("TEST".into()).unwrap();
+        let mailer = Arc::new(InMemoryMailer::default());
 
-        Self { config }
+        Self { config, mailer }
     }
 
     pub async fn start_server(&mut self) {
@@ -33,8 +44,20 @@ impl TestApp {
         self.config.app_settings.port = listener.local_addr().unwrap().port();
 
         // Create server
-        let router =
-            lib::router::setup_router(db_pool, self.config.app_settings.jwt_secret.clone());
+        let jwt_keys =
+            JwtKeys::from_settings(&self.config.app_settings).expect("could not load jwt keys");
+        let hasher = Hasher::from_settings(&self.config.app_settings.password_hashing)
+            .expect("invalid password hashing settings");
+        let totp_cipher = SecretCipher::from_key(&self.config.app_settings.totp_encryption_key)
+            .expect("invalid totp encryption key");
+        let router = lib::router::setup_router(
+            db_pool,
+            self.config.app_settings.clone(),
+            self.mailer.clone(),
+            jwt_keys,
+            hasher,
+            totp_cipher,
+        );
 
         // Spawn server
         spawn_server(listener, router);
@@ -97,38 +120,260 @@ AND pid <> pg_backend_pid();"#,
         db_pool
     }
 
-    pub fn get_http_uri(&self, path: &'static str) -> String {
+    /// Connection to the database of the app, for tests to set up what no
+    /// endpoint does
+    pub async fn db_connection(&self) -> PgConnection {
+        PgConnection::connect(
+            &self
+                .config
+                .database_settings
+                .connection_string_with_db_name(),
+        )
+        .await
+        .expect("could not connect to db")
+    }
+
+    /// Creates a user straight in the database, for tests where the server
+    /// cannot hash the password of a registration
+    pub async fn insert_user(&self, username: &str, email: &str) {
+        let mut conn = self.db_connection().await;
+
+        sqlx::query("insert into users(id, username, email, password_hash) values($1,$2,$3,$4)")
+            .bind(Uuid::new_v4())
+            .bind(username)
+            .bind(email)
+            .bind("unusable_password_hash")
+            .execute(&mut conn)
+            .await
+            .expect("could not insert user");
+        conn.close().await.expect("could not close connection");
+    }
+
+    /// Grants the admin role to a user, there is no endpoint to do so
+    pub async fn promote_to_admin(&self, username: &str) {
+        let mut conn = self.db_connection().await;
+
+        sqlx::query("update users set role = 'admin' where username = $1")
+            .bind(username)
+            .execute(&mut conn)
+            .await
+            .expect("could not promote user");
+        conn.close().await.expect("could not close connection");
+    }
+
+    /// Replaces the password hash of a user, to simulate hashes made with
+    /// other settings
+    pub async fn set_password_hash(&self, username: &str, password_hash: &str) {
+        let mut conn = self.db_connection().await;
+
+        sqlx::query("update users set password_hash = $2 where username = $1")
+            .bind(username)
+            .bind(password_hash)
+            .execute(&mut conn)
+            .await
+            .expect("could not set password hash");
+        conn.close().await.expect("could not close connection");
+    }
+
+    pub async fn password_hash(&self, username: &str) -> String {
+        let mut conn = self.db_connection().await;
+
+        let (password_hash,): (String,) =
+            sqlx::query_as("select password_hash from users where username = $1")
+                .bind(username)
+                .fetch_one(&mut conn)
+                .await
+                .expect("could not find user");
+        conn.close().await.expect("could not close connection");
+
+        password_hash
+    }
+
+    /// Secret of the user as stored in the database
+    pub async fn stored_totp_secret(&self, username: &str) -> String {
+        let mut conn = self.db_connection().await;
+
+        let (secret,): (String,) = sqlx::query_as(
+            "select secret from user_totp join users on users.id = user_id where username = $1",
+        )
+        .bind(username)
+        .fetch_one(&mut conn)
+        .await
+        .expect("could not find totp");
+        conn.close().await.expect("could not close connection");
+
+        secret
+    }
+
+    /// Makes every pending reminder due now, as if its time had come or its
+    /// delivery lease was over
+    pub async fn make_reminders_due(&self) {
+        let mut conn = self.db_connection().await;
+
+        sqlx::query("update task_reminders set next_attempt_at = now() where failed_at is null")
+            .execute(&mut conn)
+            .await
+            .expect("could not make reminders due");
+        conn.close().await.expect("could not close connection");
+    }
+
+    /// Forgets the deliveries of every reminder, as if the worker stopped
+    /// before recording them
+    pub async fn reset_reminder_deliveries(&self) {
+        let mut conn = self.db_connection().await;
+
+        sqlx::query("update task_reminders set delivered_at = null")
+            .execute(&mut conn)
+            .await
+            .expect("could not reset reminder deliveries");
+        conn.close().await.expect("could not close connection");
+    }
+
+    /// Runs the reminder worker once, returns how many reminders it delivered
+    pub async fn dispatch_reminders(&self) -> usize {
+        let db_pool = PgPool::connect(
+            &self
+                .config
+                .database_settings
+                .connection_string_with_db_name(),
+        )
+        .await
+        .expect("could not connect to db");
+
+        let settings = &self.config.app_settings.reminders;
+        let notifiers = Notifiers::new(self.mailer.clone(), db_pool.clone(), settings);
+        let delivered = dispatch_due_reminders(&db_pool, &notifiers, settings)
+            .await
+            .expect("could not dispatch reminders");
+        db_pool.close().await;
+
+        delivered
+    }
+
+    pub fn get_http_uri(&self, path: &str) -> String {
         format!(
             "http://{}:{}{}",
             &self.config.app_settings.host, self.config.app_settings.port, path
         )
     }
 
+    /// Request to `path` with a JSON content type, authenticated with `token`
+    /// when given
+    pub fn request_builder(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+    ) -> request::Builder {
+        let req = Request::builder()
+            .method(method)
+            .uri(self.get_http_uri(path))
+            .header("Content-Type", "application/json");
+
+        match token {
+            Some(token) => req.header("Authorization", format!("Bearer {}", token)),
+            None => req,
+        }
+    }
+
+    /// Sends `input` as JSON to `path`, authenticated with `token` when given
+    pub async fn request(
+        &self,
+        client: &hyper::Client<HttpConnector>,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+        input: Option<&Value>,
+    ) -> Response<Body> {
+        let req = self
+            .request_builder(method, path, token)
+            .body(input.map_or(Body::empty(), |input| Body::from(input.to_string())))
+            .expect("could not create request");
+
+        client.request(req).await.expect("could not send request")
+    }
+
     pub async fn create_user(
         &self,
         client: &hyper::Client<HttpConnector>,
         input: &Value,
     ) -> String {
-        let req = Request::builder()
-            .method(Method::POST)
-            .uri(self.get_http_uri("/api/users/register"))
-            .header("Content-Type", "application/json")
-            .body(Body::from(input.to_string()))
-            .expect("could not create request");
-
-        let response = client.request(req).await.expect("could not send request");
+        let response = self
+            .request(
+                client,
+                Method::POST,
+                "/api/users/register",
+                None,
+                Some(input),
+            )
+            .await;
 
         let body: Value = response.json_from_body().await;
 
-        body["token"].to_string()
+        body["token"]
+            .as_str()
+            .expect("could not find token")
+            .to_string()
+    }
+
+    /// Registers the user of [`user_input`], returning their access token
+    pub async fn register_user(&self, client: &hyper::Client<HttpConnector>) -> String {
+        self.create_user(client, &user_input()).await
+    }
+
+    pub async fn create_task(
+        &self,
+        client: &hyper::Client<HttpConnector>,
+        token: &str,
+        input: &Value,
+    ) -> Task {
+        self.request(client, Method::POST, "/api/tasks", Some(token), Some(input))
+            .await
+            .json_from_body()
+            .await
     }
+
+    pub async fn create_list(
+        &self,
+        client: &hyper::Client<HttpConnector>,
+        token: &str,
+        input: &Value,
+    ) -> List {
+        self.request(client, Method::POST, "/api/lists", Some(token), Some(input))
+            .await
+            .json_from_body()
+            .await
+    }
+
+    pub async fn create_tag(
+        &self,
+        client: &hyper::Client<HttpConnector>,
+        token: &str,
+        name: &str,
+    ) -> Tag {
+        let input = json!({ "name": name });
+
+        self.request(client, Method::POST, "/api/tags", Some(token), Some(&input))
+            .await
+            .json_from_body()
+            .await
+    }
+}
+
+/// Registration of the user most tests act as
+pub fn user_input() -> Value {
+    json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    })
 }
 
 fn spawn_server(listener: TcpListener, router: Router) {
     tokio::spawn(async move {
         axum::Server::from_tcp(listener)
             .expect("could not bind the tcp listener")
-            .serve(router.into_make_service())
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .expect("could not start server")
     });
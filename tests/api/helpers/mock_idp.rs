@@ -0,0 +1,250 @@
+use axum::{
+    extract::Query,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Json, Router,
+};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use lib::{
+    configuration::{AppConfig, OidcProviderSettings},
+    oidc::pkce_challenge,
+    utils::{jwt::JwtKeys, token::generate_token},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+pub const CLIENT_ID: &str = "test-client";
+pub const CLIENT_SECRET: &str = "test-secret";
+const SIGNING_KID: &str = "test-rs256";
+
+/// Account of the user logging in on the mock identity provider
+#[derive(Debug, Clone)]
+pub struct MockIdpUser {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+}
+
+impl MockIdpUser {
+    pub fn new(sub: &str, email: &str) -> Self {
+        Self {
+            sub: sub.into(),
+            email: Some(email.into()),
+            email_verified: true,
+            preferred_username: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingCode {
+    nonce: String,
+    code_challenge: String,
+    user: MockIdpUser,
+}
+
+struct MockIdpState {
+    issuer: String,
+    user: MockIdpUser,
+    /// Audience of the next id tokens, the client id when `None`
+    audience: Option<String>,
+    codes: HashMap<String, PendingCode>,
+    signing_key: EncodingKey,
+    jwks: Value,
+}
+
+/// OpenID Connect provider issuing id tokens for the current user, signed with
+/// the RS256 test key
+#[derive(Clone)]
+pub struct MockIdp {
+    pub issuer: String,
+    state: Arc<Mutex<MockIdpState>>,
+}
+
+impl MockIdp {
+    pub fn start(user: MockIdpUser) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind listener");
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let mut settings = AppConfig::build("TEST".into()).unwrap().app_settings;
+        settings.jwt_keys.retain(|key| key.kid == SIGNING_KID);
+        let jwt_keys = JwtKeys::from_settings(&settings).expect("could not load jwt keys");
+        let private_key_path = settings.jwt_keys[0]
+            .private_key_path
+            .clone()
+            .expect("missing private key");
+        let pem = std::fs::read(private_key_path).expect("could not read private key");
+
+        let state = Arc::new(Mutex::new(MockIdpState {
+            issuer: issuer.clone(),
+            user,
+            audience: None,
+            codes: HashMap::new(),
+            signing_key: EncodingKey::from_rsa_pem(&pem).expect("invalid private key"),
+            jwks: serde_json::to_value(jwt_keys.jwks()).unwrap(),
+        }));
+
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(metadata_handler))
+            .route("/authorize", get(authorize_handler))
+            .route("/token", post(token_handler))
+            .route("/jwks", get(jwks_handler))
+            .layer(Extension(state.clone()));
+        tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .expect("could not bind the tcp listener")
+                .serve(router.into_make_service())
+                .await
+                .expect("could not start mock identity provider")
+        });
+
+        Self { issuer, state }
+    }
+
+    /// Settings of the api to log in with this provider as `name`
+    pub fn provider_settings(&self, name: &str) -> OidcProviderSettings {
+        OidcProviderSettings {
+            name: name.into(),
+            issuer: self.issuer.clone(),
+            client_id: CLIENT_ID.into(),
+            client_secret: Some(CLIENT_SECRET.into()),
+            redirect_uri: "http://localhost:3000/login/callback".into(),
+            scopes: vec!["openid".into(), "email".into()],
+        }
+    }
+
+    pub fn set_user(&self, user: MockIdpUser) {
+        self.state.lock().unwrap().user = user;
+    }
+
+    pub fn set_audience(&self, audience: Option<&str>) {
+        self.state.lock().unwrap().audience = audience.map(Into::into);
+    }
+}
+
+async fn metadata_handler(Extension(state): Extension<Arc<Mutex<MockIdpState>>>) -> Json<Value> {
+    let issuer = state.lock().unwrap().issuer.clone();
+
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", issuer),
+        "token_endpoint": format!("{}/token", issuer),
+        "jwks_uri": format!("{}/jwks", issuer),
+    }))
+}
+
+async fn jwks_handler(Extension(state): Extension<Arc<Mutex<MockIdpState>>>) -> Json<Value> {
+    Json(state.lock().unwrap().jwks.clone())
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    client_id: String,
+    redirect_uri: String,
+    state: String,
+    nonce: String,
+    code_challenge: String,
+    code_challenge_method: String,
+}
+
+/// Logs the current user in right away and redirects back with a code
+async fn authorize_handler(
+    Query(query): Query<AuthorizeQuery>,
+    Extension(state): Extension<Arc<Mutex<MockIdpState>>>,
+) -> Response {
+    if query.client_id != CLIENT_ID || query.code_challenge_method != "S256" {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let code = generate_token();
+    let mut state_guard = state.lock().unwrap();
+    let user = state_guard.user.clone();
+    state_guard.codes.insert(
+        code.clone(),
+        PendingCode {
+            nonce: query.nonce,
+            code_challenge: query.code_challenge,
+            user,
+        },
+    );
+
+    let params = serde_urlencoded::to_string([("code", &code), ("state", &query.state)]).unwrap();
+    Redirect::to(&format!("{}?{}", query.redirect_uri, params)).into_response()
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: String,
+    code_verifier: String,
+}
+
+async fn token_handler(
+    headers: HeaderMap,
+    Form(form): Form<TokenForm>,
+    Extension(state): Extension<Arc<Mutex<MockIdpState>>>,
+) -> Response {
+    let credentials = format!(
+        "Basic {}",
+        base64::encode(format!("{}:{}", CLIENT_ID, CLIENT_SECRET))
+    );
+    if headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        != Some(credentials.as_str())
+    {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "invalid_client" })),
+        )
+            .into_response();
+    }
+
+    let mut state = state.lock().unwrap();
+    let pending = match state.codes.remove(&form.code) {
+        Some(pending)
+            if form.grant_type == "authorization_code"
+                && pkce_challenge(&form.code_verifier) == pending.code_challenge =>
+        {
+            pending
+        }
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "invalid_grant" })),
+            )
+                .into_response()
+        }
+    };
+
+    let now = Utc::now().timestamp();
+    let claims = json!({
+        "iss": state.issuer,
+        "aud": state.audience.as_deref().unwrap_or(CLIENT_ID),
+        "iat": now,
+        "exp": now + 300,
+        "sub": pending.user.sub,
+        "email": pending.user.email,
+        "email_verified": pending.user.email_verified,
+        "preferred_username": pending.user.preferred_username,
+        "nonce": pending.nonce,
+    });
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(SIGNING_KID.into());
+    let id_token = jsonwebtoken::encode(&header, &claims, &state.signing_key).unwrap();
+
+    Json(json!({
+        "access_token": generate_token(),
+        "token_type": "Bearer",
+        "id_token": id_token,
+    }))
+    .into_response()
+}
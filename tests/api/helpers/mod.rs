@@ -1,4 +1,5 @@
 pub mod app;
+pub mod mock_idp;
 mod response;
 
 pub use response::*;
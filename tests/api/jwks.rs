@@ -0,0 +1,46 @@
+use hyper::{Body, Method, Request};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use lib::{domain::user::Claims, utils::jwt::Jwks};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+#[tokio::test]
+async fn jwks_verifies_access_tokens() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let response = client
+        .request(
+            Request::builder()
+                .method(Method::GET)
+                .uri(app.get_http_uri("/.well-known/jwks.json"))
+                .body(Body::empty())
+                .expect("could not create request"),
+        )
+        .await
+        .expect("could not send request");
+
+    app.teardown().await;
+    assert!(response.status().is_success());
+
+    let jwks: Jwks = response.json_from_body().await;
+
+    // Verifying the token the way another service would, with the published key
+    let kid = jsonwebtoken::decode_header(&token).unwrap().kid.unwrap();
+    let jwk = jwks.keys.iter().find(|key| key.kid == kid).unwrap();
+    let public_key =
+        base64::decode_config(jwk.x.as_ref().unwrap(), base64::URL_SAFE_NO_PAD).unwrap();
+    let claims = jsonwebtoken::decode::<Claims>(
+        &token,
+        &DecodingKey::from_ed_der(&public_key),
+        &Validation::new(Algorithm::EdDSA),
+    );
+
+    assert_eq!(jwks.keys.len(), 2);
+    assert!(claims.is_ok());
+}
@@ -0,0 +1,234 @@
+use assert_json_diff::assert_json_include;
+use hyper::{Method, StatusCode};
+use lib::domain::{list::List, task::Task};
+use serde_json::{json, Value};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+#[tokio::test]
+async fn create_list_already_exists() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let list_input = json!({ "name": "groceries" });
+
+    app.create_list(&client, &token, &list_input).await;
+
+    let response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/lists",
+            Some(&token),
+            Some(&list_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "list already exists",
+        })
+    )
+}
+
+#[tokio::test]
+async fn move_task_to_list_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let list = app
+        .create_list(&client, &token, &json!({ "name": "groceries" }))
+        .await;
+    let task = app
+        .create_task(&client, &token, &json!({ "title": "buy milk" }))
+        .await;
+
+    let move_response = app
+        .request(
+            &client,
+            Method::PUT,
+            &format!("/api/tasks/{}/list", task.id),
+            Some(&token),
+            Some(&json!({ "list_id": list.id })),
+        )
+        .await;
+
+    let list_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/lists/{}/tasks", list.id),
+            Some(&token),
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert!(move_response.status().is_success());
+    assert!(list_response.status().is_success());
+
+    // Getting json data
+
+    let moved_task: Task = move_response.json_from_body().await;
+    let tasks: Vec<Task> = list_response.json_from_body().await;
+
+    assert_eq!(moved_task.list_id, Some(list.id));
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].id, task.id);
+}
+
+#[tokio::test]
+async fn move_task_to_list_of_another_user() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let owner_token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "owner@email.com",
+                "username": "owner_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+    let other_token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "other@email.com",
+                "username": "other_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+
+    let list = app
+        .create_list(&client, &owner_token, &json!({ "name": "groceries" }))
+        .await;
+    let task = app
+        .create_task(&client, &other_token, &json!({ "title": "buy milk" }))
+        .await;
+
+    let response = app
+        .request(
+            &client,
+            Method::PUT,
+            &format!("/api/tasks/{}/list", task.id),
+            Some(&other_token),
+            Some(&json!({ "list_id": list.id })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "list not found",
+        })
+    )
+}
+
+#[tokio::test]
+async fn delete_list_with_cascade() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let list = app
+        .create_list(&client, &token, &json!({ "name": "groceries" }))
+        .await;
+    app.create_task(
+        &client,
+        &token,
+        &json!({ "title": "buy milk", "list_id": list.id }),
+    )
+    .await;
+
+    let delete_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/lists/{}?tasks=cascade", list.id),
+            Some(&token),
+            None,
+        )
+        .await;
+
+    let list_response = app
+        .request(&client, Method::GET, "/api/tasks", Some(&token), None)
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(delete_response.status(), StatusCode::NO_CONTENT);
+
+    // Getting json data
+
+    let tasks: Vec<Task> = list_response.json_from_body().await;
+
+    assert!(tasks.is_empty());
+}
+
+#[tokio::test]
+async fn list_lists_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    app.create_list(&client, &token, &json!({ "name": "groceries" }))
+        .await;
+    app.create_list(&client, &token, &json!({ "name": "chores" }))
+        .await;
+
+    let response = app
+        .request(&client, Method::GET, "/api/lists", Some(&token), None)
+        .await;
+
+    app.teardown().await;
+
+    assert!(response.status().is_success());
+
+    // Getting json data
+
+    let lists: Vec<List> = response.json_from_body().await;
+
+    assert_eq!(
+        lists.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(),
+        vec!["groceries", "chores"]
+    );
+}
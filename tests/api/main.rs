@@ -1,3 +1,18 @@
+mod account_handler;
+mod admin_handler;
+mod api_token_handler;
+mod email_verification_handler;
 mod helpers;
+mod jwks;
+mod list_handler;
+mod oidc_handler;
+mod password_handler;
+mod rate_limit;
+mod recurrence_handler;
+mod reminder_handler;
+mod session_cookie;
 mod status_handler;
+mod tag_handler;
+mod task_handler;
+mod two_factor_handler;
 mod user_handler;
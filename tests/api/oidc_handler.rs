@@ -0,0 +1,332 @@
+use assert_json_diff::assert_json_include;
+use hyper::{client::HttpConnector, header::LOCATION, Body, Method, Request, StatusCode};
+use lib::domain::oidc::{OidcAuthorization, UserIdentity};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::helpers::{
+    app::TestApp,
+    mock_idp::{MockIdp, MockIdpUser},
+    ParseJson,
+};
+
+async fn start_app_with_idp(user: MockIdpUser) -> (TestApp, MockIdp) {
+    let idp = MockIdp::start(user);
+    let mut app = TestApp::build();
+    app.config.app_settings.oidc_providers = vec![idp.provider_settings("idp")];
+    app.start_server().await;
+
+    (app, idp)
+}
+
+/// Starts an authorization on `path_prefix`, logs in on the identity provider
+/// and returns the code and state it redirected back with
+async fn authorize(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+    path_prefix: &str,
+    token: Option<&str>,
+) -> Value {
+    let response = app
+        .request(
+            client,
+            Method::POST,
+            &format!("{}/authorize", path_prefix),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let authorization: OidcAuthorization = response.json_from_body().await;
+
+    let req = Request::builder()
+        .uri(authorization.authorization_url)
+        .body(Body::empty())
+        .expect("could not create request");
+    let response = client.request(req).await.expect("could not send request");
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .expect("identity provider did not redirect")
+        .to_str()
+        .unwrap();
+    let (_, query) = location.split_once('?').expect("missing query");
+    let params: HashMap<String, String> = serde_urlencoded::from_str(query).unwrap();
+
+    json!({ "code": params["code"], "state": params["state"] })
+}
+
+async fn login_with_idp(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+) -> (StatusCode, Value) {
+    let callback = authorize(app, client, "/api/users/oidc/idp", None).await;
+    let response = app
+        .request(
+            client,
+            Method::POST,
+            "/api/users/oidc/idp/callback",
+            None,
+            Some(&callback),
+        )
+        .await;
+
+    (response.status(), response.json_from_body().await)
+}
+
+#[tokio::test]
+async fn oidc_login_registers_the_user_then_logs_them_in() {
+    let (app, _idp) = start_app_with_idp(MockIdpUser {
+        preferred_username: Some("oidc_username".into()),
+        ..MockIdpUser::new("subject", "oidc@email.com")
+    })
+    .await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (first_status, first_login) = login_with_idp(&app, &client).await;
+    let (second_status, second_login) = login_with_idp(&app, &client).await;
+
+    let token = second_login["token"].as_str().expect("missing token");
+    let identities: Vec<UserIdentity> = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me/identities",
+            Some(token),
+            None,
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(first_status, StatusCode::OK);
+    assert_eq!(second_status, StatusCode::OK);
+    assert_json_include!(
+        actual: &first_login,
+        expected: json!({
+            "user": { "username": "oidc_username", "email": "oidc@email.com" }
+        })
+    );
+    assert!(!first_login["user"]["email_verified_at"].is_null());
+    assert_eq!(first_login["user"]["id"], second_login["user"]["id"]);
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0].provider, "idp");
+    assert_eq!(identities[0].subject, "subject");
+    assert!(identities[0].last_login_at.is_some());
+}
+
+#[tokio::test]
+async fn oidc_login_sends_verification_email_for_unverified_email() {
+    let (app, _idp) = start_app_with_idp(MockIdpUser {
+        email_verified: false,
+        ..MockIdpUser::new("subject", "oidc@email.com")
+    })
+    .await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (status, login) = login_with_idp(&app, &client).await;
+
+    let email = app.mailer.last_email_to("oidc@email.com");
+
+    app.teardown().await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(login["user"]["email_verified_at"].is_null());
+    // Too short to be a username on its own
+    assert!(login["user"]["username"]
+        .as_str()
+        .unwrap()
+        .starts_with("oidc_"));
+    assert!(email.is_some());
+}
+
+#[tokio::test]
+async fn oidc_login_refuses_existing_email() {
+    let (app, _idp) = start_app_with_idp(MockIdpUser::new("subject", "test@email.com")).await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    let (status, _) = login_with_idp(&app, &client).await;
+
+    app.teardown().await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn oidc_login_refuses_existing_email_in_another_case() {
+    let (app, _idp) = start_app_with_idp(MockIdpUser::new("subject", "Test@Email.com")).await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    let (status, _) = login_with_idp(&app, &client).await;
+
+    app.teardown().await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn oidc_callback_rejects_unknown_or_reused_state() {
+    let (app, _idp) = start_app_with_idp(MockIdpUser::new("subject", "oidc@email.com")).await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let callback = authorize(&app, &client, "/api/users/oidc/idp", None).await;
+    let forged = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/oidc/idp/callback",
+            None,
+            Some(&json!({ "code": callback["code"], "state": "forged" })),
+        )
+        .await;
+    let first = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/oidc/idp/callback",
+            None,
+            Some(&callback),
+        )
+        .await;
+    let replayed = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/oidc/idp/callback",
+            None,
+            Some(&callback),
+        )
+        .await;
+    let unknown_provider = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/oidc/unknown/authorize",
+            None,
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(forged.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(replayed.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(unknown_provider.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn oidc_callback_rejects_id_token_for_another_client() {
+    let (app, idp) = start_app_with_idp(MockIdpUser::new("subject", "oidc@email.com")).await;
+    idp.set_audience(Some("another-client"));
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (status, _) = login_with_idp(&app, &client).await;
+
+    app.teardown().await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn linked_identity_logs_into_the_account_until_unlinked() {
+    let (app, idp) = start_app_with_idp(MockIdpUser::new("subject", "other@email.com")).await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    // A link state can not complete a login
+    let link_callback = authorize(&app, &client, "/api/users/oidc/idp/link", Some(&token)).await;
+    let as_login = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/oidc/idp/callback",
+            None,
+            Some(&link_callback),
+        )
+        .await;
+
+    let link_callback = authorize(&app, &client, "/api/users/oidc/idp/link", Some(&token)).await;
+    let link_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/oidc/idp/link/callback",
+            Some(&token),
+            Some(&link_callback),
+        )
+        .await;
+    let link_status = link_response.status();
+    let identity: UserIdentity = link_response.json_from_body().await;
+
+    let (login_status, login) = login_with_idp(&app, &client).await;
+
+    // The account of the provider is already linked
+    let link_callback = authorize(&app, &client, "/api/users/oidc/idp/link", Some(&token)).await;
+    let relink = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/oidc/idp/link/callback",
+            Some(&token),
+            Some(&link_callback),
+        )
+        .await;
+
+    let unlink = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/users/me/identities/{}", identity.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let unlink_again = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/users/me/identities/{}", identity.id),
+            Some(&token),
+            None,
+        )
+        .await;
+
+    // Logging in again registers a new account for the provider email
+    idp.set_user(MockIdpUser::new("subject", "other@email.com"));
+    let (new_login_status, new_login) = login_with_idp(&app, &client).await;
+
+    app.teardown().await;
+
+    assert_ne!(as_login.status(), StatusCode::OK);
+    assert_eq!(link_status, StatusCode::CREATED);
+    assert_eq!(identity.subject, "subject");
+    assert_eq!(login_status, StatusCode::OK);
+    assert_eq!(login["user"]["username"], "test_username");
+    assert_eq!(relink.status(), StatusCode::CONFLICT);
+    assert_eq!(unlink.status(), StatusCode::NO_CONTENT);
+    assert_eq!(unlink_again.status(), StatusCode::NOT_FOUND);
+    assert_eq!(new_login_status, StatusCode::OK);
+    assert_ne!(new_login["user"]["id"], login["user"]["id"]);
+}
@@ -0,0 +1,280 @@
+use assert_json_diff::assert_json_include;
+use hyper::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+/// Token of the reset email sent to `to`, which is sent in the background
+async fn reset_token_sent_to(app: &TestApp, to: &str) -> String {
+    let mut email = None;
+    for _ in 0..50 {
+        email = app
+            .mailer
+            .last_email_to(to)
+            .filter(|email| email.subject == "Reset your password");
+        if email.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let email = email.expect("no reset email sent");
+
+    email
+        .body
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("no token in email")
+        .to_string()
+}
+
+#[tokio::test]
+async fn reset_password_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    let forgot_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/forgot",
+            None,
+            Some(&json!({ "email": "test@email.com" })),
+        )
+        .await;
+
+    let token = reset_token_sent_to(&app, "test@email.com").await;
+
+    let reset_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/reset",
+            None,
+            Some(&json!({ "token": token, "new_password": "new_password" })),
+        )
+        .await;
+
+    let login_input = json!({
+        "username": "test_username",
+        "password": "new_password"
+    });
+
+    let login_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login",
+            None,
+            Some(&login_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(forgot_response.status(), StatusCode::ACCEPTED);
+    assert_eq!(reset_response.status(), StatusCode::NO_CONTENT);
+    assert!(login_response.status().is_success());
+}
+
+#[tokio::test]
+async fn reset_password_token_is_single_use() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/password/forgot",
+        None,
+        Some(&json!({ "email": "test@email.com" })),
+    )
+    .await;
+
+    let token = reset_token_sent_to(&app, "test@email.com").await;
+    let reset_input = json!({ "token": token, "new_password": "new_password" });
+
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/password/reset",
+        None,
+        Some(&reset_input),
+    )
+    .await;
+    let response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/reset",
+            None,
+            Some(&reset_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "invalid or expired reset token",
+        })
+    )
+}
+
+#[tokio::test]
+async fn reset_password_rejected_by_policy_keeps_token() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/password/forgot",
+        None,
+        Some(&json!({ "email": "test@email.com" })),
+    )
+    .await;
+
+    let token = reset_token_sent_to(&app, "test@email.com").await;
+
+    let rejected = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/reset",
+            None,
+            Some(&json!({ "token": token, "new_password": "password" })),
+        )
+        .await;
+    let rejected_status = rejected.status();
+    let rejected_response: Value = rejected.json_from_body().await;
+
+    let accepted = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/reset",
+            None,
+            Some(&json!({ "token": token, "new_password": "new_password" })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(rejected_status, StatusCode::BAD_REQUEST);
+    assert_json_include!(
+        actual: rejected_response,
+        expected: json!({
+            "message": "error validating fields",
+            "error": {
+                "fields": {
+                    "new_password": "is too easy to guess"
+                }
+            }
+        })
+    );
+    assert_eq!(accepted.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn reset_password_failing_to_hash_keeps_token() {
+    let mut app = TestApp::build();
+    // No hashing slot, every hash gives up after the queue timeout
+    app.config.app_settings.password_hashing.max_concurrency = 0;
+    app.config.app_settings.password_hashing.queue_timeout_ms = 10;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.insert_user("test_username", "test@email.com").await;
+
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/password/forgot",
+        None,
+        Some(&json!({ "email": "test@email.com" })),
+    )
+    .await;
+
+    let token = reset_token_sent_to(&app, "test@email.com").await;
+
+    let first = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/reset",
+            None,
+            Some(&json!({ "token": token, "new_password": "new_password" })),
+        )
+        .await;
+    // An invalid token would be rejected before hashing
+    let second = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/reset",
+            None,
+            Some(&json!({ "token": token, "new_password": "new_password" })),
+        )
+        .await;
+    let password_hash = app.password_hash("test_username").await;
+
+    app.teardown().await;
+
+    assert_eq!(first.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(second.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(password_hash, "unusable_password_hash");
+}
+
+#[tokio::test]
+async fn forgot_password_with_unknown_email() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/forgot",
+            None,
+            Some(&json!({ "email": "unknown@email.com" })),
+        )
+        .await;
+
+    // Leaving time for a background send
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let emails = app.mailer.emails();
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert!(emails.is_empty());
+}
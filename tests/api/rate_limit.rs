@@ -0,0 +1,295 @@
+use hyper::{header::RETRY_AFTER, Body, Method, Request, StatusCode};
+use serde_json::json;
+
+use crate::helpers::app::TestApp;
+
+#[tokio::test]
+async fn requests_are_limited_per_ip() {
+    let mut app = TestApp::build();
+    app.config.app_settings.rate_limit.per_ip.max_requests = 3;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let mut statuses = Vec::new();
+    for i in 0..4 {
+        let login_input = json!({
+            "login": format!("user_{}", i),
+            "password": "test_password"
+        });
+        let res = app
+            .request(
+                &client,
+                Method::POST,
+                "/api/users/login",
+                None,
+                Some(&login_input),
+            )
+            .await;
+        statuses.push((res.status(), res.headers().get(RETRY_AFTER).cloned()));
+    }
+
+    app.teardown().await;
+
+    assert!(statuses[..3]
+        .iter()
+        .all(|(status, _)| *status == StatusCode::NOT_ACCEPTABLE));
+    assert_eq!(statuses[3].0, StatusCode::TOO_MANY_REQUESTS);
+    assert!(statuses[3].1.is_some());
+}
+
+#[tokio::test]
+async fn requests_are_limited_per_account() {
+    let mut app = TestApp::build();
+    app.config.app_settings.rate_limit.per_account.max_requests = 2;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let forgot_input = json!({ "email": "Test@email.com" });
+        let res = app
+            .request(
+                &client,
+                Method::POST,
+                "/api/users/password/forgot",
+                None,
+                Some(&forgot_input),
+            )
+            .await;
+        statuses.push(res.status());
+    }
+
+    // Another account is not limited
+    let other_input = json!({ "email": "other@email.com" });
+    let other_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/password/forgot",
+            None,
+            Some(&other_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(
+        statuses,
+        vec![
+            StatusCode::ACCEPTED,
+            StatusCode::ACCEPTED,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+    assert_eq!(other_res.status(), StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn account_is_locked_after_failed_logins() {
+    let mut app = TestApp::build();
+    app.config.app_settings.rate_limit.lockout.threshold = 2;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    // Failures count towards the same account, by username or by email
+    let mut failures = Vec::new();
+    for login in ["test_username", "test@email.com"] {
+        let bad_input = json!({
+            "login": login,
+            "password": "wrong_password"
+        });
+        let res = app
+            .request(
+                &client,
+                Method::POST,
+                "/api/users/login",
+                None,
+                Some(&bad_input),
+            )
+            .await;
+        failures.push(res.status());
+    }
+
+    // Even the right password is rejected while the account is locked
+    let good_input = json!({
+        "login": "test@email.com",
+        "password": "test_password"
+    });
+    let locked_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login",
+            None,
+            Some(&good_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(
+        failures,
+        vec![StatusCode::NOT_ACCEPTABLE, StatusCode::NOT_ACCEPTABLE]
+    );
+    assert_eq!(locked_res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(locked_res.headers().get(RETRY_AFTER).is_some());
+}
+
+#[tokio::test]
+async fn wrong_confirmation_passwords_lock_the_account() {
+    let mut app = TestApp::build();
+    app.config.app_settings.rate_limit.lockout.threshold = 2;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    // A stolen access token is not enough to guess the password
+    let mut statuses = Vec::new();
+    for password in ["wrong_password", "wrong_password", "test_password"] {
+        let res = app
+            .request(
+                &client,
+                Method::DELETE,
+                "/api/users/me",
+                Some(&token),
+                Some(&json!({ "password": password })),
+            )
+            .await;
+        statuses.push(res.status());
+    }
+
+    // The lockout is shared with the login
+    let good_input = json!({
+        "login": "test_username",
+        "password": "test_password"
+    });
+    let login_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login",
+            None,
+            Some(&good_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(
+        statuses,
+        vec![
+            StatusCode::NOT_ACCEPTABLE,
+            StatusCode::NOT_ACCEPTABLE,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+    assert_eq!(login_res.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn successful_login_clears_failed_logins() {
+    let mut app = TestApp::build();
+    app.config.app_settings.rate_limit.lockout.threshold = 2;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.register_user(&client).await;
+
+    let bad_input = json!({
+        "login": "test_username",
+        "password": "wrong_password"
+    });
+    let good_input = json!({
+        "login": "test_username",
+        "password": "test_password"
+    });
+
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/login",
+        None,
+        Some(&bad_input),
+    )
+    .await;
+    app.request(
+        &client,
+        Method::POST,
+        "/api/users/login",
+        None,
+        Some(&good_input),
+    )
+    .await;
+    let after_reset = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login",
+            None,
+            Some(&bad_input),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(after_reset.status(), StatusCode::NOT_ACCEPTABLE);
+}
+
+#[tokio::test]
+async fn oversized_bodies_are_rejected() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let login_input = json!({
+        "login": "a".repeat(32 * 1024),
+        "password": "test_password"
+    });
+    let sized_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login",
+            None,
+            Some(&login_input),
+        )
+        .await;
+
+    // Without a content length, the body is cut once it is too large
+    let (mut sender, body) = Body::channel();
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/login"))
+        .header("Content-Type", "application/json")
+        .body(body)
+        .expect("could not create request");
+    let sending = tokio::spawn(async move {
+        for _ in 0..32 {
+            if sender.send_data(vec![b' '; 1024].into()).await.is_err() {
+                break;
+            }
+        }
+    });
+    let chunked_response = client.request(req).await.expect("could not send request");
+    sending.await.unwrap();
+
+    app.teardown().await;
+
+    assert_eq!(sized_response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(chunked_response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
@@ -0,0 +1,235 @@
+use assert_json_diff::assert_json_include;
+use hyper::{client::HttpConnector, Method, StatusCode};
+use lib::domain::{
+    recurrence::{Occurrence, TaskRecurrence},
+    task::TaggedTask,
+};
+use serde_json::{json, Value};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+async fn set_completed(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+    token: &str,
+    task: &TaggedTask,
+    completed: bool,
+) {
+    app.request(
+        client,
+        Method::PATCH,
+        &format!("/api/tasks/{}", task.task.id),
+        Some(token),
+        Some(&json!({ "completed": completed })),
+    )
+    .await;
+}
+
+async fn list_tasks(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+    token: &str,
+) -> Vec<TaggedTask> {
+    let response = app
+        .request(client, Method::GET, "/api/tasks", Some(token), None)
+        .await;
+
+    response.json_from_body().await
+}
+
+#[tokio::test]
+async fn completing_an_occurrence_creates_the_next_one() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let tag = app.create_tag(&client, &token, "chores").await;
+
+    // Thursday 09:00 in Paris, before clocks go back
+    let create_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tasks",
+            Some(&token),
+            Some(&json!({
+                "title": "take out the trash",
+                "due_at": "2022-10-27T07:00:00Z",
+                "tag_ids": [tag.id]
+            })),
+        )
+        .await;
+    let first: TaggedTask = create_response.json_from_body().await;
+
+    let set_response = app.request(&client, Method::PUT, &format!("/api/tasks/{}/recurrence", first.task.id), Some(&token), Some(&json!({ "rule": "RRULE:FREQ=WEEKLY;BYDAY=TH;COUNT=3", "timezone": "Europe/Paris" })))
+    .await;
+    let set_status = set_response.status();
+    let recurrence: TaskRecurrence = set_response.json_from_body().await;
+
+    let preview_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!(
+                "/api/tasks/{}/recurrence/occurrences?count=5",
+                first.task.id
+            ),
+            Some(&token),
+            None,
+        )
+        .await;
+    let preview: Vec<Occurrence> = preview_response.json_from_body().await;
+
+    // Completing the task again does not repeat it twice
+    set_completed(&app, &client, &token, &first, true).await;
+    set_completed(&app, &client, &token, &first, false).await;
+    set_completed(&app, &client, &token, &first, true).await;
+    let after_first = list_tasks(&app, &client, &token).await;
+
+    set_completed(&app, &client, &token, &after_first[1], true).await;
+    let after_second = list_tasks(&app, &client, &token).await;
+
+    // The series is over after 3 occurrences
+    set_completed(&app, &client, &token, &after_second[2], true).await;
+    let after_last = list_tasks(&app, &client, &token).await;
+
+    app.teardown().await;
+
+    assert_eq!(set_status, StatusCode::OK);
+    assert_eq!(recurrence.rule, "FREQ=WEEKLY;BYDAY=TH;COUNT=3");
+    assert_eq!(recurrence.starts_at, first.task.due_at.unwrap());
+    assert_eq!(
+        preview
+            .iter()
+            .map(|occurrence| (
+                occurrence.due_at.to_rfc3339(),
+                occurrence.local_due_at.to_string()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                "2022-11-03T08:00:00+00:00".to_string(),
+                "2022-11-03 09:00:00".to_string()
+            ),
+            (
+                "2022-11-10T08:00:00+00:00".to_string(),
+                "2022-11-10 09:00:00".to_string()
+            ),
+        ]
+    );
+    assert_eq!(after_first.len(), 2);
+    assert_eq!(preview[0].due_at, after_first[1].task.due_at.unwrap());
+    assert_eq!(after_first[1].task.title, "take out the trash");
+    assert_eq!(after_first[1].task.recurrence_id, Some(recurrence.id));
+    assert!(!after_first[1].task.completed);
+    assert_eq!(after_first[1].tags[0].id, tag.id);
+    assert_eq!(after_second.len(), 3);
+    assert_eq!(preview[1].due_at, after_second[2].task.due_at.unwrap());
+    assert_eq!(after_last.len(), 3);
+    assert!(after_last.iter().all(|task| task.task.completed));
+}
+
+#[tokio::test]
+async fn recurrence_needs_a_valid_rule_and_a_due_date() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let undated = app
+        .create_task(&client, &token, &json!({ "title": "someday" }))
+        .await;
+    let dated = app
+        .create_task(
+            &client,
+            &token,
+            &json!({ "title": "water plants", "due_at": "2022-12-01T08:00:00Z" }),
+        )
+        .await;
+
+    let invalid_response = app
+        .request(
+            &client,
+            Method::PUT,
+            &format!("/api/tasks/{}/recurrence", dated.id),
+            Some(&token),
+            Some(&json!({ "rule": "FREQ=HOURLY", "timezone": "Mars/Olympus_Mons" })),
+        )
+        .await;
+    let invalid_status = invalid_response.status();
+    let invalid_body: Value = invalid_response.json_from_body().await;
+
+    let undated_response = app
+        .request(
+            &client,
+            Method::PUT,
+            &format!("/api/tasks/{}/recurrence", undated.id),
+            Some(&token),
+            Some(&json!({ "rule": "FREQ=DAILY", "timezone": "UTC" })),
+        )
+        .await;
+    let undated_status = undated_response.status();
+    let undated_body: Value = undated_response.json_from_body().await;
+
+    app.request(
+        &client,
+        Method::PUT,
+        &format!("/api/tasks/{}/recurrence", dated.id),
+        Some(&token),
+        Some(&json!({ "rule": "FREQ=DAILY", "timezone": "UTC" })),
+    )
+    .await;
+    let delete_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/tasks/{}/recurrence", dated.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let get_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}/recurrence", dated.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    app.request(
+        &client,
+        Method::PATCH,
+        &format!("/api/tasks/{}", dated.id),
+        Some(&token),
+        Some(&json!({ "completed": true })),
+    )
+    .await;
+    let tasks = list_tasks(&app, &client, &token).await;
+
+    app.teardown().await;
+
+    assert_eq!(invalid_status, StatusCode::BAD_REQUEST);
+    assert_json_include!(
+        actual: invalid_body,
+        expected: json!({
+            "error": { "fields": {
+                "rule": "unsupported rule part `FREQ=HOURLY`",
+                "timezone": "unknown time zone",
+            } }
+        })
+    );
+    assert_eq!(undated_status, StatusCode::BAD_REQUEST);
+    assert_json_include!(
+        actual: undated_body,
+        expected: json!({ "message": "task has no due date" })
+    );
+    assert_eq!(delete_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(get_response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(tasks.len(), 2);
+}
@@ -0,0 +1,471 @@
+use assert_json_diff::assert_json_include;
+use axum::{http::HeaderMap, routing::post, Extension, Router};
+use hyper::{body::Bytes, Method, StatusCode};
+use lib::{
+    domain::{notification::Notification, reminder::TaskReminder},
+    notifier::signature,
+};
+use serde_json::{json, Value};
+use std::{
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+/// Requests received by the webhook, which fails the first `failures` ones
+#[derive(Default)]
+struct WebhookCalls {
+    failures: usize,
+    received: Vec<(HeaderMap, Bytes)>,
+}
+
+async fn webhook_handler(
+    headers: HeaderMap,
+    body: Bytes,
+    Extension(calls): Extension<Arc<Mutex<WebhookCalls>>>,
+) -> StatusCode {
+    let mut calls = calls.lock().unwrap();
+    calls.received.push((headers, body));
+
+    if calls.received.len() <= calls.failures {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
+
+fn spawn_webhook(calls: Arc<Mutex<WebhookCalls>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind listener");
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let router = Router::new()
+        .route("/hook", post(webhook_handler))
+        .layer(Extension(calls));
+
+    tokio::spawn(async move {
+        axum::Server::from_tcp(listener)
+            .expect("could not bind the tcp listener")
+            .serve(router.into_make_service())
+            .await
+            .expect("webhook server failed")
+    });
+
+    url
+}
+
+#[tokio::test]
+async fn create_reminder_converts_local_time_to_utc() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let task = app
+        .create_task(
+            &client,
+            &token,
+            &json!({ "title": "pay rent", "due_at": "2030-07-15T10:00:00Z" }),
+        )
+        .await;
+
+    let create_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/tasks/{}/reminders", task.id),
+            Some(&token),
+            Some(&json!({
+                "remind_at": "2030-07-14T09:00:00",
+                "timezone": "Europe/Paris",
+                "channel": "email"
+            })),
+        )
+        .await;
+    let create_status = create_response.status();
+    let reminder: TaskReminder = create_response.json_from_body().await;
+
+    let invalid_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/tasks/{}/reminders", task.id),
+            Some(&token),
+            Some(&json!({
+                "remind_at": "2020-07-14T09:00:00",
+                "timezone": "Mars/Olympus_Mons",
+                "channel": "webhook"
+            })),
+        )
+        .await;
+    let invalid_status = invalid_response.status();
+    let invalid_body: Value = invalid_response.json_from_body().await;
+
+    let past_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/tasks/{}/reminders", task.id),
+            Some(&token),
+            Some(&json!({
+                "remind_at": "2020-07-14T09:00:00",
+                "timezone": "Europe/Paris",
+                "channel": "in_app"
+            })),
+        )
+        .await;
+    let past_status = past_response.status();
+    let past_body: Value = past_response.json_from_body().await;
+
+    let list_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}/reminders", task.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let reminders: Vec<TaskReminder> = list_response.json_from_body().await;
+
+    let delete_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/tasks/{}/reminders/{}", task.id, reminder.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let delete_again_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/tasks/{}/reminders/{}", task.id, reminder.id),
+            Some(&token),
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(create_status, StatusCode::CREATED);
+    assert_eq!(
+        task.due_at.unwrap().to_rfc3339(),
+        "2030-07-15T10:00:00+00:00"
+    );
+    assert_eq!(reminder.remind_at.to_rfc3339(), "2030-07-14T07:00:00+00:00");
+    assert_eq!(reminder.timezone, "Europe/Paris");
+    assert_eq!(invalid_status, StatusCode::BAD_REQUEST);
+    assert_json_include!(
+        actual: invalid_body,
+        expected: json!({
+            "error": { "fields": {
+                "timezone": "unknown time zone",
+                "webhook_url": "required by the webhook channel",
+            } }
+        })
+    );
+    assert_eq!(past_status, StatusCode::BAD_REQUEST);
+    assert_json_include!(
+        actual: past_body,
+        expected: json!({
+            "error": { "fields": { "remind_at": "must be in the future" } }
+        })
+    );
+    assert_eq!(
+        reminders.iter().map(|r| r.id).collect::<Vec<_>>(),
+        vec![reminder.id]
+    );
+    assert_eq!(delete_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(delete_again_response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn create_reminder_rejects_private_and_insecure_webhooks() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let task = app
+        .create_task(&client, &token, &json!({ "title": "pay rent" }))
+        .await;
+
+    let mut statuses = Vec::new();
+    let mut bodies = Vec::new();
+    for webhook_url in [
+        "http://example.com/hook",
+        "https://localhost/hook",
+        "https://127.0.0.1/hook",
+        "https://10.0.0.1/hook",
+        "https://192.168.1.1/hook",
+        "https://169.254.169.254/latest/meta-data",
+        "https://[::1]/hook",
+        "https://[fd00:ec2::254]/hook",
+    ] {
+        let response = app
+            .request(
+                &client,
+                Method::POST,
+                &format!("/api/tasks/{}/reminders", task.id),
+                Some(&token),
+                Some(&json!({
+                    "remind_at": "2030-12-20T09:00:00",
+                    "timezone": "UTC",
+                    "channel": "webhook",
+                    "webhook_url": webhook_url
+                })),
+            )
+            .await;
+        statuses.push(response.status());
+        let body: Value = response.json_from_body().await;
+        bodies.push(body);
+    }
+
+    app.teardown().await;
+
+    assert!(statuses
+        .iter()
+        .all(|status| *status == StatusCode::BAD_REQUEST));
+    for body in bodies {
+        assert_json_include!(
+            actual: body,
+            expected: json!({
+                "error": { "fields": { "webhook_url": "must be a public https url" } }
+            })
+        );
+    }
+}
+
+#[tokio::test]
+async fn due_reminders_are_delivered_once_by_email_and_in_app() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let task = app
+        .create_task(&client, &token, &json!({ "title": "pay rent" }))
+        .await;
+    let done_task = app
+        .create_task(&client, &token, &json!({ "title": "water plants" }))
+        .await;
+
+    for (task_id, channel) in [
+        (task.id, "email"),
+        (task.id, "in_app"),
+        (done_task.id, "email"),
+    ] {
+        app.request(
+            &client,
+            Method::POST,
+            &format!("/api/tasks/{}/reminders", task_id),
+            Some(&token),
+            Some(&json!({
+                "remind_at": "2030-12-20T09:00:00",
+                "timezone": "Europe/Paris",
+                "channel": channel
+            })),
+        )
+        .await;
+    }
+    app.request(
+        &client,
+        Method::PATCH,
+        &format!("/api/tasks/{}", done_task.id),
+        Some(&token),
+        Some(&json!({ "completed": true })),
+    )
+    .await;
+
+    let not_due = app.dispatch_reminders().await;
+    app.make_reminders_due().await;
+    let delivered = app.dispatch_reminders().await;
+    let delivered_again = app.dispatch_reminders().await;
+
+    let list_response = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me/notifications",
+            Some(&token),
+            None,
+        )
+        .await;
+    let notifications: Vec<Notification> = list_response.json_from_body().await;
+
+    let read_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/users/me/notifications/{}/read", notifications[0].id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let read_notification: Notification = read_response.json_from_body().await;
+
+    // The verification email sent on registration is left out
+    let emails: Vec<_> = app
+        .mailer
+        .emails()
+        .into_iter()
+        .filter(|email| email.subject.starts_with("Reminder"))
+        .collect();
+
+    app.teardown().await;
+
+    assert_eq!(not_due, 0);
+    assert_eq!(delivered, 2);
+    assert_eq!(delivered_again, 0);
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, "test@email.com");
+    assert_eq!(emails[0].subject, "Reminder: pay rent");
+    assert!(emails[0].body.contains("2030-12-20 09:00 CET"));
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].title, "Reminder: pay rent");
+    assert!(notifications[0].read_at.is_none());
+    assert!(read_notification.read_at.is_some());
+}
+
+#[tokio::test]
+async fn in_app_reminder_delivered_twice_notifies_once() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let task = app
+        .create_task(&client, &token, &json!({ "title": "pay rent" }))
+        .await;
+    app.request(
+        &client,
+        Method::POST,
+        &format!("/api/tasks/{}/reminders", task.id),
+        Some(&token),
+        Some(&json!({
+            "remind_at": "2030-12-20T09:00:00",
+            "timezone": "UTC",
+            "channel": "in_app"
+        })),
+    )
+    .await;
+
+    // The worker stopped before recording the delivery, the reminder is
+    // delivered again once its lease is over
+    app.make_reminders_due().await;
+    app.dispatch_reminders().await;
+    app.reset_reminder_deliveries().await;
+    app.make_reminders_due().await;
+    let redelivered = app.dispatch_reminders().await;
+
+    let list_response = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/users/me/notifications",
+            Some(&token),
+            None,
+        )
+        .await;
+    let notifications: Vec<Notification> = list_response.json_from_body().await;
+
+    app.teardown().await;
+
+    assert_eq!(redelivered, 1);
+    assert_eq!(notifications.len(), 1);
+}
+
+#[tokio::test]
+async fn webhook_reminders_are_signed_and_retried_with_the_same_key() {
+    let mut app = TestApp::build();
+    app.config.app_settings.reminders.webhook_secret = Some("webhook-secret".into());
+    // The mock webhook listens on localhost
+    app.config.app_settings.reminders.allow_private_webhooks = true;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let calls = Arc::new(Mutex::new(WebhookCalls {
+        failures: 1,
+        ..Default::default()
+    }));
+    let webhook_url = spawn_webhook(calls.clone());
+
+    let token = app.register_user(&client).await;
+    let task = app
+        .create_task(&client, &token, &json!({ "title": "pay rent" }))
+        .await;
+    let create_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/tasks/{}/reminders", task.id),
+            Some(&token),
+            Some(&json!({
+                "remind_at": "2030-12-20T09:00:00",
+                "timezone": "UTC",
+                "channel": "webhook",
+                "webhook_url": webhook_url
+            })),
+        )
+        .await;
+    let reminder: TaskReminder = create_response.json_from_body().await;
+
+    app.make_reminders_due().await;
+    let failed = app.dispatch_reminders().await;
+    let list_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}/reminders", task.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let failed_reminders: Vec<TaskReminder> = list_response.json_from_body().await;
+
+    app.make_reminders_due().await;
+    let retried = app.dispatch_reminders().await;
+
+    app.teardown().await;
+
+    let received = calls.lock().unwrap().received.clone();
+    let payload: Value = serde_json::from_slice(&received[1].1).unwrap();
+
+    assert_eq!(failed, 0);
+    assert_eq!(failed_reminders[0].attempts, 1);
+    assert_eq!(
+        failed_reminders[0].last_error.as_deref(),
+        Some("webhook answered 500 Internal Server Error")
+    );
+    assert_eq!(retried, 1);
+    assert_eq!(received.len(), 2);
+    for (headers, body) in &received {
+        assert_eq!(
+            headers["idempotency-key"].to_str().unwrap(),
+            reminder.id.to_string()
+        );
+        assert_eq!(
+            headers["x-signature"].to_str().unwrap(),
+            signature("webhook-secret", body)
+        );
+    }
+    assert_json_include!(
+        actual: payload,
+        expected: json!({
+            "reminder_id": reminder.id,
+            "task_id": task.id,
+            "title": "Reminder: pay rent",
+        })
+    );
+}
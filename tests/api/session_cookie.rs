@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use hyper::{
+    client::HttpConnector,
+    header::{COOKIE, SET_COOKIE},
+    Body, Method, Response, StatusCode,
+};
+use lib::configuration::SessionTransport;
+use serde_json::{json, Value};
+
+use crate::helpers::{
+    app::{user_input, TestApp},
+    ParseJson,
+};
+
+/// Set-Cookie headers of a response, by cookie name
+fn set_cookies(response: &Response<Body>) -> HashMap<String, String> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| {
+            let name = value.split('=').next()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Value of a cookie, from its Set-Cookie header
+fn cookie_value(set_cookie: &str) -> String {
+    set_cookie
+        .split(';')
+        .next()
+        .and_then(|pair| pair.split_once('='))
+        .map(|(_, value)| value.to_string())
+        .unwrap_or_default()
+}
+
+async fn send(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+    method: Method,
+    path: &str,
+    cookies: &[(&str, &str)],
+    csrf_token: Option<&str>,
+    input: &Value,
+) -> Response<Body> {
+    let cookie = cookies
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let mut req = app
+        .request_builder(method, path, None)
+        .header(COOKIE, cookie);
+    if let Some(csrf_token) = csrf_token {
+        req = req.header("X-CSRF-Token", csrf_token);
+    }
+    let req = req
+        .body(Body::from(input.to_string()))
+        .expect("could not create request");
+
+    client.request(req).await.expect("could not send request")
+}
+
+async fn register(app: &TestApp, client: &hyper::Client<HttpConnector>) -> Response<Body> {
+    app.request(
+        client,
+        Method::POST,
+        "/api/users/register",
+        None,
+        Some(&user_input()),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn register_sets_session_cookies() {
+    let mut app = TestApp::build();
+    app.config.app_settings.session_transport = SessionTransport::Cookie;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let response = register(&app, &client).await;
+    let cookies = set_cookies(&response);
+    let body: Value = response.json_from_body().await;
+
+    app.teardown().await;
+
+    let access_cookie = &cookies["access_token"];
+    assert!(access_cookie.contains("HttpOnly"));
+    assert!(access_cookie.contains("Secure"));
+    assert!(access_cookie.contains("SameSite=Lax"));
+    assert!(access_cookie.contains("Path=/api"));
+    assert!(cookies["refresh_token"].contains("Path=/api/users/token/refresh"));
+    assert!(!cookies["csrf_token"].contains("HttpOnly"));
+    assert!(body.get("token").is_none());
+    assert!(body.get("refresh_token").is_none());
+    assert_eq!(body["user"]["username"], "test_username");
+}
+
+#[tokio::test]
+async fn cookie_session_requires_csrf_token_for_writes() {
+    let mut app = TestApp::build();
+    app.config.app_settings.session_transport = SessionTransport::Cookie;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let cookies = set_cookies(&register(&app, &client).await);
+    let access_token = cookie_value(&cookies["access_token"]);
+    let csrf_token = cookie_value(&cookies["csrf_token"]);
+    let session_cookies = [
+        ("access_token", access_token.as_str()),
+        ("csrf_token", csrf_token.as_str()),
+    ];
+    let task_input = json!({ "title": "title", "description": "description" });
+
+    let me_res = send(
+        &app,
+        &client,
+        Method::GET,
+        "/api/users/me",
+        &session_cookies,
+        None,
+        &json!({}),
+    )
+    .await;
+    let without_csrf_res = send(
+        &app,
+        &client,
+        Method::POST,
+        "/api/tasks",
+        &session_cookies,
+        None,
+        &task_input,
+    )
+    .await;
+    let wrong_csrf_res = send(
+        &app,
+        &client,
+        Method::POST,
+        "/api/tasks",
+        &session_cookies,
+        Some("wrong"),
+        &task_input,
+    )
+    .await;
+    let with_csrf_res = send(
+        &app,
+        &client,
+        Method::POST,
+        "/api/tasks",
+        &session_cookies,
+        Some(&csrf_token),
+        &task_input,
+    )
+    .await;
+
+    app.teardown().await;
+
+    assert_eq!(me_res.status(), StatusCode::OK);
+    assert_eq!(without_csrf_res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(wrong_csrf_res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(with_csrf_res.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn refresh_and_logout_with_cookies() {
+    let mut app = TestApp::build();
+    app.config.app_settings.session_transport = SessionTransport::Cookie;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let cookies = set_cookies(&register(&app, &client).await);
+    let refresh_token = cookie_value(&cookies["refresh_token"]);
+    let csrf_token = cookie_value(&cookies["csrf_token"]);
+    let refresh_cookies = [
+        ("refresh_token", refresh_token.as_str()),
+        ("csrf_token", csrf_token.as_str()),
+    ];
+
+    let without_csrf_res = send(
+        &app,
+        &client,
+        Method::POST,
+        "/api/users/token/refresh",
+        &refresh_cookies,
+        None,
+        &json!({}),
+    )
+    .await;
+    let refresh_res = send(
+        &app,
+        &client,
+        Method::POST,
+        "/api/users/token/refresh",
+        &refresh_cookies,
+        Some(&csrf_token),
+        &json!({}),
+    )
+    .await;
+    let refresh_status = refresh_res.status();
+    let refreshed = set_cookies(&refresh_res);
+
+    let access_token = cookie_value(&refreshed["access_token"]);
+    let csrf_token = cookie_value(&refreshed["csrf_token"]);
+    let logout_res = send(
+        &app,
+        &client,
+        Method::POST,
+        "/api/users/logout",
+        &[
+            ("access_token", access_token.as_str()),
+            ("csrf_token", csrf_token.as_str()),
+        ],
+        Some(&csrf_token),
+        &json!({}),
+    )
+    .await;
+    let cleared = set_cookies(&logout_res);
+
+    app.teardown().await;
+
+    assert_eq!(without_csrf_res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(refresh_status, StatusCode::OK);
+    assert_ne!(cookie_value(&refreshed["refresh_token"]), refresh_token);
+    assert_eq!(logout_res.status(), StatusCode::NO_CONTENT);
+    assert!(["access_token", "refresh_token", "csrf_token"]
+        .iter()
+        .all(|name| cleared[*name].contains("Max-Age=0")));
+}
+
+#[tokio::test]
+async fn header_transport_sets_no_cookies() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let response = register(&app, &client).await;
+    let cookies = set_cookies(&response);
+    let body: Value = response.json_from_body().await;
+
+    app.teardown().await;
+
+    assert!(cookies.is_empty());
+    assert!(body["token"].is_string());
+}
@@ -5,6 +5,7 @@ use serde_json::{json, Value};
 use crate::helpers::{app::TestApp, ParseJson};
 use assert_json_diff::assert_json_include;
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub struct StatusResponse {
     pub status: String,
@@ -0,0 +1,224 @@
+use assert_json_diff::assert_json_include;
+use hyper::{Method, StatusCode};
+use lib::domain::task::TaggedTask;
+use serde_json::{json, Value};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+fn tag_names(task: &TaggedTask) -> Vec<&str> {
+    task.tags.iter().map(|tag| tag.name.as_str()).collect()
+}
+
+#[tokio::test]
+async fn attach_and_detach_tags_on_task_create_and_update() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let work = app.create_tag(&client, &token, "work").await;
+    let home = app.create_tag(&client, &token, "home").await;
+
+    let create_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tasks",
+            Some(&token),
+            Some(&json!({ "title": "buy milk", "tag_ids": [work.id, home.id] })),
+        )
+        .await;
+    let created_task: TaggedTask = create_response.json_from_body().await;
+
+    let retag_response = app
+        .request(
+            &client,
+            Method::PATCH,
+            &format!("/api/tasks/{}", created_task.task.id),
+            Some(&token),
+            Some(&json!({ "tag_ids": [work.id] })),
+        )
+        .await;
+    let retagged_task: TaggedTask = retag_response.json_from_body().await;
+
+    // Tags are kept when `tag_ids` is left out
+    let update_response = app
+        .request(
+            &client,
+            Method::PATCH,
+            &format!("/api/tasks/{}", created_task.task.id),
+            Some(&token),
+            Some(&json!({ "completed": true })),
+        )
+        .await;
+    let updated_task: TaggedTask = update_response.json_from_body().await;
+
+    app.request(
+        &client,
+        Method::DELETE,
+        &format!("/api/tags/{}", work.id),
+        Some(&token),
+        None,
+    )
+    .await;
+    let get_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}", created_task.task.id),
+            Some(&token),
+            None,
+        )
+        .await;
+    let untagged_task: TaggedTask = get_response.json_from_body().await;
+
+    app.teardown().await;
+
+    assert_eq!(tag_names(&created_task), vec!["home", "work"]);
+    assert_eq!(tag_names(&retagged_task), vec!["work"]);
+    assert_eq!(tag_names(&updated_task), vec!["work"]);
+    assert!(updated_task.task.completed);
+    assert!(untagged_task.tags.is_empty());
+}
+
+#[tokio::test]
+async fn list_tasks_filtered_by_any_or_all_tags() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let work = app.create_tag(&client, &token, "work").await;
+    let urgent = app.create_tag(&client, &token, "urgent").await;
+
+    for (title, tag_ids) in [
+        ("report", vec![work.id]),
+        ("deadline", vec![work.id, urgent.id]),
+        ("dentist", vec![urgent.id]),
+        ("groceries", vec![]),
+    ] {
+        app.create_task(
+            &client,
+            &token,
+            &json!({ "title": title, "tag_ids": tag_ids }),
+        )
+        .await;
+    }
+
+    let mut titles = Vec::new();
+    for query in [
+        format!("tags={}", work.id),
+        format!("tags={},{}", work.id, urgent.id),
+        format!("tags={},{}&match=all", work.id, urgent.id),
+        String::new(),
+    ] {
+        let response = app
+            .request(
+                &client,
+                Method::GET,
+                &format!("/api/tasks?{}", query),
+                Some(&token),
+                None,
+            )
+            .await;
+        let tasks: Vec<TaggedTask> = response.json_from_body().await;
+        titles.push(
+            tasks
+                .into_iter()
+                .map(|task| task.task.title)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    app.teardown().await;
+
+    assert_eq!(
+        titles,
+        vec![
+            vec!["report", "deadline"],
+            vec!["report", "deadline", "dentist"],
+            vec!["deadline"],
+            vec!["report", "deadline", "dentist", "groceries"],
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tags_are_scoped_to_their_owner() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let owner_token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "owner@email.com",
+                "username": "owner_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+    let other_token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "other@email.com",
+                "username": "other_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+    let tag = app.create_tag(&client, &owner_token, "work").await;
+
+    let duplicate_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tags",
+            Some(&owner_token),
+            Some(&json!({ "name": "work" })),
+        )
+        .await;
+    let get_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tags/{}", tag.id),
+            Some(&other_token),
+            None,
+        )
+        .await;
+    let attach_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tasks",
+            Some(&other_token),
+            Some(&json!({ "title": "buy milk", "tag_ids": [tag.id] })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(duplicate_response.status(), StatusCode::CONFLICT);
+    assert_eq!(get_response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(attach_response.status(), StatusCode::NOT_FOUND);
+
+    // Getting json data
+
+    let api_response: Value = attach_response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "tag not found",
+        })
+    );
+}
@@ -0,0 +1,583 @@
+use assert_json_diff::assert_json_include;
+use hyper::{Method, StatusCode};
+use lib::domain::task::{Task, TaskTree};
+use serde_json::{json, Value};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+#[tokio::test]
+async fn create_and_list_tasks_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let task_input = json!({
+        "title": "buy milk",
+        "description": "semi-skimmed"
+    });
+
+    let create_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tasks",
+            Some(&token),
+            Some(&task_input),
+        )
+        .await;
+
+    let list_response = app
+        .request(&client, Method::GET, "/api/tasks", Some(&token), None)
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(create_response.status(), StatusCode::CREATED);
+    assert!(list_response.status().is_success());
+
+    // Getting json data
+
+    let created_task: Task = create_response.json_from_body().await;
+    let tasks: Vec<Task> = list_response.json_from_body().await;
+
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].id, created_task.id);
+    assert_eq!(tasks[0].title, "buy milk");
+    assert!(!tasks[0].completed);
+}
+
+#[tokio::test]
+async fn create_task_with_validation_errors() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tasks",
+            Some(&token),
+            Some(&json!({ "title": "" })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+        "message": "error validating fields",
+        "error": {
+            "fields": {
+                "title": "invalid length"
+            }
+        }})
+    );
+}
+
+#[tokio::test]
+async fn update_and_delete_task_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let task = app
+        .create_task(&client, &token, &json!({ "title": "buy milk" }))
+        .await;
+
+    let update_response = app
+        .request(
+            &client,
+            Method::PATCH,
+            &format!("/api/tasks/{}", task.id),
+            Some(&token),
+            Some(&json!({ "completed": true })),
+        )
+        .await;
+
+    let delete_response = app
+        .request(
+            &client,
+            Method::DELETE,
+            &format!("/api/tasks/{}", task.id),
+            Some(&token),
+            None,
+        )
+        .await;
+
+    let get_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}", task.id),
+            Some(&token),
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert!(update_response.status().is_success());
+    assert_eq!(delete_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(get_response.status(), StatusCode::NOT_FOUND);
+
+    // Getting json data
+
+    let updated_task: Task = update_response.json_from_body().await;
+
+    assert!(updated_task.completed);
+    assert_eq!(updated_task.title, task.title);
+}
+
+#[tokio::test]
+async fn update_task_clears_due_date_and_description() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let task = app
+        .create_task(
+            &client,
+            &token,
+            &json!({
+                "title": "buy milk",
+                "description": "semi-skimmed",
+                "due_at": "2030-01-01T10:00:00Z"
+            }),
+        )
+        .await;
+    let path = format!("/api/tasks/{}", task.id);
+
+    // Missing fields are kept
+    let kept: Task = app
+        .request(
+            &client,
+            Method::PATCH,
+            &path,
+            Some(&token),
+            Some(&json!({})),
+        )
+        .await
+        .json_from_body()
+        .await;
+    let cleared: Task = app
+        .request(
+            &client,
+            Method::PATCH,
+            &path,
+            Some(&token),
+            Some(&json!({ "description": null, "due_at": null })),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(kept.description.as_deref(), Some("semi-skimmed"));
+    assert!(kept.due_at.is_some());
+    assert!(cleared.description.is_none());
+    assert!(cleared.due_at.is_none());
+}
+
+#[tokio::test]
+async fn get_task_of_another_user() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let owner_token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "owner@email.com",
+                "username": "owner_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+    let other_token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "other@email.com",
+                "username": "other_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+
+    let task = app
+        .create_task(&client, &owner_token, &json!({ "title": "secret" }))
+        .await;
+
+    let response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}", task.id),
+            Some(&other_token),
+            None,
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "task not found",
+        })
+    )
+}
+
+#[tokio::test]
+async fn list_tasks_without_token() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let response = app
+        .request(&client, Method::GET, "/api/tasks", None, None)
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn get_task_returns_subtask_tree_with_progress() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    let trip = app
+        .create_task(&client, &token, &json!({ "title": "trip" }))
+        .await;
+    let packing = app
+        .create_task(
+            &client,
+            &token,
+            &json!({ "title": "packing", "parent_id": trip.id }),
+        )
+        .await;
+    let tickets = app
+        .create_task(
+            &client,
+            &token,
+            &json!({ "title": "tickets", "parent_id": trip.id }),
+        )
+        .await;
+    app.create_task(
+        &client,
+        &token,
+        &json!({ "title": "passport", "parent_id": packing.id }),
+    )
+    .await;
+
+    app.request(
+        &client,
+        Method::PATCH,
+        &format!("/api/tasks/{}", tickets.id),
+        Some(&token),
+        Some(&json!({ "completed": true })),
+    )
+    .await;
+    let before_cascade: TaskTree = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}", trip.id),
+            Some(&token),
+            Some(&json!({})),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    app.request(
+        &client,
+        Method::PATCH,
+        &format!("/api/tasks/{}?subtasks=cascade", trip.id),
+        Some(&token),
+        Some(&json!({ "completed": true })),
+    )
+    .await;
+    let after_cascade: TaskTree = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/tasks/{}", trip.id),
+            Some(&token),
+            Some(&json!({})),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    app.teardown().await;
+
+    let titles: Vec<&str> = before_cascade
+        .subtasks
+        .iter()
+        .map(|subtask| subtask.task.task.title.as_str())
+        .collect();
+    assert_eq!(titles, vec!["packing", "tickets"]);
+    assert_eq!(
+        before_cascade.subtasks[0].subtasks[0].task.task.title,
+        "passport"
+    );
+    assert_eq!(before_cascade.progress.map(|p| p.percent), Some(50));
+    assert!(before_cascade.subtasks[1].progress.is_none());
+
+    assert!(after_cascade.task.task.completed);
+    assert_eq!(after_cascade.progress.map(|p| p.percent), Some(100));
+    assert!(after_cascade.subtasks[0].subtasks[0].task.task.completed);
+}
+
+#[tokio::test]
+async fn set_task_parent_rejects_cycles_and_deep_nesting() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+
+    // A chain of 4 tasks reaches the maximum depth
+    let mut chain: Vec<Task> = Vec::new();
+    for title in ["level 0", "level 1", "level 2", "level 3"] {
+        let parent_id = chain.last().map(|task| task.id);
+        let task = app
+            .create_task(
+                &client,
+                &token,
+                &json!({ "title": title, "parent_id": parent_id }),
+            )
+            .await;
+        chain.push(task);
+    }
+    let other = app
+        .create_task(&client, &token, &json!({ "title": "other" }))
+        .await;
+    let other_child = app
+        .create_task(
+            &client,
+            &token,
+            &json!({ "title": "other child", "parent_id": other.id }),
+        )
+        .await;
+
+    let too_deep_response = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/tasks",
+            Some(&token),
+            Some(&json!({ "title": "level 4", "parent_id": chain[3].id })),
+        )
+        .await;
+    let cycle_response = app
+        .request(
+            &client,
+            Method::PUT,
+            &format!("/api/tasks/{}/parent", chain[0].id),
+            Some(&token),
+            Some(&json!({ "parent_id": chain[2].id })),
+        )
+        .await;
+    // `level 3` would end up at depth 4 along with its ancestors
+    let subtree_too_deep_response = app
+        .request(
+            &client,
+            Method::PUT,
+            &format!("/api/tasks/{}/parent", chain[1].id),
+            Some(&token),
+            Some(&json!({ "parent_id": other_child.id })),
+        )
+        .await;
+    let move_response = app
+        .request(
+            &client,
+            Method::PUT,
+            &format!("/api/tasks/{}/parent", chain[2].id),
+            Some(&token),
+            Some(&json!({ "parent_id": other.id })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(too_deep_response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(cycle_response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(subtree_too_deep_response.status(), StatusCode::BAD_REQUEST);
+    assert!(move_response.status().is_success());
+
+    // Getting json data
+
+    let api_response: Value = cycle_response.json_from_body().await;
+    let moved_task: Task = move_response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "a task cannot be a subtask of itself or of one of its subtasks",
+        })
+    );
+    assert_eq!(moved_task.parent_id, Some(other.id));
+}
+
+#[tokio::test]
+async fn move_task_after_or_before_another_one() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app.register_user(&client).await;
+    let list = app
+        .create_list(&client, &token, &json!({ "name": "groceries" }))
+        .await;
+    let mut tasks = Vec::new();
+    for title in ["a", "b", "c"] {
+        let task = app
+            .create_task(&client, &token, &json!({ "title": title }))
+            .await;
+        tasks.push(task);
+    }
+    let in_list = app
+        .create_task(
+            &client,
+            &token,
+            &json!({ "title": "d", "list_id": list.id }),
+        )
+        .await;
+
+    // c, a, b in the inbox, then a goes to the end of the list and b right
+    // before d, in the list of its anchor
+    let moves = [
+        (&tasks[2], json!({ "before": tasks[0].id })),
+        (&tasks[0], json!({ "list_id": list.id })),
+        (&tasks[1], json!({ "before": in_list.id })),
+    ];
+    let mut statuses = Vec::new();
+    for (task, input) in moves {
+        let response = app
+            .request(
+                &client,
+                Method::POST,
+                &format!("/api/tasks/{}/move", task.id),
+                Some(&token),
+                Some(&input),
+            )
+            .await;
+        statuses.push(response.status());
+    }
+
+    let both_anchors_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/tasks/{}/move", tasks[0].id),
+            Some(&token),
+            Some(&json!({ "after": tasks[1].id, "before": in_list.id })),
+        )
+        .await;
+    let itself_response = app
+        .request(
+            &client,
+            Method::POST,
+            &format!("/api/tasks/{}/move", tasks[0].id),
+            Some(&token),
+            Some(&json!({ "after": tasks[0].id })),
+        )
+        .await;
+
+    let inbox_response = app
+        .request(
+            &client,
+            Method::GET,
+            "/api/tasks",
+            Some(&token),
+            Some(&json!({})),
+        )
+        .await;
+    let list_response = app
+        .request(
+            &client,
+            Method::GET,
+            &format!("/api/lists/{}/tasks", list.id),
+            Some(&token),
+            Some(&json!({})),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert!(statuses.iter().all(|status| status.is_success()));
+    assert_eq!(both_anchors_response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(itself_response.status(), StatusCode::BAD_REQUEST);
+
+    // Getting json data
+
+    let api_response: Value = itself_response.json_from_body().await;
+    let all_tasks: Vec<Task> = inbox_response.json_from_body().await;
+    let list_tasks: Vec<Task> = list_response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "a task moves either after or before another task of the destination list",
+        })
+    );
+    assert_eq!(
+        all_tasks
+            .iter()
+            .filter(|task| task.list_id.is_none())
+            .map(|task| task.id)
+            .collect::<Vec<_>>(),
+        vec![tasks[2].id]
+    );
+    assert_eq!(
+        list_tasks.iter().map(|task| task.id).collect::<Vec<_>>(),
+        vec![tasks[1].id, in_list.id, tasks[0].id]
+    );
+}
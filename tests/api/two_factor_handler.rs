@@ -0,0 +1,332 @@
+use chrono::{Duration, Utc};
+use hyper::{client::HttpConnector, Body, Method, Request, Response, StatusCode};
+use lib::{
+    domain::two_factor::{RecoveryCodes, TwoFactorRequired, TwoFactorSetup},
+    utils::totp::code_at,
+};
+use serde_json::{json, Value};
+
+use crate::helpers::{app::TestApp, ParseJson};
+
+/// Registers a user and enables 2FA, returning an access token, the secret and
+/// the recovery codes
+async fn create_user_with_two_factor(
+    app: &TestApp,
+    client: &hyper::Client<HttpConnector>,
+) -> (String, String, Vec<String>) {
+    let token = app.register_user(client).await;
+
+    let setup: TwoFactorSetup = app
+        .request(
+            client,
+            Method::POST,
+            "/api/users/me/2fa/setup",
+            Some(&token),
+            Some(&json!({})),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    let code = code_at(&setup.secret, Utc::now()).unwrap();
+    let codes: RecoveryCodes = app
+        .request(
+            client,
+            Method::POST,
+            "/api/users/me/2fa/confirm",
+            Some(&token),
+            Some(&json!({ "code": code })),
+        )
+        .await
+        .json_from_body()
+        .await;
+
+    (token, setup.secret, codes.recovery_codes)
+}
+
+async fn start_login(app: &TestApp, client: &hyper::Client<HttpConnector>) -> Response<Body> {
+    let login_input = json!({
+        "login": "test_username",
+        "password": "test_password"
+    });
+
+    app.request(
+        client,
+        Method::POST,
+        "/api/users/login",
+        None,
+        Some(&login_input),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn login_with_two_factor_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (_, secret, recovery_codes) = create_user_with_two_factor(&app, &client).await;
+
+    let login_res = start_login(&app, &client).await;
+    let login_status = login_res.status();
+    let login_body: Value = login_res.json_from_body().await;
+
+    // The code used to confirm the setup cannot be replayed, using the next one
+    let code = code_at(&secret, Utc::now() + Duration::seconds(30)).unwrap();
+    let res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login/2fa",
+            None,
+            Some(&json!({ "challenge_token": login_body["challenge_token"], "code": code })),
+        )
+        .await;
+    let status = res.status();
+    let body: Value = res.json_from_body().await;
+
+    app.teardown().await;
+
+    assert_eq!(recovery_codes.len(), 10);
+    assert_eq!(login_status, StatusCode::ACCEPTED);
+    assert!(login_body.get("token").is_none());
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+    assert_eq!(body["user"]["username"], "test_username");
+}
+
+#[tokio::test]
+async fn two_factor_secret_is_not_stored_in_plaintext() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (_, secret, _) = create_user_with_two_factor(&app, &client).await;
+    let stored_secret = app.stored_totp_secret("test_username").await;
+
+    app.teardown().await;
+
+    assert!(!stored_secret.contains(&secret));
+}
+
+#[tokio::test]
+async fn login_with_two_factor_rejects_wrong_and_replayed_codes() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (_, secret, _) = create_user_with_two_factor(&app, &client).await;
+
+    let challenge: TwoFactorRequired = start_login(&app, &client).await.json_from_body().await;
+
+    let wrong_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login/2fa",
+            None,
+            Some(&json!({ "challenge_token": challenge.challenge_token, "code": "000000" })),
+        )
+        .await;
+
+    // Code already used to confirm the setup
+    let replayed_code = code_at(&secret, Utc::now()).unwrap();
+    let replayed_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login/2fa",
+            None,
+            Some(&json!({ "challenge_token": challenge.challenge_token, "code": replayed_code })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(wrong_res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(replayed_res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn wrong_two_factor_codes_lock_the_account_across_challenges() {
+    let mut app = TestApp::build();
+    app.config.app_settings.rate_limit.lockout.threshold = 2;
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (_, secret, _) = create_user_with_two_factor(&app, &client).await;
+
+    let mut challenges: Vec<TwoFactorRequired> = Vec::new();
+    for _ in 0..3 {
+        challenges.push(start_login(&app, &client).await.json_from_body().await);
+    }
+
+    // Each challenge allows several attempts, the account is still locked once
+    // the failures across challenges reach the threshold
+    let mut statuses = Vec::new();
+    for challenge in &challenges[..2] {
+        let res = app
+            .request(
+                &client,
+                Method::POST,
+                "/api/users/login/2fa",
+                None,
+                Some(&json!({ "challenge_token": challenge.challenge_token, "code": "000000" })),
+            )
+            .await;
+        statuses.push(res.status());
+    }
+
+    let code = code_at(&secret, Utc::now() + Duration::seconds(30)).unwrap();
+    let locked_code_res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login/2fa",
+            None,
+            Some(&json!({ "challenge_token": challenges[2].challenge_token, "code": code })),
+        )
+        .await;
+    let locked_login_res = start_login(&app, &client).await;
+
+    app.teardown().await;
+
+    assert_eq!(
+        statuses,
+        vec![StatusCode::BAD_REQUEST, StatusCode::BAD_REQUEST]
+    );
+    assert_eq!(locked_code_res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(locked_login_res.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn login_with_recovery_code_only_once() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (_, _, recovery_codes) = create_user_with_two_factor(&app, &client).await;
+
+    let mut statuses = Vec::new();
+    for _ in 0..2 {
+        let challenge: TwoFactorRequired = start_login(&app, &client).await.json_from_body().await;
+
+        let res = app
+            .request(
+                &client,
+                Method::POST,
+                "/api/users/login/2fa",
+                None,
+                Some(&json!({
+                    "challenge_token": challenge.challenge_token,
+                    "recovery_code": recovery_codes[0]
+                })),
+            )
+            .await;
+        statuses.push(res.status());
+    }
+
+    app.teardown().await;
+
+    assert_eq!(statuses, vec![StatusCode::OK, StatusCode::BAD_REQUEST]);
+}
+
+#[tokio::test]
+async fn login_with_unknown_challenge() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/login/2fa",
+            None,
+            Some(&json!({ "challenge_token": "unknown", "code": "123456" })),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn disable_two_factor_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (token, _, _) = create_user_with_two_factor(&app, &client).await;
+
+    let disable = |password: &str| {
+        Request::builder()
+            .method(Method::DELETE)
+            .uri(app.get_http_uri("/api/users/me/2fa"))
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::from(json!({ "password": password }).to_string()))
+            .expect("could not create request")
+    };
+
+    let wrong_password_res = client
+        .request(disable("wrong_password"))
+        .await
+        .expect("could not send request");
+    let res = client
+        .request(disable("test_password"))
+        .await
+        .expect("could not send request");
+
+    let login_res = start_login(&app, &client).await;
+    let login_status = login_res.status();
+    let login_body: Value = login_res.json_from_body().await;
+
+    app.teardown().await;
+
+    assert_eq!(wrong_password_res.status(), StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert_eq!(login_status, StatusCode::OK);
+    assert!(login_body["token"].is_string());
+}
+
+#[tokio::test]
+async fn setup_two_factor_when_already_enabled() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let (token, _, _) = create_user_with_two_factor(&app, &client).await;
+
+    let res = app
+        .request(
+            &client,
+            Method::POST,
+            "/api/users/me/2fa/setup",
+            Some(&token),
+            Some(&json!({})),
+        )
+        .await;
+
+    app.teardown().await;
+
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+}
@@ -1,6 +1,14 @@
 use assert_json_diff::assert_json_include;
-use hyper::{Body, Method, Request};
-use lib::domain::user::User;
+use chrono::{Duration, Utc};
+use hyper::{Body, Method, Request, StatusCode};
+use lib::{
+    configuration::PasswordHashingSettings,
+    domain::user::{Claims, User},
+    utils::{
+        hasher::Hasher,
+        jwt::{decode_token, encode_token, JwtKeys},
+    },
+};
 use serde::Deserialize;
 use serde_json::{json, Value};
 
@@ -9,6 +17,7 @@ use crate::helpers::{app::TestApp, ParseJson};
 #[derive(Deserialize, Debug)]
 pub struct ApiResponse {
     pub token: String,
+    pub refresh_token: String,
     pub user: User,
 }
 
@@ -108,6 +117,48 @@ async fn register_handler_with_validation_errors() {
     );
 }
 
+#[tokio::test]
+async fn register_handler_rejects_passwords_against_policy() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let mut responses = Vec::new();
+    for password in ["short", "test_username_1", "correcthorsebatterystaple"] {
+        let user_input = json!({
+            "email":  "test@email.com",
+            "username": "test_username",
+            "password": password
+        });
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(app.get_http_uri("/api/users/register"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(user_input.to_string()))
+            .expect("could not create request");
+
+        let response = client.request(req).await.expect("could not send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let api_response: Value = response.json_from_body().await;
+        responses.push(api_response["error"]["fields"]["password"].clone());
+    }
+
+    app.teardown().await;
+
+    assert_eq!(
+        responses,
+        vec![
+            json!("must be at least 8 characters"),
+            json!("must not contain your username or email"),
+            json!("appeared in a data breach, choose another one"),
+        ]
+    );
+}
+
 #[tokio::test]
 async fn register_handler_already_registered() {
     let mut app = TestApp::build();
@@ -152,6 +203,43 @@ async fn register_handler_already_registered() {
     );
 }
 
+#[tokio::test]
+async fn concurrent_registrations_conflict() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "username",
+        "password": "test_password"
+    });
+
+    let register = || {
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(app.get_http_uri("/api/users/register"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(user_input.to_string()))
+            .expect("could not create request");
+
+        client.request(req)
+    };
+
+    let (first, second) = tokio::join!(register(), register());
+    let mut statuses = vec![
+        first.expect("could not send request").status(),
+        second.expect("could not send request").status(),
+    ];
+    statuses.sort();
+
+    app.teardown().await;
+
+    assert_eq!(statuses, vec![StatusCode::OK, StatusCode::CONFLICT]);
+}
+
 #[tokio::test]
 async fn login_handler_with_success() {
     let mut app = TestApp::build();
@@ -174,7 +262,7 @@ async fn login_handler_with_success() {
     });
 
     let req = Request::builder()
-        .method(Method::GET)
+        .method(Method::POST)
         .uri(app.get_http_uri("/api/users/login"))
         .header("Content-Type", "application/json")
         .body(Body::from(login_input.to_string()))
@@ -227,7 +315,7 @@ async fn login_handler_failure_with_bad_credentials() {
     });
 
     let req = Request::builder()
-        .method(Method::GET)
+        .method(Method::POST)
         .uri(app.get_http_uri("/api/users/login"))
         .header("Content-Type", "application/json")
         .body(Body::from(login_input.to_string()))
@@ -250,3 +338,752 @@ async fn login_handler_failure_with_bad_credentials() {
         })
     )
 }
+
+#[tokio::test]
+async fn me_handler_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/register"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(user_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+    let registered: ApiResponse = response.json_from_body().await;
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Authorization", format!("Bearer {}", registered.token))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert!(response.status().is_success());
+
+    // Getting json data
+
+    let user: User = response.json_from_body().await;
+
+    assert_eq!(user.id, registered.user.id);
+    assert_eq!(user.username, registered.user.username);
+}
+
+#[tokio::test]
+async fn me_handler_without_token() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(app.get_http_uri("/api/users/me"))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "unauthorized",
+        })
+    )
+}
+
+#[tokio::test]
+async fn me_handler_with_expired_token() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let token = app.create_user(&client, &user_input).await;
+
+    // Re-signing the token of the registered user as expired, so that the
+    // expiry is the only reason to reject it
+    let keys = JwtKeys::from_settings(&app.config.app_settings).unwrap();
+    let now = Utc::now();
+    let claims = Claims {
+        iat: now - Duration::hours(5),
+        exp: now - Duration::hours(1),
+        ..decode_token(&token, &keys).unwrap()
+    };
+    let token = encode_token(&claims, &keys).unwrap();
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn refresh_token_handler_with_success() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/register"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(user_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+    let registered: ApiResponse = response.json_from_body().await;
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/token/refresh"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(
+            json!({ "refresh_token": registered.refresh_token }).to_string(),
+        ))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert!(response.status().is_success());
+
+    // Getting json data
+
+    let refreshed: ApiResponse = response.json_from_body().await;
+
+    assert!(!refreshed.token.is_empty());
+    assert_ne!(refreshed.refresh_token, registered.refresh_token);
+    assert_eq!(refreshed.user.id, registered.user.id);
+}
+
+#[tokio::test]
+async fn refresh_token_handler_reuse_revokes_family() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/register"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(user_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+    let registered: ApiResponse = response.json_from_body().await;
+
+    let refresh = |refresh_token: String| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(app.get_http_uri("/api/users/token/refresh"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(
+                json!({ "refresh_token": refresh_token }).to_string(),
+            ))
+            .expect("could not create request")
+    };
+
+    // Rotating once, then replaying the rotated token
+    let response = client
+        .request(refresh(registered.refresh_token.clone()))
+        .await
+        .expect("could not send request");
+    let rotated: ApiResponse = response.json_from_body().await;
+
+    let reuse_response = client
+        .request(refresh(registered.refresh_token))
+        .await
+        .expect("could not send request");
+
+    // The legitimately rotated token is revoked along with its family
+    let rotated_response = client
+        .request(refresh(rotated.refresh_token))
+        .await
+        .expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(reuse_response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(rotated_response.status(), StatusCode::UNAUTHORIZED);
+
+    // Getting json data
+
+    let api_response: Value = reuse_response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "invalid refresh token",
+        })
+    )
+}
+
+#[tokio::test]
+async fn logout_handler_revokes_session() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/register"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(user_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+    let registered: ApiResponse = response.json_from_body().await;
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/logout"))
+        .header("Authorization", format!("Bearer {}", registered.token))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let logout_response = client.request(req).await.expect("could not send request");
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Authorization", format!("Bearer {}", registered.token))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let me_response = client.request(req).await.expect("could not send request");
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/token/refresh"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(
+            json!({ "refresh_token": registered.refresh_token }).to_string(),
+        ))
+        .expect("could not create request");
+
+    let refresh_response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(logout_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(me_response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(refresh_response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn logout_all_handler_revokes_every_session() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let first_token = app.create_user(&client, &user_input).await;
+
+    let login_input = json!({
+        "username": &user_input["username"],
+        "password": &user_input["password"]
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/login"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(login_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+    let second_session: ApiResponse = response.json_from_body().await;
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/logout/all"))
+        .header("Authorization", format!("Bearer {}", second_session.token))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let logout_response = client.request(req).await.expect("could not send request");
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Authorization", format!("Bearer {}", first_token))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let me_response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(logout_response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(me_response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn update_me_handler_changes_password_and_revokes_sessions() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let token = app.create_user(&client, &user_input).await;
+
+    let update_input = json!({
+        "old_password": "test_password",
+        "new_password": "new_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::PATCH)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::from(update_input.to_string()))
+        .expect("could not create request");
+
+    let update_response = client.request(req).await.expect("could not send request");
+
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .expect("could not create request");
+
+    let me_response = client.request(req).await.expect("could not send request");
+
+    let login_input = json!({
+        "username": "test_username",
+        "password": "new_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/login"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(login_input.to_string()))
+        .expect("could not create request");
+
+    let login_response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert!(update_response.status().is_success());
+    assert_eq!(me_response.status(), StatusCode::UNAUTHORIZED);
+    assert!(login_response.status().is_success());
+}
+
+#[tokio::test]
+async fn update_me_handler_with_wrong_password() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    let token = app.create_user(&client, &user_input).await;
+
+    let update_input = json!({
+        "username": "new_username",
+        "old_password": "wrong_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::PATCH)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::from(update_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert!(response.status().is_client_error());
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "bad credentials",
+        })
+    )
+}
+
+#[tokio::test]
+async fn update_me_handler_with_taken_username() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    app.create_user(
+        &client,
+        &json!({
+            "email":  "taken@email.com",
+            "username": "taken_username",
+            "password": "test_password"
+        }),
+    )
+    .await;
+    let token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "test@email.com",
+                "username": "test_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+
+    let update_input = json!({
+        "username": "taken_username",
+        "old_password": "test_password"
+    });
+
+    let req = Request::builder()
+        .method(Method::PATCH)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::from(update_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Getting json data
+
+    let api_response: Value = response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "message": "username already taken",
+        })
+    )
+}
+
+#[tokio::test]
+async fn usernames_cannot_contain_at_sign() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let token = app
+        .create_user(
+            &client,
+            &json!({
+                "email":  "test@email.com",
+                "username": "test_username",
+                "password": "test_password"
+            }),
+        )
+        .await;
+
+    // Taking the email of another user as username
+    let register_input = json!({
+        "email":  "other@email.com",
+        "username": "test@email.com",
+        "password": "test_password"
+    });
+    let register_req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/register"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(register_input.to_string()))
+        .expect("could not create request");
+    let register_response = client
+        .request(register_req)
+        .await
+        .expect("could not send request");
+
+    let update_input = json!({
+        "username": "other@email.com",
+        "old_password": "test_password"
+    });
+    let update_req = Request::builder()
+        .method(Method::PATCH)
+        .uri(app.get_http_uri("/api/users/me"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::from(update_input.to_string()))
+        .expect("could not create request");
+    let update_response = client
+        .request(update_req)
+        .await
+        .expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(register_response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(update_response.status(), StatusCode::BAD_REQUEST);
+
+    // Getting json data
+
+    let api_response: Value = register_response.json_from_body().await;
+
+    assert_json_include!(
+        actual: api_response,
+        expected: json!({
+            "error": { "fields": { "username": "cannot contain @" } }
+        })
+    )
+}
+
+#[tokio::test]
+async fn login_handler_with_email() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    app.create_user(&client, &user_input).await;
+
+    let login_input = json!({
+        "login": &user_input["email"],
+        "password": &user_input["password"]
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(app.get_http_uri("/api/users/login"))
+        .header("Content-Type", "application/json")
+        .body(Body::from(login_input.to_string()))
+        .expect("could not create request");
+
+    let response = client.request(req).await.expect("could not send request");
+
+    app.teardown().await;
+
+    assert!(response.status().is_success());
+
+    // Getting json data
+
+    let api_response: ApiResponse = response.json_from_body().await;
+
+    assert_eq!(api_response.user.username, "test_username");
+}
+
+#[tokio::test]
+async fn login_handler_unknown_user_looks_like_bad_credentials() {
+    let mut app = TestApp::build();
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    app.create_user(&client, &user_input).await;
+
+    let login = |login_input: Value| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(app.get_http_uri("/api/users/login"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(login_input.to_string()))
+            .expect("could not create request")
+    };
+
+    let unknown_response = client
+        .request(login(json!({
+            "login": "unknown_username",
+            "password": "test_password"
+        })))
+        .await
+        .expect("could not send request");
+
+    let wrong_password_response = client
+        .request(login(json!({
+            "login": "test_username",
+            "password": "wrong_password"
+        })))
+        .await
+        .expect("could not send request");
+
+    app.teardown().await;
+
+    assert_eq!(unknown_response.status(), wrong_password_response.status());
+
+    // Getting json data
+
+    let unknown_body: Value = unknown_response.json_from_body().await;
+    let wrong_password_body: Value = wrong_password_response.json_from_body().await;
+
+    assert_eq!(unknown_body, wrong_password_body);
+}
+
+#[tokio::test]
+async fn login_handler_rehashes_password_made_with_other_settings() {
+    let mut app = TestApp::build();
+    app.config.app_settings.password_hashing.pepper = Some("test-pepper".into());
+    app.start_server().await;
+
+    // Creating client
+    let client = hyper::Client::new();
+
+    let user_input = json!({
+        "email":  "test@email.com",
+        "username": "test_username",
+        "password": "test_password"
+    });
+
+    app.create_user(&client, &user_input).await;
+
+    // Hash made with weaker costs, before the pepper was set
+    let weak_hasher = Hasher::from_settings(&PasswordHashingSettings {
+        memory_cost_kib: 1024,
+        time_cost: 1,
+        parallelism: 1,
+        pepper: None,
+        ..PasswordHashingSettings::default()
+    })
+    .unwrap();
+    let weak_hash = weak_hasher.hash_password(b"test_password").await.unwrap();
+    app.set_password_hash("test_username", &weak_hash).await;
+
+    let login = || {
+        Request::builder()
+            .method(Method::POST)
+            .uri(app.get_http_uri("/api/users/login"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(
+                json!({
+                    "login": "test_username",
+                    "password": "test_password"
+                })
+                .to_string(),
+            ))
+            .expect("could not create request")
+    };
+
+    let response = client
+        .request(login())
+        .await
+        .expect("could not send request");
+    let rehashed = app.password_hash("test_username").await;
+    let second_response = client
+        .request(login())
+        .await
+        .expect("could not send request");
+
+    let hasher = Hasher::from_settings(&app.config.app_settings.password_hashing).unwrap();
+
+    app.teardown().await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(second_response.status(), StatusCode::OK);
+    assert_ne!(rehashed, weak_hash);
+    assert!(!hasher.needs_rehash(&rehashed));
+    assert!(hasher
+        .verify_password(b"test_password", &rehashed)
+        .await
+        .unwrap());
+}